@@ -1,20 +1,31 @@
 use cosmwasm_std::{
-    coins, entry_point, to_json_binary, BalanceResponse, BankMsg, BankQuery, Binary, CosmosMsg,
-    Deps, DepsMut, DistributionMsg, Env, MessageInfo, Order, QuerierWrapper, Reply, Response,
-    StdResult, SubMsg, Uint128,
+    coins, entry_point, to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal,
+    Decimal256, Deps, DepsMut, DistributionMsg, Empty, Env, HexBinary, MessageInfo, Order,
+    QuerierWrapper, Reply, Response, StakingMsg, StdResult, Storage, SubMsg, Uint128,
 };
-use cw2::set_contract_version;
+use cw2::{ensure_from_older_version, set_contract_version};
 use cw_storage_plus::Bound;
+use gov_query::{query_proposal, query_vote_options, GovProposal, ProposalStatus};
 use lsm_types::{
-    Config, ConfigResponse, ExecuteMsg, InstantiateMsg, LsmShareInfo, QueryMsg,
-    RewardIndexResponse, Staker, StakerInfoResponse, StakersResponse, State, TotalStakedResponse,
+    Auction, AuctionParams, Bid, BidsResponse, Config, ConfigResponse, ConfigUpdate,
+    DelegationsResponse, ExchangeRateResponse, ExecuteMsg, FeesResponse, InstantiateMsg,
+    LsmShareInfo, MigrateMsg, PricingModel, QueryMsg, Rental, RentalQuoteResponse, RentalTotal,
+    RentalTotalsResponse, RentalsResponse, RevealedBid, RewardDenomsResponse, RewardIndexResponse,
+    Staker, StakerInfoResponse, StakersResponse, State, TotalStakedResponse, ValidatorDelegation,
+    VotingSession, VotingSessionResponse, WEIGHTED_LOCKER_OPTION,
 };
+use proposal_locker_types::{
+    QueryMsg as LockerQueryMsg, TotalVotingPowerResponse, WeightedVoteOption,
+};
+use sha2::{Digest, Sha256};
 
 use crate::error::ContractError;
 use crate::state::{
-    ActiveClaim, ActiveDeposit, ActiveRental, ActiveVotingSessionCreation, ActiveWithdraw,
-    ACTIVE_CLAIM, ACTIVE_DEPOSIT, ACTIVE_RENTAL, ACTIVE_VOTING_SESSION_CREATION, ACTIVE_WITHDRAW,
-    CONFIG, IS_PAUSED, STAKERS, STATE, VOTING_SESSIONS,
+    rentals, ActiveClaim, ActiveCompound, ActiveDeposit, ActiveRebalance, ActiveRental,
+    ActiveVotingSessionCreation, ACTIVE_CLAIM, ACTIVE_COMPOUND, ACTIVE_DEPOSIT, ACTIVE_REBALANCE,
+    ACTIVE_RENTAL, ACTIVE_VOTING_SESSION_CREATION, AUTO_COMPOUNDERS, BIDS, COMPOUND_CURSOR, CONFIG,
+    IS_PAUSED, LEGACY_STAKERS, LEGACY_STATE, RENTAL_COUNT, RENTAL_TOTALS, STAKERS, STATE,
+    UNHEALTHY_VALIDATORS, VOTING_SESSIONS,
 };
 
 const CONTRACT_NAME: &str = "crates.io:lsm-staking";
@@ -23,6 +34,19 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
 
+/// Maximum number of sealed bids per auction, bounds the gas used by settlement
+const MAX_AUCTION_BIDS: u32 = 100;
+
+/// Maximum number of stakers compounded per Compound call, bounds its gas
+const MAX_COMPOUND_BATCH: usize = 50;
+
+/// Hard caps on the protocol fee rates, the owner can never set them higher
+const MAX_RENTAL_FEE_RATE: Decimal = Decimal::percent(50);
+const MAX_REWARD_FEE_RATE: Decimal = Decimal::percent(25);
+
+/// Hard cap on the keeper tip, bounds the rental income a session withholds from stakers
+const MAX_KEEPER_TIP: Uint128 = Uint128::new(1_000_000);
+
 // Reply IDs
 const REPLY_CLAIM_REWARDS: u64 = 1;
 const REPLY_TOKENIZE_SHARES_RENTAL: u64 = 2;
@@ -30,12 +54,58 @@ const REPLY_TOKENIZE_SHARES_WITHDRAW: u64 = 3;
 const REPLY_CLAIM_REWARDS_DEPOSIT: u64 = 4;
 const REPLY_REDEEM_SHARES_DEPOSIT: u64 = 5;
 const REPLY_INSTANTIATE_LOCKER: u64 = 6;
+const REPLY_TOKENIZE_SHARES_AUCTION: u64 = 7;
+const REPLY_COMPOUND: u64 = 8;
+const REPLY_REBALANCE: u64 = 9;
+
+const VALIDATOR_QUERY_PATH: &str = "/cosmos.staking.v1beta1.Query/Validator";
+
+/// Proto definitions of the staking validator query (simplified, only fields we need)
+mod staking_proto {
+    use prost::Message;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QueryValidatorRequest {
+        #[prost(string, tag = "1")]
+        pub validator_addr: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QueryValidatorResponse {
+        #[prost(message, optional, tag = "1")]
+        pub validator: Option<Validator>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Validator {
+        #[prost(string, tag = "1")]
+        pub operator_address: String,
+        #[prost(bool, tag = "3")]
+        pub jailed: bool,
+    }
+}
 
 /// Payload for locker instantiation reply
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 struct LockerInstantiatePayload {
     proposal_id: u64,
     vote_option: i32,
+    validator: String,
+}
+
+/// Payload for the tokenize reply of an auction settlement (one per winning option and validator)
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+struct AuctionTokenizePayload {
+    proposal_id: u64,
+    vote_option: i32,
+    validator: String,
+}
+
+/// Payload for the tokenize reply of a withdrawal (one per validator tokenized from)
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+struct WithdrawTokenizePayload {
+    withdrawer: Addr,
+    validator: String,
 }
 
 #[entry_point]
@@ -49,16 +119,52 @@ pub fn instantiate(
 
     let owner = deps.api.addr_validate(&msg.owner)?;
 
-    // Verify that the validator exists on chain
+    // Verify that the validators exist on chain
     verify_validator_exists(&deps.querier, &msg.validator)?;
+    let validators = msg.validators.unwrap_or_default();
+    validate_validators(&deps.querier, &msg.validator, &validators)?;
+
+    let rental_pricing = msg.rental_pricing.unwrap_or_default();
+    rental_pricing
+        .validate()
+        .map_err(|e| ContractError::InvalidPricing {
+            reason: e.to_string(),
+        })?;
+    let keeper_tip = msg.keeper_tip.unwrap_or_default();
+    validate_keeper_tip(keeper_tip)?;
+
+    let reward_denoms = msg.reward_denoms.unwrap_or_default();
+    validate_reward_denoms(&msg.staking_denom, &reward_denoms)?;
+
+    let fee_collector = msg
+        .fee_collector
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let rental_fee_rate = msg.rental_fee_rate.unwrap_or_default();
+    validate_fee_rate(rental_fee_rate, MAX_RENTAL_FEE_RATE)?;
+    let reward_fee_rate = msg.reward_fee_rate.unwrap_or_default();
+    validate_fee_rate(reward_fee_rate, MAX_REWARD_FEE_RATE)?;
+    if let Some(max_rent_fraction) = msg.max_rent_fraction {
+        validate_rent_fraction(max_rent_fraction)?;
+    }
 
     let config = Config {
         owner: owner.clone(),
         staking_denom: msg.staking_denom,
         validator: msg.validator.clone(),
+        validators,
         max_cap: msg.max_cap,
         locker_code_id: msg.locker_code_id,
+        rental_pricing,
+        keeper_tip,
+        reward_denoms,
+        fee_collector,
+        rental_fee_rate,
+        reward_fee_rate,
+        fallback_validator: msg.fallback_validator,
+        max_rent_fraction: msg.max_rent_fraction,
     };
+    validate_fallback_validator(&config)?;
 
     CONFIG.save(deps.storage, &config)?;
     STATE.save(deps.storage, &State::new())?;
@@ -70,6 +176,97 @@ pub fn instantiate(
         .add_attribute("validator", msg.validator))
 }
 
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let from_version = ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // Fields added to the config since then are filled with their defaults
+    let config = CONFIG.load(deps.storage)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    // Map the single reward index of older versions onto the staking denom entry
+    let mut migrated_stakers = 0u64;
+    if STATE.load(deps.storage).is_err() {
+        let legacy_state = LEGACY_STATE.load(deps.storage)?;
+        let mut state = State::new();
+        state.total_staked = legacy_state.total_staked;
+        state.reward_indices.insert(
+            config.staking_denom.clone(),
+            legacy_state.global_reward_index,
+        );
+        STATE.save(deps.storage, &state)?;
+
+        let legacy_stakers = LEGACY_STAKERS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (addr, legacy_staker) in legacy_stakers {
+            let mut staker = Staker::new();
+            staker.staked_amount = legacy_staker.staked_amount;
+            staker
+                .reward_indices
+                .insert(config.staking_denom.clone(), legacy_staker.reward_index);
+            STAKERS.save(deps.storage, &addr, &staker)?;
+            migrated_stakers += 1;
+        }
+    }
+
+    // Older versions credited shares 1:1 with tokens, any slashing since then is
+    // detected on the next deposit or withdrawal
+    let mut state = STATE.load(deps.storage)?;
+    if state.total_tokens.is_zero() {
+        state.total_tokens = state.total_staked;
+        STATE.save(deps.storage, &state)?;
+    }
+
+    // Rewrite the voting sessions in the current layout
+    // Older sessions don't record the end of the voting period, without it anyone could
+    // destroy them right away
+    let sessions = VOTING_SESSIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut migrated_sessions = 0u64;
+    for (proposal_id, mut session) in sessions {
+        let mut migrated = false;
+        if session.is_active && session.voting_end_time.nanos() == 0 {
+            if let Ok(Some(proposal)) = query_proposal(&deps.querier, proposal_id) {
+                session.voting_end_time = proposal.voting_end_time;
+            }
+            migrated = true;
+        }
+
+        // Nor the stake deployed in their lockers, each locker holds the stake rented on it.
+        // Without it the rent cap and the released amounts would ignore the older rentals
+        if session.is_active && session.deployed.is_empty() {
+            let lockers: Vec<Addr> = session.all_lockers().cloned().collect();
+            for locker in lockers {
+                let response: StdResult<TotalVotingPowerResponse> = deps
+                    .querier
+                    .query_wasm_smart(&locker, &LockerQueryMsg::TotalVotingPower {});
+                if let Ok(response) = response {
+                    if !response.total_staked.is_zero() {
+                        session.deploy(&locker, response.total_staked);
+                    }
+                }
+            }
+            if session.rented_amount.is_zero() {
+                session.rented_amount = session.deployed_amount();
+            }
+            migrated |= !session.deployed.is_empty();
+        }
+
+        if migrated {
+            migrated_sessions += 1;
+        }
+        VOTING_SESSIONS.save(deps.storage, proposal_id, &session)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", from_version.to_string())
+        .add_attribute("migrated_stakers", migrated_stakers.to_string())
+        .add_attribute("migrated_sessions", migrated_sessions.to_string()))
+}
+
 #[entry_point]
 pub fn execute(
     deps: DepsMut,
@@ -82,14 +279,26 @@ pub fn execute(
         ExecuteMsg::ClaimRewards {} => execute_claim_rewards(deps, env, info),
         ExecuteMsg::DepositRewards {} => execute_deposit_rewards(deps, info),
         ExecuteMsg::Withdraw { amount } => execute_withdraw(deps, env, info, amount),
-        ExecuteMsg::UpdateConfig { owner, max_cap } => {
-            execute_update_config(deps, info, owner, max_cap)
-        }
-        ExecuteMsg::CreateVotingLockers { proposal_id } => {
-            execute_create_voting_lockers(deps, env, info, proposal_id)
+        ExecuteMsg::SetAutoCompound { enabled } => execute_set_auto_compound(deps, info, enabled),
+        ExecuteMsg::Compound {} => execute_compound(deps, env, info),
+        ExecuteMsg::UpdateConfig(update) => execute_update_config(deps, info, *update),
+        ExecuteMsg::WithdrawFees {} => execute_withdraw_fees(deps, info),
+        ExecuteMsg::Rebalance { moves } => execute_rebalance(deps, env, info, moves),
+        ExecuteMsg::CheckValidatorHealth {} => execute_check_validator_health(deps, env),
+        ExecuteMsg::EmergencyRedelegate {} => execute_emergency_redelegate(deps, env, info),
+        ExecuteMsg::CreateVotingLockers {
+            proposal_id,
+            auction,
+            options,
+            weighted,
+        } => {
+            execute_create_voting_lockers(deps, env, info, proposal_id, auction, options, weighted)
         }
         ExecuteMsg::DestroyVotingLockers { proposal_id } => {
-            execute_destroy_voting_lockers(deps, info, proposal_id)
+            execute_destroy_voting_lockers(deps, env, info, proposal_id, false)
+        }
+        ExecuteMsg::EmergencyDestroyVotingLockers { proposal_id } => {
+            execute_destroy_voting_lockers(deps, env, info, proposal_id, true)
         }
         ExecuteMsg::ReturnLsmShares {
             proposal_id,
@@ -98,16 +307,53 @@ pub fn execute(
         ExecuteMsg::RentVotingPower {
             proposal_id,
             vote_option,
-        } => execute_rent_voting_power(deps, env, info, proposal_id, vote_option),
+            min_vp_amount,
+            max_price,
+            allow_partial,
+            validator,
+        } => execute_rent_voting_power(
+            deps,
+            env,
+            info,
+            proposal_id,
+            vote_option,
+            min_vp_amount,
+            max_price,
+            allow_partial,
+            validator,
+        ),
+        ExecuteMsg::CommitBid {
+            proposal_id,
+            commitment,
+        } => execute_commit_bid(deps, env, info, proposal_id, commitment),
+        ExecuteMsg::RevealBid {
+            proposal_id,
+            vote_option,
+            vp_amount,
+            price_per_vp,
+            salt,
+        } => execute_reveal_bid(
+            deps,
+            env,
+            info,
+            proposal_id,
+            RevealedBid {
+                vote_option,
+                vp_amount,
+                price_per_vp,
+            },
+            salt,
+        ),
+        ExecuteMsg::SettleAuction { proposal_id } => execute_settle_auction(deps, env, proposal_id),
     }
 }
 
-/// Deposit LSM shares which will be redeemed and staked
+/// Deposit LSM shares of an accepted validator which will be redeemed and staked
 /// This will:
-/// 1. Claim rewards from the validator and update global_reward_index
+/// 1. Claim rewards from the validators and update the reward indices
 /// 2. Calculate and send any pending rewards to the depositor
-/// 3. Update staker's staked_amount and total_staked with the new deposit
-/// 4. Redeem the LSM shares to add to delegation
+/// 3. Redeem the LSM shares to add to delegation
+/// 4. Mint pool shares for the tokens actually delegated at the exchange rate
 pub fn execute_deposit_lsm_shares(
     deps: DepsMut,
     env: Env,
@@ -119,7 +365,9 @@ pub fn execute_deposit_lsm_shares(
         return Err(ContractError::ContractPaused {});
     }
 
-    let state = STATE.load(deps.storage)?;
+    verify_not_in_emergency(deps.storage)?;
+
+    let mut state = STATE.load(deps.storage)?;
     let config = CONFIG.load(deps.storage)?;
 
     // Verify exactly one token is sent
@@ -138,35 +386,35 @@ pub fn execute_deposit_lsm_shares(
     // Parse and validate LSM denom
     let lsm_info = parse_lsm_denom(&lsm_share.denom)?;
 
-    // Verify validator exists
-    if lsm_info.validator != config.validator {
+    // Verify the shares are of an accepted validator
+    if !config.is_validator(&lsm_info.validator) {
         return Err(ContractError::InvalidValidator {
             validator: lsm_info.validator,
-            expected: config.validator,
+            expected: config.all_validators().join(","),
         });
     }
 
+    // Detect slashing so the deposit is credited at the current exchange rate
+    // Also used to check if any delegation exists (to know if we should claim rewards first)
+    let delegations = query_delegations(&deps.querier, &env)?;
+    let slashed = record_slashing(deps.storage, &mut state, &delegations)?;
+    STATE.save(deps.storage, &state)?;
+
     // Check if adding this amount would exceed max_cap
     if let Some(max_cap) = config.max_cap {
         let new_total = state
-            .total_staked
+            .total_tokens
             .checked_add(lsm_share.amount)
             .map_err(|e| ContractError::Std(e.into()))?;
         if new_total > max_cap {
             return Err(ContractError::MaxCapReached {
                 cap: max_cap,
-                current: state.total_staked,
+                current: state.total_tokens,
                 attempting: lsm_share.amount,
             });
         }
     }
 
-    // Check if delegation exists (to know if we should claim rewards first)
-    let delegation_exists = deps
-        .querier
-        .query_delegation(env.contract.address.clone(), config.validator.clone())?
-        .is_some();
-
     // Store active deposit info for the reply handlers
     ACTIVE_DEPOSIT.save(
         deps.storage,
@@ -177,43 +425,30 @@ pub fn execute_deposit_lsm_shares(
         },
     )?;
 
-    if delegation_exists {
-        // Query current balance before claiming rewards
-        let balance_query: BalanceResponse = deps.querier.query(
-            &BankQuery::Balance {
-                address: env.contract.address.to_string(),
-                denom: config.staking_denom.clone(),
-            }
-            .into(),
-        )?;
-
+    if !delegations.is_empty() {
         // Store active claim state for reward distribution
         ACTIVE_CLAIM.save(
             deps.storage,
             &ActiveClaim {
                 claimer: info.sender.clone(),
-                balance_before: balance_query.amount.amount,
-                global_index_before: state.global_reward_index,
+                balances_before: query_reward_balances(&deps.querier, &env, &config)?,
                 withdraw_amount: None, // This is a deposit, not a withdraw
             },
         )?;
 
         // First, withdraw delegation rewards to update the global index
         // The reply handler will then update state and redeem the LSM shares
-        let withdraw_rewards_msg = SubMsg::reply_on_success(
-            CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward {
-                validator: config.validator.clone(),
-            }),
-            REPLY_CLAIM_REWARDS_DEPOSIT,
-        );
+        let withdraw_rewards_msgs =
+            withdraw_rewards_msgs(&config, &delegations, REPLY_CLAIM_REWARDS_DEPOSIT);
 
         Ok(Response::new()
-            .add_submessage(withdraw_rewards_msg)
+            .add_submessages(withdraw_rewards_msgs)
             .add_attribute("method", "deposit_lsm_shares")
             .add_attribute("sender", info.sender)
             .add_attribute("validator", lsm_info.validator)
             .add_attribute("record_id", lsm_info.record_id)
-            .add_attribute("amount", lsm_share.amount))
+            .add_attribute("amount", lsm_share.amount)
+            .add_attribute("slashed_tokens", slashed))
     } else {
         // This is the first deposit - no delegation exists yet, so skip reward claiming
         // Load or create staker
@@ -221,13 +456,10 @@ pub fn execute_deposit_lsm_shares(
             .may_load(deps.storage, &info.sender)?
             .unwrap_or_else(Staker::new);
 
-        // Add the new deposit to state and staker
+        // Pending rewards are settled before the staked amount changes
+        // The deposit is credited once the redeem reports the tokens actually delegated
         let mut state = STATE.load(deps.storage)?;
-        state.total_staked += lsm_share.amount;
-        staker.staked_amount += lsm_share.amount;
-
-        // Set staker's reward index to the current global index
-        staker.update_index(state.global_reward_index);
+        let (compounded, user_rewards) = settle_rewards(&config, &mut state, &mut staker);
 
         STAKERS.save(deps.storage, &info.sender, &staker)?;
         STATE.save(deps.storage, &state)?;
@@ -239,10 +471,9 @@ pub fn execute_deposit_lsm_shares(
             lsm_share.amount,
         )?;
 
-        // Clean up active deposit after creating the message
-        ACTIVE_DEPOSIT.remove(deps.storage);
-
         Ok(Response::new()
+            .add_messages(send_rewards(&info.sender, user_rewards))
+            .add_messages(delegate_compounded(&config, compounded))
             .add_submessage(SubMsg::reply_on_success(
                 redeem_msg,
                 REPLY_REDEEM_SHARES_DEPOSIT,
@@ -252,6 +483,8 @@ pub fn execute_deposit_lsm_shares(
             .add_attribute("validator", lsm_info.validator)
             .add_attribute("record_id", lsm_info.record_id)
             .add_attribute("amount", lsm_share.amount)
+            .add_attribute("slashed_tokens", slashed)
+            .add_attribute("compounded", compounded)
             .add_attribute("first_deposit", "true"))
     }
 }
@@ -260,7 +493,7 @@ pub fn execute_deposit_lsm_shares(
 /// This will:
 /// 1. Verify user has staked tokens
 /// 2. Query current balance
-/// 3. Withdraw rewards from the single validator
+/// 3. Withdraw rewards from every validator the contract delegates to
 /// 4. In the reply, update global index, calculate user rewards, and distribute to user
 pub fn execute_claim_rewards(
     deps: DepsMut,
@@ -268,46 +501,31 @@ pub fn execute_claim_rewards(
     info: MessageInfo,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
-    let state = STATE.load(deps.storage)?;
 
     // Verify user has staked tokens (we'll calculate rewards in the reply)
     let _staker = STAKERS
         .load(deps.storage, &info.sender)
         .map_err(|_| ContractError::NoRewards {})?;
 
-    // Query current balance before claiming
-    let balance_query: BalanceResponse = deps.querier.query(
-        &BankQuery::Balance {
-            address: env.contract.address.to_string(),
-            denom: config.staking_denom.clone(),
-        }
-        .into(),
-    )?;
-
     // Store active claim state with current global index
     ACTIVE_CLAIM.save(
         deps.storage,
         &ActiveClaim {
             claimer: info.sender.clone(),
-            balance_before: balance_query.amount.amount,
-            global_index_before: state.global_reward_index,
+            balances_before: query_reward_balances(&deps.querier, &env, &config)?,
             withdraw_amount: None, // This is just a claim, not a withdraw
         },
     )?;
 
-    // Create withdraw reward message for the single validator
-    let withdraw_msg = SubMsg::reply_on_success(
-        CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward {
-            validator: config.validator.clone(),
-        }),
-        REPLY_CLAIM_REWARDS,
-    );
+    // Create withdraw reward messages for the validators
+    let delegations = query_delegations(&deps.querier, &env)?;
+    let withdraw_msgs = withdraw_rewards_msgs(&config, &delegations, REPLY_CLAIM_REWARDS);
 
     Ok(Response::new()
-        .add_submessage(withdraw_msg)
+        .add_submessages(withdraw_msgs)
         .add_attribute("method", "claim_rewards")
         .add_attribute("sender", info.sender)
-        .add_attribute("validator", config.validator))
+        .add_attribute("num_validators", delegations.len().to_string()))
 }
 
 /// Deposit additional rewards to be distributed among stakers
@@ -318,33 +536,43 @@ pub fn execute_deposit_rewards(
     let config = CONFIG.load(deps.storage)?;
     let mut state = STATE.load(deps.storage)?;
 
-    // Find the staking token in the sent funds
-    let reward = info
-        .funds
-        .iter()
-        .find(|coin| coin.denom == config.staking_denom)
-        .ok_or(ContractError::InvalidFunds {
-            expected: config.staking_denom.clone(),
-        })?;
+    if info.funds.is_empty() {
+        return Err(ContractError::InvalidFunds {
+            expected: config.staking_denom,
+        });
+    }
 
-    if reward.amount.is_zero() {
+    if info.funds.iter().all(|coin| coin.amount.is_zero()) {
         return Err(ContractError::ZeroAmount {});
     }
 
-    // Update global reward index using the cumulative reward algorithm
-    state.add_rewards(reward.amount);
+    // Only whitelisted denoms are distributed as rewards
+    if let Some(coin) = info
+        .funds
+        .iter()
+        .find(|coin| !config.is_reward_denom(&coin.denom))
+    {
+        return Err(ContractError::InvalidRewardDenom {
+            denom: coin.denom.clone(),
+        });
+    }
+
+    // Update the reward index of each denom using the cumulative reward algorithm
+    for reward in &info.funds {
+        add_staking_rewards(&config, &mut state, &reward.denom, reward.amount);
+    }
     STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
         .add_attribute("method", "deposit_rewards")
         .add_attribute("sender", info.sender)
-        .add_attribute("amount", reward.amount))
+        .add_attribute("amount", coins_attribute(&info.funds)))
 }
 
 /// Withdraw staked tokens
 /// This will:
 /// 1. Automatically claim any pending rewards before withdrawing
-/// 2. Tokenize the delegation into LSM shares
+/// 2. Tokenize the delegations into LSM shares, largest delegation first
 /// 3. Send the LSM shares to the user
 pub fn execute_withdraw(
     deps: DepsMut,
@@ -369,128 +597,29 @@ pub fn execute_withdraw(
         .load(deps.storage, &info.sender)
         .map_err(|_| ContractError::InsufficientStakedAmount {})?;
 
-    // Query the current delegation to get the actual token amount
-    let delegation_response = deps
-        .querier
-        .query_delegation(env.contract.address.clone(), config.validator.clone())?;
-
-    let delegated_tokens = delegation_response
-        .map(|d| d.amount.amount)
-        .unwrap_or(Uint128::zero());
-
-    // Calculate user's share of tokens based on their shares proportion
-    // user_tokens = (delegated_tokens * user_shares) / total_shares
-    // Using Decimal256 for precision
-    let user_available_tokens = if state.total_staked.is_zero() {
-        Uint128::zero()
-    } else {
-        let delegated_decimal = cosmwasm_std::Decimal256::from_ratio(delegated_tokens, 1u128);
-        let user_shares_decimal = cosmwasm_std::Decimal256::from_ratio(staker.staked_amount, 1u128);
-        let total_shares_decimal = cosmwasm_std::Decimal256::from_ratio(state.total_staked, 1u128);
-
-        let user_tokens_decimal = delegated_decimal
-            .checked_mul(user_shares_decimal)
-            .map_err(|e| {
-                ContractError::Std(cosmwasm_std::StdError::generic_err(format!(
-                    "Decimal multiplication error: {}",
-                    e
-                )))
-            })?
-            .checked_div(total_shares_decimal)
-            .map_err(|e| {
-                ContractError::Std(cosmwasm_std::StdError::generic_err(format!(
-                    "Decimal division error: {}",
-                    e
-                )))
-            })?;
-
-        // Convert back to Uint128, rounding down
-        user_tokens_decimal
-            .atomics()
-            .checked_div(cosmwasm_std::Uint256::from(1_000_000_000_000_000_000u128))
-            .map_err(|e| ContractError::Std(e.into()))?
-            .try_into()
-            .map_err(|_| {
-                ContractError::Std(cosmwasm_std::StdError::generic_err(
-                    "Overflow converting to Uint128",
-                ))
-            })?
-    };
+    // Detect slashing so the withdrawal uses the current exchange rate
+    let delegations = query_delegations(&deps.querier, &env)?;
+    let slashed = record_slashing(deps.storage, &mut state, &delegations)?;
 
     // Check if user has enough tokens available
-    if user_available_tokens < amount {
+    if state.tokens_for_shares(staker.staked_amount) < amount {
         return Err(ContractError::InsufficientStakedAmount {});
     }
 
-    // Calculate how many shares to deduct based on the token amount requested
-    // shares_to_deduct = (amount * total_shares) / delegated_tokens
-    // Using Decimal256 for precision
-    let shares_to_deduct = if delegated_tokens.is_zero() {
-        staker.staked_amount // If no delegation, deduct all shares
-    } else {
-        let amount_decimal = cosmwasm_std::Decimal256::from_ratio(amount, 1u128);
-        let total_shares_decimal = cosmwasm_std::Decimal256::from_ratio(state.total_staked, 1u128);
-        let delegated_decimal = cosmwasm_std::Decimal256::from_ratio(delegated_tokens, 1u128);
-
-        let shares_decimal = amount_decimal
-            .checked_mul(total_shares_decimal)
-            .map_err(|e| {
-                ContractError::Std(cosmwasm_std::StdError::generic_err(format!(
-                    "Decimal multiplication error: {}",
-                    e
-                )))
-            })?
-            .checked_div(delegated_decimal)
-            .map_err(|e| {
-                ContractError::Std(cosmwasm_std::StdError::generic_err(format!(
-                    "Decimal division error: {}",
-                    e
-                )))
-            })?;
-
-        // Convert back to Uint128, rounding up to ensure we deduct enough shares
-        let shares_atomics = shares_decimal.atomics();
-        let divisor = cosmwasm_std::Uint256::from(1_000_000_000_000_000_000u128);
-        let quotient = shares_atomics
-            .checked_div(divisor)
-            .map_err(|e| ContractError::Std(e.into()))?;
-        let remainder = shares_atomics
-            .checked_rem(divisor)
-            .map_err(|e| ContractError::Std(e.into()))?;
-
-        // Round up if there's a remainder
-        let shares_u256 = if remainder.is_zero() {
-            quotient
-        } else {
-            quotient
-                .checked_add(cosmwasm_std::Uint256::from(1u128))
-                .map_err(|e| ContractError::Std(e.into()))?
-        };
-
-        shares_u256.try_into().map_err(|_| {
-            ContractError::Std(cosmwasm_std::StdError::generic_err(
-                "Overflow converting to Uint128",
-            ))
-        })?
-    };
+    // Shares to burn for the tokens, rounded up so the remaining stakers never lose tokens
+    let shares_to_deduct = state
+        .shares_for_tokens_ceil(amount)
+        .min(staker.staked_amount);
 
     // Update staker and state BEFORE claiming rewards
     // We need to do this first so the state is correct when we claim
-    staker.staked_amount = staker.staked_amount.saturating_sub(shares_to_deduct);
-    state.total_staked = state.total_staked.saturating_sub(shares_to_deduct);
+    staker.staked_amount -= shares_to_deduct;
+    state.total_staked -= shares_to_deduct;
+    state.total_tokens = state.total_tokens.saturating_sub(amount);
 
     STAKERS.save(deps.storage, &info.sender, &staker)?;
     STATE.save(deps.storage, &state)?;
 
-    // Query current balance before claiming rewards
-    let balance_query: BalanceResponse = deps.querier.query(
-        &BankQuery::Balance {
-            address: env.contract.address.to_string(),
-            denom: config.staking_denom.clone(),
-        }
-        .into(),
-    )?;
-
     // Store active claim state with withdraw info
     // The reply will handle:
     // 1. Update global reward index
@@ -500,37 +629,127 @@ pub fn execute_withdraw(
         deps.storage,
         &ActiveClaim {
             claimer: info.sender.clone(),
-            balance_before: balance_query.amount.amount,
-            global_index_before: state.global_reward_index,
+            balances_before: query_reward_balances(&deps.querier, &env, &config)?,
             withdraw_amount: Some(amount), // This is a withdraw with tokenize
         },
     )?;
 
     // First, withdraw delegation rewards
     // The reply handler will then tokenize shares
-    let withdraw_rewards_msg = SubMsg::reply_on_success(
-        CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward {
-            validator: config.validator.clone(),
-        }),
-        REPLY_CLAIM_REWARDS,
-    );
+    let withdraw_rewards_msgs = withdraw_rewards_msgs(&config, &delegations, REPLY_CLAIM_REWARDS);
 
     Ok(Response::new()
-        .add_submessage(withdraw_rewards_msg)
+        .add_submessages(withdraw_rewards_msgs)
         .add_attribute("method", "withdraw")
         .add_attribute("sender", info.sender.to_string())
         .add_attribute("amount", amount)
         .add_attribute("shares_deducted", shares_to_deduct)
-        .add_attribute("validator", config.validator))
+        .add_attribute("slashed_tokens", slashed))
+}
+
+/// Set whether the staking denom rewards of the caller are compounded into their stake
+/// The pending rewards are settled under the previous setting first
+pub fn execute_set_auto_compound(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    // Settling may compound into the delegation, which is locked during voting sessions
+    if IS_PAUSED.load(deps.storage)? {
+        return Err(ContractError::ContractPaused {});
+    }
+    // Settling may also delegate to the primary validator, which is unsafe in emergency mode
+    verify_not_in_emergency(deps.storage)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    let mut staker = STAKERS
+        .load(deps.storage, &info.sender)
+        .map_err(|_| ContractError::InsufficientStakedAmount {})?;
+
+    let (compounded, paid) = settle_rewards(&config, &mut state, &mut staker);
+    staker.auto_compound = enabled;
+    STAKERS.save(deps.storage, &info.sender, &staker)?;
+    STATE.save(deps.storage, &state)?;
+
+    if enabled {
+        AUTO_COMPOUNDERS.save(deps.storage, &info.sender, &Empty {})?;
+    } else {
+        AUTO_COMPOUNDERS.remove(deps.storage, &info.sender);
+    }
+
+    Ok(Response::new()
+        .add_messages(delegate_compounded(&config, compounded))
+        .add_messages(send_rewards(&info.sender, paid))
+        .add_attribute("method", "set_auto_compound")
+        .add_attribute("sender", info.sender)
+        .add_attribute("enabled", enabled.to_string())
+        .add_attribute("compounded", compounded))
+}
+
+/// Compound the pending rewards of the next batch of auto-compounding stakers
+/// The validator rewards are claimed first so they are compounded too
+/// Compounded rewards are delegated to the primary validator, so it is blocked in emergency mode
+pub fn execute_compound(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // Compounding changes the delegation, which is locked during voting sessions
+    let is_paused = IS_PAUSED.load(deps.storage)?;
+    if is_paused {
+        return Err(ContractError::ContractPaused {});
+    }
+    verify_not_in_emergency(deps.storage)?;
+
+    let config = CONFIG.load(deps.storage)?;
+
+    let delegations = query_delegations(&deps.querier, &env)?;
+
+    // Nothing to claim, compound the rewards already received
+    if delegations.is_empty() {
+        let response = compound_batch(deps, &config)?;
+        return Ok(response
+            .add_attribute("method", "compound")
+            .add_attribute("sender", info.sender));
+    }
+
+    ACTIVE_COMPOUND.save(
+        deps.storage,
+        &ActiveCompound {
+            balances_before: query_reward_balances(&deps.querier, &env, &config)?,
+        },
+    )?;
+
+    // The reply handler compounds once the rewards are received
+    let withdraw_rewards_msgs = withdraw_rewards_msgs(&config, &delegations, REPLY_COMPOUND);
+
+    Ok(Response::new()
+        .add_submessages(withdraw_rewards_msgs)
+        .add_attribute("method", "compound")
+        .add_attribute("sender", info.sender))
 }
 
 /// Update contract configuration (owner only)
 pub fn execute_update_config(
     deps: DepsMut,
     info: MessageInfo,
-    owner: Option<String>,
-    max_cap: Option<Uint128>,
+    update: ConfigUpdate,
 ) -> Result<Response, ContractError> {
+    let ConfigUpdate {
+        owner,
+        max_cap,
+        rental_pricing,
+        keeper_tip,
+        reward_denoms,
+        fee_collector,
+        rental_fee_rate,
+        reward_fee_rate,
+        validators,
+        fallback_validator,
+        clear_fallback_validator,
+        max_rent_fraction,
+    } = update;
     let mut config = CONFIG.load(deps.storage)?;
 
     // Check if sender is owner
@@ -551,144 +770,589 @@ pub fn execute_update_config(
         response = response.add_attribute("new_max_cap", new_max_cap.to_string());
     }
 
-    CONFIG.save(deps.storage, &config)?;
-
-    Ok(response)
-}
-
-/// Create voting lockers for a governance proposal (owner only)
-/// This queries the proposal to get vote options and creates a locker for each
-pub fn execute_create_voting_lockers(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
-    proposal_id: u64,
-) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-
-    // Only owner can create voting lockers
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized {});
+    if let Some(new_rental_pricing) = rental_pricing {
+        new_rental_pricing
+            .validate()
+            .map_err(|e| ContractError::InvalidPricing {
+                reason: e.to_string(),
+            })?;
+        config.rental_pricing = new_rental_pricing;
+        response = response.add_attribute("rental_pricing_updated", "true");
     }
 
-    // Check if voting session already exists for this proposal
-    if VOTING_SESSIONS.has(deps.storage, proposal_id) {
-        return Err(ContractError::VotingSessionExists { proposal_id });
+    if let Some(new_keeper_tip) = keeper_tip {
+        validate_keeper_tip(new_keeper_tip)?;
+        config.keeper_tip = new_keeper_tip;
+        response = response.add_attribute("new_keeper_tip", new_keeper_tip);
     }
 
-    // Check if there's already a voting session creation in progress
-    if ACTIVE_VOTING_SESSION_CREATION
-        .may_load(deps.storage)?
-        .is_some()
-    {
-        return Err(ContractError::InvalidLsmShares {
-            reason: "Another voting session creation is already in progress".to_string(),
-        });
+    // Removing a denom stops new rewards in it, pending rewards can still be claimed
+    if let Some(new_reward_denoms) = reward_denoms {
+        validate_reward_denoms(&config.staking_denom, &new_reward_denoms)?;
+        response = response.add_attribute("new_reward_denoms", new_reward_denoms.join(","));
+        config.reward_denoms = new_reward_denoms;
     }
 
-    // Query the governance proposal to get vote options
-    // For Cosmos SDK governance, standard options are: 1=Yes, 2=Abstain, 3=No, 4=NoWithVeto
-    // We'll create a locker for each option
-    let vote_options = vec![1i32, 2i32, 3i32, 4i32]; // Yes, Abstain, No, NoWithVeto
+    if let Some(fee_collector) = fee_collector {
+        let new_fee_collector = deps.api.addr_validate(&fee_collector)?;
+        config.fee_collector = Some(new_fee_collector.clone());
+        response = response.add_attribute("new_fee_collector", new_fee_collector);
+    }
 
-    use cosmwasm_std::WasmMsg;
-    use proposal_locker_types::InstantiateMsg as LockerInstantiateMsg;
+    if let Some(new_rental_fee_rate) = rental_fee_rate {
+        validate_fee_rate(new_rental_fee_rate, MAX_RENTAL_FEE_RATE)?;
+        config.rental_fee_rate = new_rental_fee_rate;
+        response = response.add_attribute("new_rental_fee_rate", new_rental_fee_rate.to_string());
+    }
 
-    // Initialize the active voting session creation tracker
-    let active_creation = ActiveVotingSessionCreation {
-        proposal_id,
-        expected_lockers: vote_options.len() as u32,
-        created_count: 0,
-        locker_addresses: Vec::new(),
-    };
-    ACTIVE_VOTING_SESSION_CREATION.save(deps.storage, &active_creation)?;
+    if let Some(new_reward_fee_rate) = reward_fee_rate {
+        validate_fee_rate(new_reward_fee_rate, MAX_REWARD_FEE_RATE)?;
+        config.reward_fee_rate = new_reward_fee_rate;
+        response = response.add_attribute("new_reward_fee_rate", new_reward_fee_rate.to_string());
+    }
 
-    let mut submessages: Vec<SubMsg> = Vec::new();
+    // Removing a validator stops new deposits of its shares, its delegation can still be withdrawn
+    if let Some(new_validators) = validators {
+        validate_validators(&deps.querier, &config.validator, &new_validators)?;
+        response = response.add_attribute("new_validators", new_validators.join(","));
+        config.validators = new_validators;
+    }
 
-    // Create a locker for each vote option
-    for vote_option in &vote_options {
-        let locker_init_msg = LockerInstantiateMsg {
-            proposal_id,
-            vote_option: *vote_option,
-            validator: config.validator.clone(),
-            manager: env.contract.address.to_string(),
-        };
+    if clear_fallback_validator {
+        if fallback_validator.is_some() {
+            return Err(ContractError::ConflictingFallbackValidator {});
+        }
+        response = response.add_attribute("new_fallback_validator", "none");
+        config.fallback_validator = None;
+    }
+    if let Some(new_fallback_validator) = fallback_validator {
+        response = response.add_attribute("new_fallback_validator", &new_fallback_validator);
+        config.fallback_validator = Some(new_fallback_validator);
+    }
+    validate_fallback_validator(&config)?;
+
+    // Rentals already made on an active proposal are kept when the fraction is lowered
+    if let Some(new_max_rent_fraction) = max_rent_fraction {
+        validate_rent_fraction(new_max_rent_fraction)?;
+        config.max_rent_fraction = Some(new_max_rent_fraction);
+        response =
+            response.add_attribute("new_max_rent_fraction", new_max_rent_fraction.to_string());
+    }
 
-        // Use a unique label for each locker
-        let label = format!("proposal_{}_option_{}", proposal_id, vote_option);
+    CONFIG.save(deps.storage, &config)?;
 
-        let instantiate_msg = WasmMsg::Instantiate {
-            admin: Some(env.contract.address.to_string()),
-            code_id: config.locker_code_id,
-            msg: to_json_binary(&locker_init_msg)?,
-            funds: vec![],
-            label,
-        };
+    Ok(response)
+}
 
-        // Create payload with the vote option
-        let payload = LockerInstantiatePayload {
-            proposal_id,
-            vote_option: *vote_option,
-        };
+/// Send the accrued protocol fees to the fee collector (owner or fee collector only)
+pub fn execute_withdraw_fees(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let fee_collector = config.fee_collector();
 
-        // Create SubMsg with reply and payload
-        let submsg =
-            SubMsg::reply_on_success(CosmosMsg::Wasm(instantiate_msg), REPLY_INSTANTIATE_LOCKER)
-                .with_payload(to_json_binary(&payload)?);
+    if info.sender != config.owner && info.sender != fee_collector {
+        return Err(ContractError::Unauthorized {});
+    }
 
-        submessages.push(submsg);
+    let mut state = STATE.load(deps.storage)?;
+    let fees = accrued_fees(&state);
+    if fees.is_empty() {
+        return Err(ContractError::NoFees {});
     }
 
-    // Set contract to paused
-    IS_PAUSED.save(deps.storage, &true)?;
+    state.accrued_fees.clear();
+    STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
-        .add_submessages(submessages)
-        .add_attribute("method", "create_voting_lockers")
-        .add_attribute("proposal_id", proposal_id.to_string())
-        .add_attribute("num_lockers", vote_options.len().to_string()))
+        .add_message(BankMsg::Send {
+            to_address: fee_collector.to_string(),
+            amount: fees.clone(),
+        })
+        .add_attribute("method", "withdraw_fees")
+        .add_attribute("fee_collector", fee_collector)
+        .add_attribute("amount", coins_attribute(&fees)))
 }
 
-/// Destroy voting lockers for a governance proposal (owner only)
-/// This will call destroy on each locker and unpause if no other voting sessions are active
-/// The proposal must be finished (PASSED, REJECTED, FAILED) or no longer exist on-chain
-pub fn execute_destroy_voting_lockers(
+/// Move stake between validators by redelegation (owner only)
+/// Staker shares are unchanged, only the validators holding the delegated tokens change
+/// The rewards withdrawn by the redelegations are added to the reward indices in the reply
+pub fn execute_rebalance(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    proposal_id: u64,
+    moves: Vec<(String, String, Uint128)>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    // Only owner can destroy voting lockers
     if info.sender != config.owner {
         return Err(ContractError::Unauthorized {});
     }
 
-    // Verify proposal is finished or doesn't exist anymore
-    verify_proposal_finished(&deps.querier, proposal_id)?;
+    // Lockers hold tokenized stake of the validators during a voting session
+    let is_paused = IS_PAUSED.load(deps.storage)?;
+    if is_paused {
+        return Err(ContractError::ContractPaused {});
+    }
 
-    // Load the voting session
-    let mut voting_session = VOTING_SESSIONS
+    if moves.is_empty() {
+        return Err(ContractError::InvalidRebalance {
+            reason: "At least one move is required".to_string(),
+        });
+    }
+
+    // Stake still locked by an earlier redelegation can't be redelegated again
+    let mut redelegatable: Vec<(String, Uint128)> = Vec::new();
+    for (from, to, amount) in &moves {
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmount {});
+        }
+        if from == to {
+            return Err(ContractError::InvalidRebalance {
+                reason: format!("Cannot redelegate from {} to itself", from),
+            });
+        }
+        if !config.is_validator(to) {
+            return Err(ContractError::InvalidValidator {
+                validator: to.clone(),
+                expected: config.all_validators().join(","),
+            });
+        }
+
+        let index = match redelegatable.iter().position(|(v, _)| v == from) {
+            Some(index) => index,
+            None => {
+                let available = deps
+                    .querier
+                    .query_delegation(env.contract.address.clone(), from.clone())?
+                    .map(|delegation| delegation.can_redelegate.amount)
+                    .unwrap_or_default();
+                redelegatable.push((from.clone(), available));
+                redelegatable.len() - 1
+            }
+        };
+        let available = &mut redelegatable[index].1;
+        if *amount > *available {
+            return Err(ContractError::RedelegationLimit {
+                validator: from.clone(),
+                available: *available,
+                required: *amount,
+            });
+        }
+        *available -= *amount;
+    }
+
+    let submessages = redelegate(deps, &env, &config, &moves)?;
+
+    Ok(Response::new()
+        .add_submessages(submessages)
+        .add_attribute("method", "rebalance")
+        .add_attribute("num_moves", moves.len().to_string()))
+}
+
+/// Check whether the validators are jailed, callable by anyone
+/// Enters emergency mode if any validator is unhealthy and lifts it once all are healthy again
+/// Also records any slashing, so the exchange rate is current
+pub fn execute_check_validator_health(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let delegations = query_delegations(&deps.querier, &env)?;
+    let mut state = STATE.load(deps.storage)?;
+    let slashed = record_slashing(deps.storage, &mut state, &delegations)?;
+    STATE.save(deps.storage, &state)?;
+
+    // Validators no longer accepted may still hold part of the stake
+    let mut validators = config.all_validators();
+    for (validator, _) in delegations {
+        if !validators.contains(&validator) {
+            validators.push(validator);
+        }
+    }
+
+    let mut unhealthy = Vec::new();
+    for validator in validators {
+        if !is_validator_healthy(&deps.querier, &validator)? {
+            unhealthy.push(validator);
+        }
+    }
+
+    if unhealthy.is_empty() {
+        UNHEALTHY_VALIDATORS.remove(deps.storage);
+    } else {
+        UNHEALTHY_VALIDATORS.save(deps.storage, &unhealthy)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "check_validator_health")
+        .add_attribute("emergency", (!unhealthy.is_empty()).to_string())
+        .add_attribute("unhealthy_validators", unhealthy.join(","))
+        .add_attribute("slashed_tokens", slashed))
+}
+
+/// Redelegate the stake of the unhealthy validators to the fallback validator
+/// Only in emergency mode, callable by the owner or a staker, and like Rebalance not while
+/// a voting session holds the delegations in its lockers
+pub fn execute_emergency_redelegate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if IS_PAUSED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::ContractPaused {});
+    }
+
+    let unhealthy = UNHEALTHY_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if unhealthy.is_empty() {
+        return Err(ContractError::NotInEmergency {});
+    }
+
+    let is_staker = STAKERS
+        .may_load(deps.storage, &info.sender)?
+        .is_some_and(|staker| !staker.staked_amount.is_zero());
+    if info.sender != config.owner && !is_staker {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // The configured fallback validator, or else the first healthy accepted validator
+    let fallback = match &config.fallback_validator {
+        Some(fallback) => Some(fallback.clone()),
+        None => config
+            .all_validators()
+            .into_iter()
+            .find(|validator| !unhealthy.contains(validator)),
+    }
+    .filter(|fallback| !unhealthy.contains(fallback))
+    .ok_or(ContractError::NoFallbackValidator {})?;
+
+    // Stake still locked by an earlier redelegation stays where it is
+    let mut moves: Vec<(String, String, Uint128)> = Vec::new();
+    for validator in &unhealthy {
+        let redelegatable = deps
+            .querier
+            .query_delegation(env.contract.address.clone(), validator.clone())?
+            .map(|delegation| delegation.can_redelegate.amount)
+            .unwrap_or_default();
+        if !redelegatable.is_zero() {
+            moves.push((validator.clone(), fallback.clone(), redelegatable));
+        }
+    }
+
+    if moves.is_empty() {
+        return Err(ContractError::InvalidRebalance {
+            reason: "Nothing can be redelegated from the unhealthy validators".to_string(),
+        });
+    }
+
+    let submessages = redelegate(deps, &env, &config, &moves)?;
+
+    Ok(Response::new()
+        .add_submessages(submessages)
+        .add_attribute("method", "emergency_redelegate")
+        .add_attribute("sender", info.sender)
+        .add_attribute("fallback_validator", fallback)
+        .add_attribute("num_moves", moves.len().to_string()))
+}
+
+/// Redelegation messages for the moves, only the last one replies once all
+/// the rewards withdrawn by the redelegations are received
+fn redelegate(
+    deps: DepsMut,
+    env: &Env,
+    config: &Config,
+    moves: &[(String, String, Uint128)],
+) -> Result<Vec<SubMsg>, ContractError> {
+    ACTIVE_REBALANCE.save(
+        deps.storage,
+        &ActiveRebalance {
+            balances_before: query_reward_balances(&deps.querier, env, config)?,
+        },
+    )?;
+
+    let last = moves.len() - 1;
+    Ok(moves
+        .iter()
+        .enumerate()
+        .map(|(i, (from, to, amount))| {
+            let msg = CosmosMsg::Staking(StakingMsg::Redelegate {
+                src_validator: from.clone(),
+                dst_validator: to.clone(),
+                amount: Coin::new(*amount, &config.staking_denom),
+            });
+            if i == last {
+                SubMsg::reply_on_success(msg, REPLY_REBALANCE)
+            } else {
+                SubMsg::new(msg)
+            }
+        })
+        .collect())
+}
+
+/// Create voting lockers for a governance proposal (owner only)
+/// This queries the proposal to get vote options and creates a locker for each,
+/// for the primary validator and every other accepted validator the contract delegates to
+pub fn execute_create_voting_lockers(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+    auction: Option<AuctionParams>,
+    options: Option<Vec<i32>>,
+    weighted: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Only owner can create voting lockers
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // The proposal must be accepting votes for the lockers to be of any use
+    let proposal = verify_proposal_in_voting(&deps.querier, &env, proposal_id)?;
+    let voting_end_time = proposal.voting_end_time;
+
+    if let Some(params) = &auction {
+        if params.commit_duration == 0 || params.reveal_duration == 0 {
+            return Err(ContractError::InvalidAuction {
+                reason: "Commit and reveal durations must be greater than zero".to_string(),
+            });
+        }
+        let reveal_end = env
+            .block
+            .time
+            .plus_seconds(params.commit_duration)
+            .plus_seconds(params.reveal_duration);
+        if reveal_end >= voting_end_time {
+            return Err(ContractError::InvalidAuction {
+                reason: "Auction must be settled before the end of the voting period".to_string(),
+            });
+        }
+    }
+
+    // Check if voting session already exists for this proposal
+    if VOTING_SESSIONS.has(deps.storage, proposal_id) {
+        return Err(ContractError::VotingSessionExists { proposal_id });
+    }
+
+    // Check if there's already a voting session creation in progress
+    if ACTIVE_VOTING_SESSION_CREATION
+        .may_load(deps.storage)?
+        .is_some()
+    {
+        return Err(ContractError::InvalidLsmShares {
+            reason: "Another voting session creation is already in progress".to_string(),
+        });
+    }
+
+    // Query the vote options of the proposal
+    // The owner may restrict the session to a subset of them
+    let proposal_options = query_vote_options(&deps.querier, proposal_id)?;
+    let vote_options = match options {
+        Some(options) => {
+            if options.is_empty() {
+                return Err(ContractError::InvalidVoteOptions {
+                    reason: "At least one vote option is required".to_string(),
+                });
+            }
+            for (i, option) in options.iter().enumerate() {
+                if !proposal_options.contains(option) {
+                    return Err(ContractError::InvalidVoteOptions {
+                        reason: format!(
+                            "Option {} is not a vote option of proposal {}",
+                            option, proposal_id
+                        ),
+                    });
+                }
+                if options[..i].contains(option) {
+                    return Err(ContractError::InvalidVoteOptions {
+                        reason: format!("Duplicate vote option {}", option),
+                    });
+                }
+            }
+            options
+        }
+        None => proposal_options,
+    };
+
+    use cosmwasm_std::WasmMsg;
+    use proposal_locker_types::InstantiateMsg as LockerInstantiateMsg;
+
+    // Create a locker for each vote option, or a single one splitting its vote
+    // evenly between the options until the first rental
+    let lockers: Vec<(i32, Option<Vec<WeightedVoteOption>>)> = if weighted {
+        let even_amounts = vote_options.iter().map(|option| (*option, Uint128::one()));
+        vec![(
+            WEIGHTED_LOCKER_OPTION,
+            Some(vote_weights(even_amounts.collect())),
+        )]
+    } else {
+        vote_options.iter().map(|option| (*option, None)).collect()
+    };
+
+    // Each locker can only hold the voting power of a single validator
+    let delegations = query_delegations(&deps.querier, &env)?;
+    let validators: Vec<String> = std::iter::once(config.validator.clone())
+        .chain(
+            config
+                .validators
+                .iter()
+                .filter(|validator| delegations.iter().any(|(v, _)| v == *validator))
+                .cloned(),
+        )
+        .collect();
+
+    // Initialize the active voting session creation tracker
+    let active_creation = ActiveVotingSessionCreation {
+        proposal_id,
+        expected_lockers: (lockers.len() * validators.len()) as u32,
+        created_count: 0,
+        locker_addresses: Vec::new(),
+        validator_lockers: Vec::new(),
+        auction: auction.clone(),
+        voting_end_time,
+        weighted_options: weighted.then(|| vote_options.clone()),
+    };
+    ACTIVE_VOTING_SESSION_CREATION.save(deps.storage, &active_creation)?;
+
+    let mut submessages: Vec<SubMsg> = Vec::new();
+
+    for validator in &validators {
+        for (vote_option, weights) in &lockers {
+            let vote_option = *vote_option;
+            let locker_init_msg = LockerInstantiateMsg {
+                proposal_id,
+                vote_option,
+                validator: validator.clone(),
+                manager: env.contract.address.to_string(),
+                staking_denom: config.staking_denom.clone(),
+                weights: weights.clone(),
+            };
+
+            // Use a unique label for each locker
+            let mut label = if weighted {
+                format!("proposal_{}_weighted", proposal_id)
+            } else {
+                format!("proposal_{}_option_{}", proposal_id, vote_option)
+            };
+            if *validator != config.validator {
+                label = format!("{}_{}", label, validator);
+            }
+
+            let instantiate_msg = WasmMsg::Instantiate {
+                admin: Some(env.contract.address.to_string()),
+                code_id: config.locker_code_id,
+                msg: to_json_binary(&locker_init_msg)?,
+                funds: vec![],
+                label,
+            };
+
+            // Create payload with the vote option and validator
+            let payload = LockerInstantiatePayload {
+                proposal_id,
+                vote_option,
+                validator: validator.clone(),
+            };
+
+            // Create SubMsg with reply and payload
+            let submsg = SubMsg::reply_on_success(
+                CosmosMsg::Wasm(instantiate_msg),
+                REPLY_INSTANTIATE_LOCKER,
+            )
+            .with_payload(to_json_binary(&payload)?);
+
+            submessages.push(submsg);
+        }
+    }
+
+    // Set contract to paused
+    IS_PAUSED.save(deps.storage, &true)?;
+
+    let submessages_len = submessages.len();
+    Ok(Response::new()
+        .add_submessages(submessages)
+        .add_attribute("method", "create_voting_lockers")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("num_lockers", submessages_len.to_string())
+        .add_attribute("num_validators", validators.len().to_string())
+        .add_attribute("weighted", weighted.to_string())
+        .add_attribute("auction", auction.is_some().to_string())
+        .add_attribute("voting_end_time", voting_end_time.seconds().to_string()))
+}
+
+/// Destroy voting lockers for a governance proposal
+/// This will call destroy on each locker and unpause if no other voting sessions are active
+/// The proposal must be finished (PASSED, REJECTED, FAILED), no longer exist on-chain,
+/// or have passed its voting end time
+/// Anyone can call this so an inactive owner can't keep the contract paused,
+/// a caller other than the owner is paid the keeper reserve of the session
+pub fn execute_destroy_voting_lockers(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+    emergency: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Only owner can destroy before the proposal is finished
+    if emergency && info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // Load the voting session
+    let mut voting_session = VOTING_SESSIONS
         .load(deps.storage, proposal_id)
         .map_err(|_| ContractError::VotingSessionNotFound { proposal_id })?;
 
+    if !voting_session.is_active {
+        return Err(ContractError::VotingSessionInactive { proposal_id });
+    }
+
+    // Verify proposal is finished or doesn't exist anymore
+    // The gov module may not have processed the proposal yet when its end time has passed
+    if !emergency && env.block.time < voting_session.voting_end_time {
+        verify_proposal_finished(&deps.querier, proposal_id)?;
+    }
+
     use cosmwasm_std::WasmMsg;
     use proposal_locker_types::ExecuteMsg as LockerExecuteMsg;
 
     let mut messages: Vec<CosmosMsg> = Vec::new();
 
     // Call Destroy on each locker
-    for (_vote_option, locker_addr) in &voting_session.locker_addresses {
+    let locker_msg = if emergency {
+        LockerExecuteMsg::EmergencyDestroy {}
+    } else {
+        LockerExecuteMsg::Destroy {}
+    };
+    for locker_addr in voting_session.all_lockers() {
         let destroy_msg = WasmMsg::Execute {
             contract_addr: locker_addr.to_string(),
-            msg: to_json_binary(&LockerExecuteMsg::Destroy {})?,
+            msg: to_json_binary(&locker_msg)?,
             funds: vec![],
         };
         messages.push(CosmosMsg::Wasm(destroy_msg));
     }
 
+    // Tip the keeper from the session reserve, or give it back to stakers if the owner destroys
+    let keeper_tip = voting_session.keeper_reserve;
+    if !keeper_tip.is_zero() {
+        if info.sender == config.owner {
+            let mut state = STATE.load(deps.storage)?;
+            add_rental_income(&config, &mut state, keeper_tip);
+            STATE.save(deps.storage, &state)?;
+        } else {
+            messages.push(CosmosMsg::Bank(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: coins(keeper_tip.u128(), &config.staking_denom),
+            }));
+        }
+    }
+
     // Mark voting session as inactive
     voting_session.is_active = false;
+    voting_session.keeper_reserve = Uint128::zero();
     VOTING_SESSIONS.save(deps.storage, proposal_id, &voting_session)?;
 
     // Check if there are any other active voting sessions
@@ -703,37 +1367,27 @@ pub fn execute_destroy_voting_lockers(
         });
 
     // Only unpause if no other active sessions exist
+    // Otherwise the contract stays paused until the last session is destroyed
     if !has_active_sessions {
         IS_PAUSED.save(deps.storage, &false)?;
-    } else {
-        // Count active sessions for error reporting
-        let active_count = VOTING_SESSIONS
-            .range(deps.storage, None, None, Order::Ascending)
-            .filter(|result| {
-                if let Ok((_id, session)) = result {
-                    session.is_active
-                } else {
-                    false
-                }
-            })
-            .count() as u64;
-
-        return Err(ContractError::CannotUnpause { active_count });
     }
 
     Ok(Response::new()
         .add_messages(messages)
         .add_attribute("method", "destroy_voting_lockers")
         .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("emergency", emergency.to_string())
+        .add_attribute("keeper", info.sender)
+        .add_attribute("keeper_tip", keeper_tip)
         .add_attribute(
             "num_lockers",
-            voting_session.locker_addresses.len().to_string(),
+            voting_session.all_lockers().count().to_string(),
         )
-        .add_attribute("unpaused", "true"))
+        .add_attribute("unpaused", (!has_active_sessions).to_string()))
 }
 
 /// Return LSM shares from a voting locker
-/// This redeems the shares WITHOUT modifying total_staked or global_reward_index
+/// This redeems the shares WITHOUT modifying total_staked or the reward indices
 /// because these shares were already counted when the locker was created
 pub fn execute_return_lsm_shares(
     deps: DepsMut,
@@ -745,30 +1399,10 @@ pub fn execute_return_lsm_shares(
     let config = CONFIG.load(deps.storage)?;
 
     // Load the voting session
-    let voting_session = VOTING_SESSIONS
+    let mut voting_session = VOTING_SESSIONS
         .load(deps.storage, proposal_id)
         .map_err(|_| ContractError::VotingSessionNotFound { proposal_id })?;
 
-    // Verify that the sender is the registered locker for this proposal and vote option
-    let expected_locker = voting_session
-        .locker_addresses
-        .iter()
-        .find(|(option, _)| *option == vote_option)
-        .map(|(_, addr)| addr)
-        .ok_or(ContractError::InvalidLocker {
-            sender: info.sender.to_string(),
-            proposal_id,
-            vote_option,
-        })?;
-
-    if info.sender != *expected_locker {
-        return Err(ContractError::InvalidLocker {
-            sender: info.sender.to_string(),
-            proposal_id,
-            vote_option,
-        });
-    }
-
     // Verify exactly one token is sent
     if info.funds.len() != 1 {
         return Err(ContractError::InvalidLsmShares {
@@ -785,14 +1419,36 @@ pub fn execute_return_lsm_shares(
     // Parse and validate LSM denom
     let lsm_info = parse_lsm_denom(&lsm_share.denom)?;
 
-    // Verify validator matches
-    if lsm_info.validator != config.validator {
-        return Err(ContractError::InvalidValidator {
-            validator: lsm_info.validator,
-            expected: config.validator,
+    // Verify that the sender is the locker registered for this proposal, vote option
+    // and validator of the shares
+    // Lockers return their shares under the option they were created with, which is
+    // WEIGHTED_LOCKER_OPTION for the locker of a weighted session
+    let registered = if lsm_info.validator == config.validator {
+        voting_session
+            .locker_addresses
+            .iter()
+            .any(|(option, addr)| *option == vote_option && *addr == info.sender)
+    } else {
+        voting_session
+            .validator_lockers
+            .iter()
+            .any(|(validator, option, addr)| {
+                *validator == lsm_info.validator && *option == vote_option && *addr == info.sender
+            })
+    };
+
+    if !registered {
+        return Err(ContractError::InvalidLocker {
+            sender: info.sender.to_string(),
+            proposal_id,
+            vote_option,
         });
     }
 
+    // The locker returns all of its stake at once, so its deployment is over
+    let released = voting_session.release(&info.sender);
+    VOTING_SESSIONS.save(deps.storage, proposal_id, &voting_session)?;
+
     // Create MsgRedeemTokensForShares message
     // IMPORTANT: We do NOT update total_staked or any state because these shares
     // were already counted in the contract's total before being sent to the locker
@@ -808,32 +1464,71 @@ pub fn execute_return_lsm_shares(
         .add_attribute("locker", info.sender)
         .add_attribute("proposal_id", proposal_id.to_string())
         .add_attribute("vote_option", vote_option.to_string())
-        .add_attribute("amount", lsm_share.amount))
+        .add_attribute("amount", lsm_share.amount)
+        .add_attribute("released", released))
 }
 
 /// Rent voting power for a governance proposal
-/// Receives ATOM in funds, calculates VP amount, tokenizes shares, and deposits to locker
-/// VP_PRICE: 1 VP = 0.1 ATOM (hardcoded for now)
+/// Receives ATOM in funds, calculates VP amount from the proposal's pricing model,
+/// tokenizes shares of the chosen validator, or of the session validator with the
+/// most delegated tokens, and deposits to its locker
+#[allow(clippy::too_many_arguments)]
 pub fn execute_rent_voting_power(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     proposal_id: u64,
     vote_option: i32,
+    min_vp_amount: Option<Uint128>,
+    max_price: Option<Decimal256>,
+    allow_partial: bool,
+    validator: Option<String>,
 ) -> Result<Response, ContractError> {
+    verify_not_in_emergency(deps.storage)?;
+
     let config = CONFIG.load(deps.storage)?;
 
     // Verify voting session exists for this proposal
-    let voting_session = VOTING_SESSIONS
+    let mut voting_session = VOTING_SESSIONS
         .load(deps.storage, proposal_id)
         .map_err(|_| ContractError::NoVotingSession { proposal_id })?;
 
+    // The lockers can no longer vote once destroyed or past the voting period
+    if !voting_session.is_active {
+        return Err(ContractError::VotingSessionInactive { proposal_id });
+    }
+    if env.block.time >= voting_session.voting_end_time {
+        return Err(ContractError::ProposalVotingEnded { proposal_id });
+    }
+
+    // Auction sessions only allocate voting power through sealed bids
+    if voting_session.auction.is_some() {
+        return Err(ContractError::AuctionMode { proposal_id });
+    }
+
+    // Pick the validator the voting power is tokenized from
+    let delegations = session_delegations(&deps.querier, &env, &config, &voting_session)?;
+    let validator = match validator {
+        Some(validator) => {
+            if validator != config.validator
+                && !voting_session
+                    .validator_lockers
+                    .iter()
+                    .any(|(v, _, _)| *v == validator)
+            {
+                return Err(ContractError::ValidatorNotInSession {
+                    validator,
+                    proposal_id,
+                });
+            }
+            validator
+        }
+        None => largest_delegation(&delegations).unwrap_or_else(|| config.validator.clone()),
+    };
+
     // Verify the vote option exists in the voting session
-    let locker_addr = voting_session
-        .locker_addresses
-        .iter()
-        .find(|(option, _)| *option == vote_option)
-        .map(|(_, addr)| addr)
+    let locker_addr = session_locker(&config, &voting_session, &validator, vote_option)
+        .cloned()
         .ok_or(ContractError::LockerNotFound {
             proposal_id,
             vote_option,
@@ -857,1024 +1552,5500 @@ pub fn execute_rent_voting_power(
         return Err(ContractError::ZeroAmount {});
     }
 
-    // Calculate VP amount: 1 VP = 0.1 ATOM
-    // So VP_amount = ATOM_amount / 0.1 = ATOM_amount * 10
-    let vp_amount = payment.amount.checked_mul(Uint128::new(10)).map_err(|_| {
-        ContractError::InsufficientBalance {
+    // Calculate VP amount from the pricing model that applies to this proposal
+    // Curve prices depend on how much voting power was already rented on this proposal
+    let mut state = STATE.load(deps.storage)?;
+    let pricing = config.rental_pricing.for_proposal(proposal_id);
+    let mut vp_amount = pricing.vp_for_payment(
+        payment.amount,
+        voting_session.rented_amount,
+        state.total_tokens,
+    )?;
+    if vp_amount.is_zero() {
+        return Err(ContractError::InsufficientBalance {
             available: payment.amount,
-            required: Uint128::new(1),
-        }
-    })?;
+            required: pricing.cost(
+                Uint128::one(),
+                voting_session.rented_amount,
+                state.total_tokens,
+            )?,
+        });
+    }
 
-    // Query the delegation to get our shares and calculate available tokens
-    // We need to account for the shares→tokens ratio which can be < 1 if validator was slashed
-    let delegation_response = deps
-        .querier
-        .query_delegation(env.contract.address.clone(), config.validator.clone())?;
+    // Enforce the max fraction of the pool tokens that may be rented on this proposal
+    // In partial mode, fill up to the cap and refund the unspent payment
+    let mut amount_paid = payment.amount;
+    if let Some(cap) = config.max_rent_amount(state.total_tokens) {
+        let remaining = cap.saturating_sub(voting_session.rented_amount);
+        if vp_amount > remaining {
+            if !allow_partial || remaining.is_zero() {
+                return Err(ContractError::RentCapReached {
+                    proposal_id,
+                    cap,
+                    rented: voting_session.rented_amount,
+                    attempting: vp_amount,
+                });
+            }
+            vp_amount = remaining;
+            amount_paid =
+                pricing.cost(vp_amount, voting_session.rented_amount, state.total_tokens)?;
+        }
+    }
 
-    // The delegation response already contains the token amount (not shares)
-    // This is because CosmWasm's query_delegation returns the Coin amount which represents tokens
-    let available_tokens = delegation_response
-        .map(|d| d.amount.amount)
-        .unwrap_or(Uint128::zero());
+    // The delegations contain the token amount (not shares)
+    // This accounts for the shares→tokens ratio which can be < 1 if the validator was slashed
+    let available_tokens = delegations
+        .iter()
+        .find(|(v, _)| *v == validator)
+        .map(|(_, amount)| *amount)
+        .unwrap_or_default();
 
     // Verify we have enough tokens available to tokenize
+    // In partial mode, fill what is available and refund the unspent payment
     if vp_amount > available_tokens {
-        return Err(ContractError::InsufficientStakedTokens {
-            available: available_tokens,
-            required: vp_amount,
+        if !allow_partial || available_tokens.is_zero() {
+            return Err(ContractError::InsufficientStakedTokens {
+                available: available_tokens,
+                required: vp_amount,
+            });
+        }
+        vp_amount = available_tokens;
+        amount_paid = pricing.cost(vp_amount, voting_session.rented_amount, state.total_tokens)?;
+    }
+    let refund_amount = payment.amount - amount_paid;
+
+    // Enforce the renter's slippage bounds on the executed rental
+    let price = Decimal256::from_ratio(amount_paid, vp_amount);
+    let below_min = min_vp_amount.is_some_and(|min| vp_amount < min);
+    let above_max = max_price.is_some_and(|max| price > max);
+    if below_min || above_max {
+        return Err(ContractError::RentalSlippageExceeded {
+            vp_amount,
+            price,
+            min_vp_amount: min_vp_amount.unwrap_or_default(),
+            max_price: max_price.unwrap_or(Decimal256::MAX),
         });
     }
 
-    // Add the rental payment to the global reward index
-    // The payment goes into the contract balance and should be distributed as rewards
-    let mut state = STATE.load(deps.storage)?;
-    state.add_rewards(payment.amount);
+    // Add the rental payment to the rental income index
+    // The payment goes into the contract balance and is distributed to stakers
+    let stakers_income = withhold_keeper_tip(&config, &mut voting_session, amount_paid);
+    add_rental_income(&config, &mut state, stakers_income);
     STATE.save(deps.storage, &state)?;
 
+    record_rental(
+        deps.storage,
+        &info.sender,
+        proposal_id,
+        vote_option,
+        amount_paid,
+        vp_amount,
+        env.block.height,
+    )?;
+
+    voting_session.rented_amount += vp_amount;
+    voting_session.deploy(&locker_addr, vp_amount);
+    VOTING_SESSIONS.save(deps.storage, proposal_id, &voting_session)?;
+
     // Store rental info for the reply handler
     ACTIVE_RENTAL.save(
         deps.storage,
         &ActiveRental {
             proposal_id,
             vote_option,
+            validator: validator.clone(),
         },
     )?;
 
     // Create MsgTokenizeShares to convert delegation to LSM shares
     let tokenize_msg = create_tokenize_shares_msg(
         env.contract.address.to_string(),
-        config.validator.clone(),
+        validator.clone(),
+        config.staking_denom.clone(),
         vp_amount,
         env.contract.address.to_string(), // Send to self first, then forward in reply
     )?;
 
-    Ok(Response::new()
-        .add_submessage(SubMsg::reply_on_success(
-            tokenize_msg,
-            REPLY_TOKENIZE_SHARES_RENTAL,
-        ))
-        .add_attribute("method", "rent_voting_power")
-        .add_attribute("renter", info.sender)
-        .add_attribute("proposal_id", proposal_id.to_string())
-        .add_attribute("vote_option", vote_option.to_string())
+    let mut response = Response::new().add_submessage(SubMsg::reply_on_success(
+        tokenize_msg,
+        REPLY_TOKENIZE_SHARES_RENTAL,
+    ));
+
+    if !refund_amount.is_zero() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: coins(refund_amount.u128(), &config.staking_denom),
+        });
+    }
+
+    Ok(response
+        .add_attribute("method", "rent_voting_power")
+        .add_attribute("renter", info.sender)
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("vote_option", vote_option.to_string())
         .add_attribute("payment", payment.amount)
         .add_attribute("vp_amount", vp_amount)
+        .add_attribute("filled", vp_amount)
+        .add_attribute("refunded", refund_amount)
+        .add_attribute("rented_amount", voting_session.rented_amount)
+        .add_attribute("validator", validator)
         .add_attribute("locker", locker_addr))
 }
 
-#[entry_point]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
-        QueryMsg::StakerInfo { address } => {
-            to_json_binary(&query_staker_info(deps, env.clone(), address)?)
-        }
-        QueryMsg::TotalStaked {} => to_json_binary(&query_total_staked(deps)?),
-        QueryMsg::RewardIndex {} => to_json_binary(&query_reward_index(deps)?),
-        QueryMsg::Stakers { start_after, limit } => {
-            to_json_binary(&query_stakers(deps, env, start_after, limit)?)
-        }
-    }
-}
-
-/// Calculate the simulated global reward index by querying pending staking rewards
-/// This is used in queries to show accurate pending rewards without modifying state
-fn calculate_simulated_global_index(
-    deps: Deps,
-    env: &Env,
-    state: &State,
-    config: &Config,
-) -> StdResult<cosmwasm_std::Decimal256> {
-    if state.total_staked.is_zero() {
-        return Ok(state.global_reward_index);
-    }
-
-    // Query pending staking rewards from the validator
-    let pending_rewards = deps
-        .querier
-        .query_delegation(env.contract.address.clone(), config.validator.clone())?
-        .and_then(|delegation| Some(delegation.accumulated_rewards))
-        .and_then(|rewards| {
-            rewards
-                .iter()
-                .find(|coin| coin.denom == config.staking_denom)
-                .map(|coin| coin.amount)
-        })
-        .unwrap_or(Uint128::zero());
-
-    // If there are pending rewards, calculate the simulated global index
-    if !pending_rewards.is_zero() {
-        let reward_per_token = cosmwasm_std::Decimal256::from_ratio(
-            cosmwasm_std::Uint256::from(pending_rewards),
-            cosmwasm_std::Uint256::from(state.total_staked),
-        );
-
-        state
-            .global_reward_index
-            .checked_add(reward_per_token)
-            .or(Ok(state.global_reward_index))
-    } else {
-        Ok(state.global_reward_index)
-    }
-}
+/// Commit a sealed bid to a voting session auction
+/// The deposit in staking_denom must cover the revealed bid (vp_amount * price_per_vp)
+pub fn execute_commit_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+    commitment: HexBinary,
+) -> Result<Response, ContractError> {
+    verify_not_in_emergency(deps.storage)?;
 
-fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let config = CONFIG.load(deps.storage)?;
-    let state = STATE.load(deps.storage)?;
-    let is_paused = IS_PAUSED.load(deps.storage)?;
 
-    Ok(ConfigResponse {
-        owner: config.owner,
-        staking_denom: config.staking_denom,
-        validator: config.validator,
-        max_cap: config.max_cap,
-        locker_code_id: config.locker_code_id,
-        total_staked: state.total_staked,
-        global_reward_index: state.global_reward_index,
-        is_paused,
-    })
-}
+    let voting_session = VOTING_SESSIONS
+        .load(deps.storage, proposal_id)
+        .map_err(|_| ContractError::NoVotingSession { proposal_id })?;
+    let auction = voting_session
+        .auction
+        .ok_or(ContractError::NoAuction { proposal_id })?;
 
-fn query_staker_info(deps: Deps, env: Env, address: String) -> StdResult<StakerInfoResponse> {
-    let addr = deps.api.addr_validate(&address)?;
-    let state = STATE.load(deps.storage)?;
-    let config = CONFIG.load(deps.storage)?;
+    if !voting_session.is_active || env.block.time >= auction.commit_end {
+        return Err(ContractError::WrongAuctionPhase {
+            proposal_id,
+            phase: "commit".to_string(),
+        });
+    }
 
-    let staker = STAKERS.load(deps.storage, &addr)?;
+    if commitment.len() != 32 {
+        return Err(ContractError::InvalidAuction {
+            reason: "Commitment must be a sha256 hash".to_string(),
+        });
+    }
 
-    // Calculate simulated global index including pending staking rewards
-    let simulated_global_index = calculate_simulated_global_index(deps, &env, &state, &config)?;
+    if BIDS.has(deps.storage, (proposal_id, &info.sender)) {
+        return Err(ContractError::BidAlreadyCommitted {
+            proposal_id,
+            bidder: info.sender.to_string(),
+        });
+    }
 
-    // Calculate pending rewards using the simulated index
-    let pending_rewards = staker.calculate_pending_rewards(simulated_global_index);
+    let bid_count = BIDS
+        .prefix(proposal_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count() as u32;
+    if bid_count >= MAX_AUCTION_BIDS {
+        return Err(ContractError::TooManyBids {
+            proposal_id,
+            max: MAX_AUCTION_BIDS,
+        });
+    }
 
-    Ok(StakerInfoResponse {
-        address: addr,
-        staked_amount: staker.staked_amount,
-        reward_index: staker.reward_index,
-        pending_rewards,
-    })
-}
+    // Verify exactly one coin is sent and it's the staking denom
+    if info.funds.len() != 1 || info.funds[0].denom != config.staking_denom {
+        return Err(ContractError::InvalidFunds {
+            expected: config.staking_denom,
+        });
+    }
+    let deposit = info.funds[0].amount;
+    if deposit.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
 
-fn query_total_staked(deps: Deps) -> StdResult<TotalStakedResponse> {
-    let state = STATE.load(deps.storage)?;
-    Ok(TotalStakedResponse {
-        total_staked: state.total_staked,
-    })
-}
+    BIDS.save(
+        deps.storage,
+        (proposal_id, &info.sender),
+        &Bid {
+            bidder: info.sender.clone(),
+            commitment,
+            deposit,
+            committed_at: env.block.height,
+            revealed: None,
+        },
+    )?;
 
-fn query_reward_index(deps: Deps) -> StdResult<RewardIndexResponse> {
-    let state = STATE.load(deps.storage)?;
-    Ok(RewardIndexResponse {
-        global_reward_index: state.global_reward_index,
-    })
+    Ok(Response::new()
+        .add_attribute("method", "commit_bid")
+        .add_attribute("bidder", info.sender)
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("deposit", deposit))
 }
 
-fn query_stakers(
-    deps: Deps,
+/// Reveal a sealed bid during the reveal phase of a voting session auction
+/// The reveal phase ends before the voting period, so only destroyed sessions are left to reject
+pub fn execute_reveal_bid(
+    deps: DepsMut,
     env: Env,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<StakersResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let state = STATE.load(deps.storage)?;
+    info: MessageInfo,
+    proposal_id: u64,
+    revealed: RevealedBid,
+    salt: String,
+) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    // Calculate simulated global index including pending staking rewards
-    let simulated_global_index = calculate_simulated_global_index(deps, &env, &state, &config)?;
-
-    let stakers: Vec<StakerInfoResponse> = if let Some(s) = start_after {
-        let addr = deps.api.addr_validate(&s)?;
-        STAKERS
-            .range(
-                deps.storage,
-                Some(Bound::exclusive(&addr)),
-                None,
-                Order::Ascending,
-            )
-            .take(limit)
-            .map(|item| {
-                let (addr, staker) = item?;
-                let pending_rewards = staker.calculate_pending_rewards(simulated_global_index);
-
-                Ok(StakerInfoResponse {
-                    address: addr,
-                    staked_amount: staker.staked_amount,
-                    reward_index: staker.reward_index,
-                    pending_rewards,
-                })
-            })
-            .collect::<StdResult<Vec<_>>>()?
-    } else {
-        STAKERS
-            .range(deps.storage, None, None, Order::Ascending)
-            .take(limit)
-            .map(|item| {
-                let (addr, staker) = item?;
-                let pending_rewards = staker.calculate_pending_rewards(simulated_global_index);
-
-                Ok(StakerInfoResponse {
-                    address: addr,
-                    staked_amount: staker.staked_amount,
-                    reward_index: staker.reward_index,
-                    pending_rewards,
-                })
-            })
-            .collect::<StdResult<Vec<_>>>()?
-    };
-
-    Ok(StakersResponse { stakers })
-}
+    let voting_session = VOTING_SESSIONS
+        .load(deps.storage, proposal_id)
+        .map_err(|_| ContractError::NoVotingSession { proposal_id })?;
+    let auction = voting_session
+        .auction
+        .as_ref()
+        .ok_or(ContractError::NoAuction { proposal_id })?;
+
+    if !voting_session.is_active
+        || env.block.time < auction.commit_end
+        || env.block.time >= auction.reveal_end
+    {
+        return Err(ContractError::WrongAuctionPhase {
+            proposal_id,
+            phase: "reveal".to_string(),
+        });
+    }
 
-/// Parse LSM denom and validate format
-/// LSM denom format: {validator_address}/{record_id}
-/// Example: cosmosvaloper1abc.../123
-fn parse_lsm_denom(lsm_denom: &str) -> Result<LsmShareInfo, ContractError> {
-    let parts: Vec<&str> = lsm_denom.split('/').collect();
+    let mut bid = BIDS
+        .load(deps.storage, (proposal_id, &info.sender))
+        .map_err(|_| ContractError::BidNotFound {
+            proposal_id,
+            bidder: info.sender.to_string(),
+        })?;
 
-    if parts.len() != 2 {
-        return Err(ContractError::InvalidLsmShares {
-            reason: format!(
-                "Invalid LSM denom format. Expected 'validator/record_id', got '{}'",
-                lsm_denom
-            ),
+    if bid.revealed.is_some() {
+        return Err(ContractError::InvalidBidReveal {
+            reason: "Bid already revealed".to_string(),
         });
     }
 
-    let validator = parts[0].to_string();
-    let record_id = parts[1].to_string();
+    if bid_commitment(&info.sender, proposal_id, &revealed, &salt) != bid.commitment {
+        return Err(ContractError::InvalidBidReveal {
+            reason: "Bid does not match commitment".to_string(),
+        });
+    }
 
-    // Validate validator address format (should start with valoper prefix)
-    if !validator.starts_with("cosmosvaloper") && !validator.starts_with("osmosisvaloper") {
-        return Err(ContractError::InvalidLsmShares {
-            reason: format!(
-                "Invalid validator address format. Expected valoper address, got '{}'",
-                validator
-            ),
+    // Settlement may allocate the bid from any validator of the session
+    if !session_accepts_option(&config, &voting_session, revealed.vote_option) {
+        return Err(ContractError::LockerNotFound {
+            proposal_id,
+            vote_option: revealed.vote_option,
         });
     }
 
-    // Validate record_id is numeric
-    if record_id.parse::<u64>().is_err() {
-        return Err(ContractError::InvalidLsmShares {
-            reason: format!(
-                "Invalid record_id. Expected numeric value, got '{}'",
-                record_id
-            ),
+    if revealed.vp_amount.is_zero() || revealed.price_per_vp.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    // The escrowed deposit must cover the full bid
+    let bid_cost = PricingModel::Flat {
+        price_per_vp: revealed.price_per_vp,
+    }
+    .cost(revealed.vp_amount, Uint128::zero(), Uint128::zero())?;
+    if bid_cost > bid.deposit {
+        return Err(ContractError::InsufficientBalance {
+            available: bid.deposit,
+            required: bid_cost,
         });
     }
 
-    Ok(LsmShareInfo {
-        validator,
-        record_id,
-    })
-}
+    let response = Response::new()
+        .add_attribute("method", "reveal_bid")
+        .add_attribute("bidder", info.sender.to_string())
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("vote_option", revealed.vote_option.to_string())
+        .add_attribute("vp_amount", revealed.vp_amount)
+        .add_attribute("price_per_vp", revealed.price_per_vp.to_string());
 
-/// Verify that the validator exists on chain
-fn verify_validator_exists(querier: &QuerierWrapper, validator: &str) -> Result<(), ContractError> {
-    querier
-        .query_validator(validator)
-        .map_err(|_| ContractError::ValidatorNotFound {
-            validator: validator.to_string(),
-        })?;
+    bid.revealed = Some(revealed);
+    BIDS.save(deps.storage, (proposal_id, &info.sender), &bid)?;
 
-    Ok(())
+    Ok(response)
 }
 
-/// Verify that a proposal is finished or doesn't exist anymore
-/// Finished means status is PASSED (3), REJECTED (4), or FAILED (5)
-/// If the proposal doesn't exist (query fails), we allow the destroy (proposal was purged)
-fn verify_proposal_finished(
-    querier: &QuerierWrapper,
+/// Settle a voting session auction after the reveal phase (permissionless)
+/// This will:
+/// 1. Allocate available voting power to revealed bids by descending price (pay-as-bid)
+/// 2. Refund unused deposits and forfeit deposits of unrevealed bids to stakers
+/// 3. Add the auction income to the rental income index
+/// 4. Tokenize the allocated voting power of each option into its lockers,
+///    from the session validators with the most delegated tokens first
+pub fn execute_settle_auction(
+    deps: DepsMut,
+    env: Env,
     proposal_id: u64,
-) -> Result<(), ContractError> {
-    use cosmwasm_std::QueryRequest;
-    use prost::Message;
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
 
-    // Proto definition for QueryProposalRequest
-    #[derive(Clone, PartialEq, Message)]
-    struct QueryProposalRequest {
-        #[prost(uint64, tag = "1")]
-        pub proposal_id: u64,
+    let mut voting_session = VOTING_SESSIONS
+        .load(deps.storage, proposal_id)
+        .map_err(|_| ContractError::NoVotingSession { proposal_id })?;
+    let mut auction = voting_session
+        .auction
+        .clone()
+        .ok_or(ContractError::NoAuction { proposal_id })?;
+
+    if auction.settled {
+        return Err(ContractError::AuctionAlreadySettled { proposal_id });
+    }
+    if env.block.time < auction.reveal_end {
+        return Err(ContractError::WrongAuctionPhase {
+            proposal_id,
+            phase: "settlement".to_string(),
+        });
     }
 
-    // Proto definition for QueryProposalResponse
-    #[derive(Clone, PartialEq, Message)]
-    struct QueryProposalResponse {
-        #[prost(message, optional, tag = "1")]
-        pub proposal: Option<Proposal>,
+    let bids = BIDS
+        .prefix(proposal_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, bid)| bid))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // Voting power can only be allocated while the lockers are still alive and can vote
+    // Otherwise every revealed bid is refunded
+    let can_vote = voting_session.is_active && env.block.time < voting_session.voting_end_time;
+    let mut delegations = if can_vote {
+        session_delegations(&deps.querier, &env, &config, &voting_session)?
+    } else {
+        vec![]
+    };
+    let mut remaining_vp: Uint128 = delegations.iter().map(|(_, amount)| *amount).sum();
+
+    // No more than the max fraction of the pool tokens is allocated
+    let mut state = STATE.load(deps.storage)?;
+    if let Some(remaining) = remaining_rent_amount(&config, &state, &voting_session) {
+        remaining_vp = remaining_vp.min(remaining);
     }
 
-    // Proto definition for Proposal (simplified, only fields we need)
-    #[derive(Clone, PartialEq, Message)]
-    struct Proposal {
-        #[prost(uint64, tag = "1")]
-        pub proposal_id: u64,
-        #[prost(int32, tag = "3")]
-        pub status: i32,
-        // We skip other fields we don't need
-    }
-
-    // Encode the query request
-    let request = QueryProposalRequest { proposal_id };
-    let mut query_data = Vec::new();
-    request
-        .encode(&mut query_data)
-        .map_err(|e| ContractError::InvalidLsmShares {
-            reason: format!("Failed to encode proposal query: {}", e),
-        })?;
+    // Highest price first, earliest commitment wins ties
+    let mut revealed_bids: Vec<(&Bid, &RevealedBid)> = bids
+        .iter()
+        .filter_map(|bid| bid.revealed.as_ref().map(|revealed| (bid, revealed)))
+        .collect();
+    revealed_bids.sort_by(|(bid_a, revealed_a), (bid_b, revealed_b)| {
+        revealed_b
+            .price_per_vp
+            .cmp(&revealed_a.price_per_vp)
+            .then(bid_a.committed_at.cmp(&bid_b.committed_at))
+    });
 
-    // Query the gov module using Stargate
-    let stargate_result: Result<Binary, cosmwasm_std::StdError> =
-        querier.query(&QueryRequest::Stargate {
-            path: "/cosmos.gov.v1beta1.Query/Proposal".to_string(),
-            data: Binary::from(query_data),
-        });
+    let mut option_allocations: Vec<(i32, Uint128)> = Vec::new();
+    let mut income = Uint128::zero();
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    let mut winners = 0u32;
 
-    match stargate_result {
-        Ok(stargate_response) => {
-            // Decode the response
-            let proposal_response = QueryProposalResponse::decode(stargate_response.as_slice())
-                .map_err(|e| ContractError::InvalidLsmShares {
-                    reason: format!("Failed to decode proposal query response: {}", e),
-                })?;
-            // Proposal exists, check its status
-            if let Some(proposal) = proposal_response.proposal {
-                // Status codes:
-                // 0 = UNSPECIFIED
-                // 1 = DEPOSIT_PERIOD
-                // 2 = VOTING_PERIOD
-                // 3 = PASSED
-                // 4 = REJECTED
-                // 5 = FAILED
-                if proposal.status >= 3 && proposal.status <= 5 {
-                    // Proposal is finished (PASSED, REJECTED, or FAILED)
-                    Ok(())
-                } else {
-                    // Proposal is still active (DEPOSIT_PERIOD or VOTING_PERIOD)
-                    Err(ContractError::ProposalStillActive {
-                        proposal_id,
-                        status: match proposal.status {
-                            0 => "UNSPECIFIED".to_string(),
-                            1 => "DEPOSIT_PERIOD".to_string(),
-                            2 => "VOTING_PERIOD".to_string(),
-                            _ => format!("UNKNOWN({})", proposal.status),
-                        },
-                    })
-                }
-            } else {
-                // Proposal exists but has no data - treat as finished
-                Ok(())
+    for (bid, revealed) in revealed_bids {
+        let allocated = revealed.vp_amount.min(remaining_vp);
+        let cost = PricingModel::Flat {
+            price_per_vp: revealed.price_per_vp,
+        }
+        .cost(allocated, Uint128::zero(), Uint128::zero())?;
+
+        if !allocated.is_zero() {
+            remaining_vp -= allocated;
+            income += cost;
+            winners += 1;
+            record_rental(
+                deps.storage,
+                &bid.bidder,
+                proposal_id,
+                revealed.vote_option,
+                cost,
+                allocated,
+                env.block.height,
+            )?;
+            match option_allocations
+                .iter_mut()
+                .find(|(option, _)| *option == revealed.vote_option)
+            {
+                Some((_, amount)) => *amount += allocated,
+                None => option_allocations.push((revealed.vote_option, allocated)),
             }
         }
-        Err(_) => {
-            // Query failed - proposal doesn't exist (was purged)
-            // This is OK, we can destroy the lockers
-            Ok(())
+
+        let refund = bid.deposit.saturating_sub(cost);
+        if !refund.is_zero() {
+            messages.push(CosmosMsg::Bank(BankMsg::Send {
+                to_address: bid.bidder.to_string(),
+                amount: coins(refund.u128(), config.staking_denom.clone()),
+            }));
         }
     }
-}
 
-/// Create MsgRedeemTokensForShares message for redeeming LSM shares
-/// This uses the gaia.liquid.v1beta1.MsgRedeemTokensForShares proto
-fn create_redeem_tokens_msg(
-    delegator_address: String,
-    denom: String,
-    amount: Uint128,
-) -> Result<CosmosMsg, ContractError> {
-    use prost::Message;
+    // Unrevealed bids forfeit their deposit to stakers
+    let forfeited: Uint128 = bids
+        .iter()
+        .filter(|bid| bid.revealed.is_none())
+        .map(|bid| bid.deposit)
+        .sum();
+    income += forfeited;
+
+    // A destroyed session has already paid its keeper, a tip withheld now could never be paid out
+    let stakers_income = if voting_session.is_active {
+        withhold_keeper_tip(&config, &mut voting_session, income)
+    } else {
+        income
+    };
+    add_rental_income(&config, &mut state, stakers_income);
+    STATE.save(deps.storage, &state)?;
 
-    // Proto definition for MsgRedeemTokensForShares
-    #[derive(Clone, PartialEq, Message)]
-    struct MsgRedeemTokensForShares {
-        #[prost(string, tag = "1")]
-        pub delegator_address: String,
-        #[prost(message, required, tag = "2")]
-        pub amount: ProtoCoin,
-    }
+    let allocated_vp: Uint128 = option_allocations.iter().map(|(_, amount)| *amount).sum();
+    auction.settled = true;
+    voting_session.auction = Some(auction);
+    voting_session.rented_amount += allocated_vp;
 
-    #[derive(Clone, PartialEq, Message)]
-    struct ProtoCoin {
-        #[prost(string, tag = "1")]
-        pub denom: String,
-        #[prost(string, tag = "2")]
-        pub amount: String,
+    // Tokenize the voting power of each winning option, the reply forwards it to the locker
+    let mut submessages: Vec<SubMsg> = Vec::new();
+    for (vote_option, amount) in &option_allocations {
+        let parts = split_across_validators(&mut delegations, *amount).ok_or(
+            ContractError::InsufficientStakedTokens {
+                available: Uint128::zero(),
+                required: *amount,
+            },
+        )?;
+        for (validator, part) in parts {
+            let locker = session_locker(&config, &voting_session, &validator, *vote_option)
+                .cloned()
+                .ok_or(ContractError::LockerNotFound {
+                    proposal_id,
+                    vote_option: *vote_option,
+                })?;
+            voting_session.deploy(&locker, part);
+
+            let tokenize_msg = create_tokenize_shares_msg(
+                env.contract.address.to_string(),
+                validator.clone(),
+                config.staking_denom.clone(),
+                part,
+                env.contract.address.to_string(),
+            )?;
+            let payload = AuctionTokenizePayload {
+                proposal_id,
+                vote_option: *vote_option,
+                validator,
+            };
+            submessages.push(
+                SubMsg::reply_on_success(tokenize_msg, REPLY_TOKENIZE_SHARES_AUCTION)
+                    .with_payload(to_json_binary(&payload)?),
+            );
+        }
     }
+    VOTING_SESSIONS.save(deps.storage, proposal_id, &voting_session)?;
 
-    let msg = MsgRedeemTokensForShares {
-        delegator_address,
-        amount: ProtoCoin {
-            denom,
-            amount: amount.to_string(),
-        },
-    };
-
-    // Encode the message
-    let mut buf = Vec::new();
-    msg.encode(&mut buf)
-        .map_err(|e| ContractError::InvalidLsmShares {
-            reason: format!("Failed to encode MsgRedeemTokensForShares: {}", e),
-        })?;
-
-    // Create Any message with the correct type URL
-    // The package is gaia.liquid.v1beta1 as defined in the proto file
-    Ok(CosmosMsg::Any(cosmwasm_std::AnyMsg {
-        type_url: "/gaia.liquid.v1beta1.MsgRedeemTokensForShares".to_string(),
-        value: Binary::from(buf),
-    }))
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_submessages(submessages)
+        .add_attribute("method", "settle_auction")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("num_bids", bids.len().to_string())
+        .add_attribute("num_winners", winners.to_string())
+        .add_attribute("allocated_vp", allocated_vp)
+        .add_attribute("income", income)
+        .add_attribute("forfeited", forfeited))
 }
 
-/// Create MsgTokenizeShares message to convert delegation to LSM shares
-/// This uses the gaia.liquid.v1beta1.MsgTokenizeShares proto
-fn create_tokenize_shares_msg(
-    delegator_address: String,
-    validator_address: String,
-    amount: Uint128,
-    tokenized_share_owner: String,
-) -> Result<CosmosMsg, ContractError> {
-    use prost::Message;
+/// Split a vote between options proportionally to their amounts
+/// Options without amount are left out, rounding dust goes to the largest weight
+fn vote_weights(amounts: Vec<(i32, Uint128)>) -> Vec<WeightedVoteOption> {
+    let total: Uint128 = amounts.iter().map(|(_, amount)| *amount).sum();
+    let mut weights: Vec<WeightedVoteOption> = amounts
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(option, amount)| WeightedVoteOption {
+            option,
+            weight: Decimal::from_ratio(amount, total),
+        })
+        .collect();
 
-    // Proto definition for MsgTokenizeShares
-    #[derive(Clone, PartialEq, Message)]
-    struct MsgTokenizeShares {
-        #[prost(string, tag = "1")]
-        pub delegator_address: String,
-        #[prost(string, tag = "2")]
-        pub validator_address: String,
-        #[prost(message, required, tag = "3")]
-        pub amount: ProtoCoin,
-        #[prost(string, tag = "4")]
-        pub tokenized_share_owner: String,
+    let allocated: Decimal = weights.iter().map(|weighted| weighted.weight).sum();
+    if let Some(largest) = weights.iter_mut().max_by_key(|weighted| weighted.weight) {
+        largest.weight += Decimal::one() - allocated;
     }
 
-    #[derive(Clone, PartialEq, Message)]
-    struct ProtoCoin {
-        #[prost(string, tag = "1")]
-        pub denom: String,
-        #[prost(string, tag = "2")]
-        pub amount: String,
-    }
+    weights
+}
 
-    let msg = MsgTokenizeShares {
-        delegator_address,
-        validator_address,
-        amount: ProtoCoin {
-            denom: "uatom".to_string(), // TODO: make configurable
-            amount: amount.to_string(),
-        },
-        tokenized_share_owner,
+/// Withhold the keeper tip of a voting session from its rental income
+/// Returns the part of the income distributed to stakers
+fn withhold_keeper_tip(
+    config: &Config,
+    voting_session: &mut VotingSession,
+    income: Uint128,
+) -> Uint128 {
+    let withheld = config
+        .keeper_tip
+        .saturating_sub(voting_session.keeper_reserve)
+        .min(income);
+    voting_session.keeper_reserve += withheld;
+    income - withheld
+}
+
+/// Add a rental to the ledger and to the per-option aggregate
+fn record_rental(
+    storage: &mut dyn Storage,
+    renter: &Addr,
+    proposal_id: u64,
+    vote_option: i32,
+    amount_paid: Uint128,
+    vp_amount: Uint128,
+    height: u64,
+) -> StdResult<()> {
+    let id = RENTAL_COUNT.may_load(storage)?.unwrap_or_default();
+    RENTAL_COUNT.save(storage, &(id + 1))?;
+    let rental = Rental {
+        id,
+        renter: renter.clone(),
+        proposal_id,
+        vote_option,
+        amount_paid,
+        vp_amount,
+        height,
     };
+    rentals().save(storage, id, &rental)?;
 
-    // Encode the message
-    let mut buf = Vec::new();
-    msg.encode(&mut buf)
-        .map_err(|e| ContractError::InvalidLsmShares {
-            reason: format!("Failed to encode MsgTokenizeShares: {}", e),
-        })?;
+    RENTAL_TOTALS.update(storage, (proposal_id, vote_option), |total| {
+        let mut total = total.unwrap_or(RentalTotal {
+            vote_option,
+            amount_paid: Uint128::zero(),
+            vp_amount: Uint128::zero(),
+        });
+        total.amount_paid += amount_paid;
+        total.vp_amount += vp_amount;
+        Ok::<_, cosmwasm_std::StdError>(total)
+    })?;
 
-    // Create Any message with the correct type URL
-    Ok(CosmosMsg::Any(cosmwasm_std::AnyMsg {
-        type_url: "/gaia.liquid.v1beta1.MsgTokenizeShares".to_string(),
-        value: Binary::from(buf),
-    }))
+    Ok(())
 }
 
-#[entry_point]
-pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
-    match msg.id {
-        REPLY_CLAIM_REWARDS => reply_claim_rewards(deps, env),
-        REPLY_TOKENIZE_SHARES_RENTAL => reply_tokenize_shares_rental(deps, env),
-        REPLY_TOKENIZE_SHARES_WITHDRAW => reply_tokenize_shares_withdraw(deps, env),
-        REPLY_CLAIM_REWARDS_DEPOSIT => reply_claim_rewards_deposit(deps, env),
-        REPLY_REDEEM_SHARES_DEPOSIT => reply_redeem_shares_deposit(deps, env),
-        REPLY_INSTANTIATE_LOCKER => reply_instantiate_locker(deps, msg),
-        _ => Err(ContractError::InvalidLsmShares {
-            reason: format!("Unknown reply ID: {}", msg.id),
-        }),
+/// Validate the denoms accepted as rewards besides the staking denom
+fn validate_reward_denoms(
+    staking_denom: &str,
+    reward_denoms: &[String],
+) -> Result<(), ContractError> {
+    for (i, denom) in reward_denoms.iter().enumerate() {
+        if denom.is_empty() || denom == staking_denom || reward_denoms[..i].contains(denom) {
+            return Err(ContractError::InvalidRewardDenom {
+                denom: denom.clone(),
+            });
+        }
     }
+    Ok(())
 }
 
-/// Reply handler after withdrawing rewards from the validator
-/// This:
-/// 1. Calculates the rewards received from the validator
-/// 2. Updates the global reward index with these rewards
-/// 3. Calculates the user's pending rewards with the new index
-/// 4. Updates user state and sends rewards
-/// 5. If this is a withdraw (not just a claim), also tokenizes shares
-fn reply_claim_rewards(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-    let active_claim = ACTIVE_CLAIM.load(deps.storage)?;
+/// Balances of the contract in the reward denoms
+fn query_reward_balances(
+    querier: &QuerierWrapper,
+    env: &Env,
+    config: &Config,
+) -> StdResult<Vec<Coin>> {
+    std::iter::once(&config.staking_denom)
+        .chain(&config.reward_denoms)
+        .map(|denom| querier.query_balance(env.contract.address.clone(), denom))
+        .collect()
+}
 
-    // Query balance after rewards withdrawal
-    let balance_query: BalanceResponse = deps.querier.query(
-        &BankQuery::Balance {
-            address: env.contract.address.to_string(),
-            denom: config.staking_denom.clone(),
-        }
-        .into(),
-    )?;
-    let balance_after = balance_query.amount.amount;
+/// Rewards received in each denom since the balances were recorded
+fn rewards_received(balances_before: &[Coin], balances_after: Vec<Coin>) -> Vec<Coin> {
+    balances_after
+        .into_iter()
+        .filter_map(|coin| {
+            let before = balances_before
+                .iter()
+                .find(|before| before.denom == coin.denom)
+                .map(|before| before.amount)
+                .unwrap_or_default();
+            let received = coin.amount.saturating_sub(before);
+            (!received.is_zero()).then(|| Coin::new(received, coin.denom))
+        })
+        .collect()
+}
 
-    // Calculate actual rewards received from the validator
-    let rewards_received = balance_after.saturating_sub(active_claim.balance_before);
+/// Send rewards to a staker, if any
+fn send_rewards(recipient: &Addr, rewards: Vec<Coin>) -> Option<CosmosMsg> {
+    (!rewards.is_empty()).then(|| {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: rewards,
+        })
+    })
+}
 
-    // Update global reward index with the rewards received
-    let mut state = STATE.load(deps.storage)?;
-    state.add_rewards(rewards_received);
-    STATE.save(deps.storage, &state)?;
+/// Settle the pending rewards of a staker before their staked amount changes
+/// Staking denom rewards of an auto-compounding staker are added to their stake
+/// Returns the compounded amount and the rewards to pay out
+fn settle_rewards(config: &Config, state: &mut State, staker: &mut Staker) -> (Uint128, Vec<Coin>) {
+    let rewards = staker.calculate_pending_income(state, &config.staking_denom);
+    staker.update_index(state);
 
-    // NOW calculate the user's pending rewards with the updated global index
-    // This includes both:
-    // 1. Rewards that were pending before (from global_index_before)
-    // 2. Rewards from this claim (from rewards_received)
-    let mut staker = STAKERS.load(deps.storage, &active_claim.claimer)?;
-    let user_rewards = staker.calculate_pending_rewards(state.global_reward_index);
+    if !staker.auto_compound {
+        return (Uint128::zero(), rewards);
+    }
 
-    // Update staker state - update their reward index to the new global index
-    staker.update_index(state.global_reward_index);
-    STAKERS.save(deps.storage, &active_claim.claimer, &staker)?;
+    let (compounded, paid): (Vec<Coin>, Vec<Coin>) = rewards
+        .into_iter()
+        .partition(|coin| coin.denom == config.staking_denom);
+    let compounded = compounded
+        .first()
+        .map(|coin| coin.amount)
+        .unwrap_or_default();
+
+    // Compounded rewards are delegated, so they buy shares at the exchange rate
+    let shares = state.shares_for_tokens(compounded);
+    staker.staked_amount += shares;
+    state.total_staked += shares;
+    state.total_tokens += compounded;
+
+    (compounded, paid)
+}
 
-    let mut messages = vec![];
-    let mut response = Response::new()
-        .add_attribute("action", "rewards_claimed")
-        .add_attribute("user", active_claim.claimer.to_string())
-        .add_attribute("rewards_received", rewards_received.to_string())
-        .add_attribute("user_amount", user_rewards.to_string());
+/// Delegate compounded rewards back to the validator, if any
+fn delegate_compounded(config: &Config, amount: Uint128) -> Option<CosmosMsg> {
+    (!amount.is_zero()).then(|| {
+        CosmosMsg::Staking(StakingMsg::Delegate {
+            validator: config.validator.clone(),
+            amount: Coin::new(amount, &config.staking_denom),
+        })
+    })
+}
 
-    // Send rewards to user if they have any
-    if !user_rewards.is_zero() {
-        let send_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: active_claim.claimer.to_string(),
-            amount: coins(user_rewards.u128(), config.staking_denom.clone()),
-        });
-        messages.push(send_msg);
-    }
+/// Compound the next batch of auto-compounding stakers
+/// Starts over from the first staker once the last one was compounded
+fn compound_batch(deps: DepsMut, config: &Config) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
 
-    // Check if this is part of a withdraw operation
-    if let Some(withdraw_amount) = active_claim.withdraw_amount {
-        // Store active withdraw info for the tokenize reply handler
-        ACTIVE_WITHDRAW.save(
+    let cursor = COMPOUND_CURSOR.may_load(deps.storage)?;
+    let batch = AUTO_COMPOUNDERS
+        .keys(
             deps.storage,
-            &ActiveWithdraw {
-                withdrawer: active_claim.claimer.clone(),
-                amount: withdraw_amount,
-            },
-        )?;
+            cursor.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(MAX_COMPOUND_BATCH)
+        .collect::<StdResult<Vec<_>>>()?;
 
-        // Create tokenize shares message to convert delegation to LSM shares
-        let tokenize_msg = create_tokenize_shares_msg(
-            env.contract.address.to_string(),
-            config.validator,
-            withdraw_amount,
-            env.contract.address.to_string(), // Send to self first, then forward in reply
-        )?;
+    match batch.last() {
+        Some(last) if batch.len() == MAX_COMPOUND_BATCH => {
+            COMPOUND_CURSOR.save(deps.storage, last)?
+        }
+        _ => COMPOUND_CURSOR.remove(deps.storage),
+    }
 
-        response = response
-            .add_submessage(SubMsg::reply_on_success(
-                tokenize_msg,
-                REPLY_TOKENIZE_SHARES_WITHDRAW,
-            ))
-            .add_attribute("withdraw_amount", withdraw_amount);
+    let mut total_compounded = Uint128::zero();
+    let mut messages = vec![];
+    for addr in &batch {
+        let mut staker = STAKERS.load(deps.storage, addr)?;
+        let (compounded, paid) = settle_rewards(config, &mut state, &mut staker);
+        STAKERS.save(deps.storage, addr, &staker)?;
+
+        total_compounded += compounded;
+        messages.extend(send_rewards(addr, paid));
     }
 
-    // Clean up active claim
-    ACTIVE_CLAIM.remove(deps.storage);
+    STATE.save(deps.storage, &state)?;
 
-    Ok(response.add_messages(messages))
+    Ok(Response::new()
+        .add_messages(delegate_compounded(config, total_compounded))
+        .add_messages(messages)
+        .add_attribute("stakers", batch.len().to_string())
+        .add_attribute("compounded", total_compounded))
 }
 
-/// Reply handler after tokenizing shares for rental
-/// This sends the LSM shares to the corresponding locker via DepositLsmShares
-fn reply_tokenize_shares_rental(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-    let active_rental = ACTIVE_RENTAL.load(deps.storage)?;
+/// Withhold the reward fee from staking rewards and add the rest to the staking reward index
+fn add_staking_rewards(config: &Config, state: &mut State, denom: &str, amount: Uint128) {
+    let rewards = state.withhold_fee(denom, amount, config.reward_fee_rate);
+    state.add_rewards(denom, rewards);
+}
 
-    // Load voting session to get locker address
-    let voting_session = VOTING_SESSIONS
-        .load(deps.storage, active_rental.proposal_id)
-        .map_err(|_| ContractError::NoVotingSession {
-            proposal_id: active_rental.proposal_id,
-        })?;
+/// Withhold the rental fee from rental income and add the rest to the rental income index
+fn add_rental_income(config: &Config, state: &mut State, amount: Uint128) {
+    let income = state.withhold_fee(&config.staking_denom, amount, config.rental_fee_rate);
+    state.add_rental_income(income);
+}
 
-    // Find the locker address for this vote option
-    let locker_addr = voting_session
-        .locker_addresses
-        .iter()
-        .find(|(option, _)| *option == active_rental.vote_option)
-        .map(|(_, addr)| addr)
-        .ok_or(ContractError::LockerNotFound {
-            proposal_id: active_rental.proposal_id,
-            vote_option: active_rental.vote_option,
-        })?;
+/// Verify that a protocol fee rate does not exceed its hard cap
+fn validate_fee_rate(rate: Decimal, max: Decimal) -> Result<(), ContractError> {
+    if rate > max {
+        return Err(ContractError::FeeRateTooHigh { rate, max });
+    }
+    Ok(())
+}
 
-    // Query all token balances to find the LSM share
-    // LSM shares have format: {validator}/{record_id}
-    use cosmwasm_std::{AllBalanceResponse, BankQuery, QueryRequest};
-    let all_balances_response: AllBalanceResponse =
-        deps.querier
-            .query(&QueryRequest::Bank(BankQuery::AllBalances {
-                address: env.contract.address.to_string(),
-            }))?;
-    let all_balances = all_balances_response.amount;
+/// Verify that the keeper tip is within MAX_KEEPER_TIP
+fn validate_keeper_tip(tip: Uint128) -> Result<(), ContractError> {
+    if tip > MAX_KEEPER_TIP {
+        return Err(ContractError::KeeperTipTooHigh {
+            tip,
+            max: MAX_KEEPER_TIP,
+        });
+    }
+    Ok(())
+}
 
-    // Find the LSM share token for our specific validator
-    // The denom should start with the validator address followed by '/'
-    let expected_prefix = format!("{}/", config.validator);
-    let lsm_share = all_balances
+/// Verify that the max rent fraction is within (0, 1]
+fn validate_rent_fraction(fraction: Decimal) -> Result<(), ContractError> {
+    if fraction.is_zero() || fraction > Decimal::one() {
+        return Err(ContractError::InvalidRentFraction { fraction });
+    }
+    Ok(())
+}
+
+/// Voting power that may still be rented on a proposal, None if unlimited
+fn remaining_rent_amount(
+    config: &Config,
+    state: &State,
+    voting_session: &VotingSession,
+) -> Option<Uint128> {
+    config
+        .max_rent_amount(state.total_tokens)
+        .map(|cap| cap.saturating_sub(voting_session.rented_amount))
+}
+
+/// Protocol fees accrued in each denom, zero amounts are skipped
+fn accrued_fees(state: &State) -> Vec<Coin> {
+    state
+        .accrued_fees
         .iter()
-        .find(|coin| coin.denom.starts_with(&expected_prefix))
-        .ok_or(ContractError::InvalidLsmShares {
-            reason: format!(
-                "No LSM share found for validator {} after tokenization",
-                config.validator
-            ),
-        })?;
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin::new(*amount, denom))
+        .collect()
+}
 
-    // Call DepositLsmShares on the locker with the LSM shares
-    use cosmwasm_std::WasmMsg;
-    use proposal_locker_types::ExecuteMsg as LockerExecuteMsg;
+/// Validate the validators accepted besides the primary one
+fn validate_validators(
+    querier: &QuerierWrapper,
+    primary: &str,
+    validators: &[String],
+) -> Result<(), ContractError> {
+    for (i, validator) in validators.iter().enumerate() {
+        if validator == primary || validators[..i].contains(validator) {
+            return Err(ContractError::DuplicateValidator {
+                validator: validator.clone(),
+            });
+        }
+        verify_validator_exists(querier, validator)?;
+    }
+    Ok(())
+}
 
-    let deposit_msg = WasmMsg::Execute {
-        contract_addr: locker_addr.to_string(),
-        msg: to_json_binary(&LockerExecuteMsg::DepositLsmShares {})?,
-        funds: vec![lsm_share.clone()],
-    };
+/// Verify that the fallback validator, if any, is an accepted validator
+fn validate_fallback_validator(config: &Config) -> Result<(), ContractError> {
+    match &config.fallback_validator {
+        Some(fallback) if !config.is_validator(fallback) => Err(ContractError::InvalidValidator {
+            validator: fallback.clone(),
+            expected: config.all_validators().join(","),
+        }),
+        _ => Ok(()),
+    }
+}
 
-    // Clean up active rental
-    ACTIVE_RENTAL.remove(deps.storage);
+/// Detect slashing by comparing the delegated tokens with the tokens backing the pool shares
+/// Lowers the pool tokens by the slashed amount, which lowers the exchange rate for all stakers
+/// The stake deployed in the lockers still backs the shares, it counts until the locker
+/// returns it, even after its session is destroyed
+fn record_slashing(
+    storage: &dyn Storage,
+    state: &mut State,
+    delegations: &[(String, Uint128)],
+) -> StdResult<Uint128> {
+    let delegated_tokens: Uint128 = delegations.iter().map(|(_, amount)| *amount).sum();
+    let deployed_tokens = VOTING_SESSIONS
+        .range(storage, None, None, Order::Ascending)
+        .map(|result| result.map(|(_, session)| session.deployed_amount()))
+        .sum::<StdResult<Uint128>>()?;
+    Ok(state.record_delegated_tokens(delegated_tokens + deployed_tokens))
+}
 
-    Ok(Response::new()
-        .add_message(deposit_msg)
-        .add_attribute("action", "tokenize_shares_rental_reply")
-        .add_attribute("proposal_id", active_rental.proposal_id.to_string())
-        .add_attribute("vote_option", active_rental.vote_option.to_string())
-        .add_attribute("locker", locker_addr)
-        .add_attribute("lsm_denom", &lsm_share.denom)
-        .add_attribute("amount", lsm_share.amount))
+/// Block deposits and rentals while a validator is unhealthy
+fn verify_not_in_emergency(storage: &dyn Storage) -> Result<(), ContractError> {
+    let unhealthy = UNHEALTHY_VALIDATORS.may_load(storage)?.unwrap_or_default();
+    if !unhealthy.is_empty() {
+        return Err(ContractError::EmergencyMode {
+            validators: unhealthy.join(","),
+        });
+    }
+    Ok(())
 }
 
-/// Reply handler after tokenizing shares for withdrawal
-/// This sends the LSM shares directly to the user
-fn reply_tokenize_shares_withdraw(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-    let active_withdraw = ACTIVE_WITHDRAW.load(deps.storage)?;
+/// Whether a validator exists and is not jailed
+/// A validator missing from the staking module is unhealthy, as is a jailed one (tombstoned
+/// validators stay jailed). Any other query failure is an error so the permissionless health
+/// check can't flip a healthy pool into emergency mode
+/// The standard validator query doesn't expose the jailed flag, so the staking module is
+/// queried directly for it
+#[allow(deprecated)]
+fn is_validator_healthy(querier: &QuerierWrapper, validator: &str) -> StdResult<bool> {
+    use prost::Message;
+    use staking_proto::{QueryValidatorRequest, QueryValidatorResponse};
 
-    // Query all token balances to find the LSM share
-    // LSM shares have format: {validator}/{record_id}
-    use cosmwasm_std::{AllBalanceResponse, BankQuery, QueryRequest};
-    let all_balances_response: AllBalanceResponse =
-        deps.querier
-            .query(&QueryRequest::Bank(BankQuery::AllBalances {
-                address: env.contract.address.to_string(),
-            }))?;
-    let all_balances = all_balances_response.amount;
+    if querier.query_validator(validator)?.is_none() {
+        return Ok(false);
+    }
 
-    // Find the LSM share token for our specific validator
-    // The denom should start with the validator address followed by '/'
-    let expected_prefix = format!("{}/", config.validator);
-    let lsm_share = all_balances
-        .iter()
-        .find(|coin| coin.denom.starts_with(&expected_prefix))
-        .ok_or(ContractError::InvalidLsmShares {
-            reason: format!(
-                "No LSM share found for validator {} after tokenization",
-                config.validator
-            ),
-        })?;
+    let request = QueryValidatorRequest {
+        validator_addr: validator.to_string(),
+    };
+    let response: Binary = querier.query(&cosmwasm_std::QueryRequest::Stargate {
+        path: VALIDATOR_QUERY_PATH.to_string(),
+        data: Binary::from(request.encode_to_vec()),
+    })?;
+    let response = QueryValidatorResponse::decode(response.as_slice()).map_err(|e| {
+        cosmwasm_std::StdError::parse_err(
+            "QueryValidatorResponse",
+            format!("Failed to decode validator query response: {}", e),
+        )
+    })?;
 
-    // Send the LSM shares directly to the withdrawer
-    let send_msg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: active_withdraw.withdrawer.to_string(),
-        amount: vec![lsm_share.clone()],
-    });
+    Ok(response
+        .validator
+        .is_some_and(|validator| !validator.jailed))
+}
 
-    // Clean up active withdraw
-    ACTIVE_WITHDRAW.remove(deps.storage);
+/// Tokens delegated by the contract to each validator, empty delegations are skipped
+fn query_delegations(querier: &QuerierWrapper, env: &Env) -> StdResult<Vec<(String, Uint128)>> {
+    Ok(querier
+        .query_all_delegations(env.contract.address.clone())?
+        .into_iter()
+        .filter(|delegation| !delegation.amount.amount.is_zero())
+        .map(|delegation| (delegation.validator, delegation.amount.amount))
+        .collect())
+}
 
-    Ok(Response::new()
-        .add_message(send_msg)
-        .add_attribute("action", "tokenize_shares_withdraw_reply")
-        .add_attribute("withdrawer", active_withdraw.withdrawer)
-        .add_attribute("lsm_denom", &lsm_share.denom)
-        .add_attribute("amount", lsm_share.amount))
+/// Delegations of the validators with lockers in a voting session
+fn session_delegations(
+    querier: &QuerierWrapper,
+    env: &Env,
+    config: &Config,
+    voting_session: &VotingSession,
+) -> StdResult<Vec<(String, Uint128)>> {
+    Ok(query_delegations(querier, env)?
+        .into_iter()
+        .filter(|(validator, _)| {
+            *validator == config.validator
+                || voting_session
+                    .validator_lockers
+                    .iter()
+                    .any(|(v, _, _)| v == validator)
+        })
+        .collect())
 }
 
-/// Reply handler after claiming rewards for a deposit
-/// This:
-/// 1. Updates global reward index with rewards received
-/// 2. Calculates and sends any pending rewards to the depositor
-/// 3. Updates staker state with the new deposit
-/// 4. Redeems the LSM shares
-fn reply_claim_rewards_deposit(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-    let active_claim = ACTIVE_CLAIM.load(deps.storage)?;
-    let active_deposit = ACTIVE_DEPOSIT.load(deps.storage)?;
+/// Validator with the most delegated tokens, the first one listed wins ties
+fn largest_delegation(delegations: &[(String, Uint128)]) -> Option<String> {
+    delegations
+        .iter()
+        .rev()
+        .max_by_key(|(_, amount)| *amount)
+        .map(|(validator, _)| validator.clone())
+}
 
-    // Query balance after rewards withdrawal
-    let balance_query: BalanceResponse = deps.querier.query(
-        &BankQuery::Balance {
-            address: env.contract.address.to_string(),
-            denom: config.staking_denom.clone(),
+/// Split an amount of tokens between validators, largest delegation first
+/// The parts are taken from the delegations, returns None if they can't cover the amount
+fn split_across_validators(
+    delegations: &mut [(String, Uint128)],
+    amount: Uint128,
+) -> Option<Vec<(String, Uint128)>> {
+    delegations.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let mut remaining = amount;
+    let mut parts = vec![];
+    for (validator, delegated) in delegations.iter_mut() {
+        let part = remaining.min(*delegated);
+        if part.is_zero() {
+            continue;
         }
-        .into(),
-    )?;
-    let balance_after = balance_query.amount.amount;
+        *delegated -= part;
+        remaining -= part;
+        parts.push((validator.clone(), part));
+    }
+    remaining.is_zero().then_some(parts)
+}
 
-    // Calculate actual rewards received from the validator
-    let rewards_received = balance_after.saturating_sub(active_claim.balance_before);
+/// Messages withdrawing the rewards of every delegation, only the last one replies
+/// Falls back to the primary validator when nothing is delegated
+fn withdraw_rewards_msgs(
+    config: &Config,
+    delegations: &[(String, Uint128)],
+    reply_id: u64,
+) -> Vec<SubMsg> {
+    let mut validators: Vec<String> = delegations.iter().map(|(v, _)| v.clone()).collect();
+    if validators.is_empty() {
+        validators.push(config.validator.clone());
+    }
+    let last = validators.len() - 1;
+    validators
+        .into_iter()
+        .enumerate()
+        .map(|(i, validator)| {
+            let msg =
+                CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward { validator });
+            if i == last {
+                SubMsg::reply_on_success(msg, reply_id)
+            } else {
+                SubMsg::new(msg)
+            }
+        })
+        .collect()
+}
 
-    // Update global reward index with the rewards received BEFORE adding new stake
-    let mut state = STATE.load(deps.storage)?;
-    state.add_rewards(rewards_received);
+/// Locker of a voting session holding the voting power of an option delegated to a validator
+fn session_locker<'a>(
+    config: &Config,
+    voting_session: &'a VotingSession,
+    validator: &str,
+    vote_option: i32,
+) -> Option<&'a Addr> {
+    if validator == config.validator {
+        voting_session.locker_for(vote_option)
+    } else {
+        voting_session.validator_locker_for(validator, vote_option)
+    }
+}
 
-    // Load or create staker
-    let mut staker = STAKERS
-        .may_load(deps.storage, &active_deposit.depositor)?
-        .unwrap_or_else(Staker::new);
+/// Whether every validator of a voting session has a locker for the vote option
+/// Rentals and auction settlement may tokenize the voting power from any of them
+fn session_accepts_option(
+    config: &Config,
+    voting_session: &VotingSession,
+    vote_option: i32,
+) -> bool {
+    std::iter::once(config.validator.as_str())
+        .chain(
+            voting_session
+                .validator_lockers
+                .iter()
+                .map(|(validator, _, _)| validator.as_str()),
+        )
+        .all(|validator| session_locker(config, voting_session, validator, vote_option).is_some())
+}
 
-    // Calculate pending rewards with the updated global index (before changing staked amount)
-    let user_rewards = staker.calculate_pending_rewards(state.global_reward_index);
+/// Format coins as an attribute value
+fn coins_attribute(coins: &[Coin]) -> String {
+    coins
+        .iter()
+        .map(|coin| coin.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
 
-    // NOW add the new deposit to state and staker
-    state.total_staked += active_deposit.amount;
-    staker.staked_amount += active_deposit.amount;
+/// Compute the sha256 commitment of a sealed bid
+/// commitment = sha256("{bidder}:{proposal_id}:{vote_option}:{vp_amount}:{price_per_vp}:{salt}")
+pub fn bid_commitment(bidder: &Addr, proposal_id: u64, bid: &RevealedBid, salt: &str) -> HexBinary {
+    let preimage = format!(
+        "{}:{}:{}:{}:{}:{}",
+        bidder, proposal_id, bid.vote_option, bid.vp_amount, bid.price_per_vp, salt
+    );
+    HexBinary::from(Sha256::digest(preimage.as_bytes()).to_vec())
+}
 
-    // Update staker's reward index to the new global index
-    staker.update_index(state.global_reward_index);
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::StakerInfo { address } => {
+            to_json_binary(&query_staker_info(deps, env.clone(), address)?)
+        }
+        QueryMsg::TotalStaked {} => to_json_binary(&query_total_staked(deps)?),
+        QueryMsg::RewardIndex {} => to_json_binary(&query_reward_index(deps)?),
+        QueryMsg::ExchangeRate {} => to_json_binary(&query_exchange_rate(deps, env)?),
+        QueryMsg::Stakers { start_after, limit } => {
+            to_json_binary(&query_stakers(deps, env, start_after, limit)?)
+        }
+        QueryMsg::RentalQuote {
+            proposal_id,
+            vote_option,
+            vp_amount,
+        } => to_json_binary(&query_rental_quote(
+            deps,
+            proposal_id,
+            vote_option,
+            vp_amount,
+        )?),
+        QueryMsg::Bids {
+            proposal_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_bids(deps, proposal_id, start_after, limit)?),
+        QueryMsg::RentalsByProposal {
+            proposal_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_rentals_by_proposal(
+            deps,
+            proposal_id,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::RentalsByRenter {
+            renter,
+            start_after,
+            limit,
+        } => to_json_binary(&query_rentals_by_renter(deps, renter, start_after, limit)?),
+        QueryMsg::RentalTotals { proposal_id } => {
+            to_json_binary(&query_rental_totals(deps, proposal_id)?)
+        }
+        QueryMsg::Fees {} => to_json_binary(&query_fees(deps)?),
+        QueryMsg::RewardDenoms {} => to_json_binary(&query_reward_denoms(deps)?),
+        QueryMsg::Delegations {} => to_json_binary(&query_delegations_response(deps, env)?),
+        QueryMsg::VotingSession { proposal_id } => {
+            to_json_binary(&query_voting_session(deps, proposal_id)?)
+        }
+    }
+}
 
-    STAKERS.save(deps.storage, &active_deposit.depositor, &staker)?;
-    STATE.save(deps.storage, &state)?;
+/// Calculate the simulated state by querying pending staking rewards
+/// This is used in queries to show accurate pending rewards without modifying state
+fn calculate_simulated_state(
+    deps: Deps,
+    env: &Env,
+    state: &State,
+    config: &Config,
+) -> StdResult<State> {
+    let mut state = state.clone();
+
+    // Account for slashing not yet recorded
+    let delegations = query_delegations(&deps.querier, env)?;
+    record_slashing(deps.storage, &mut state, &delegations)?;
+
+    // Query pending staking rewards from each validator
+    for (validator, _) in delegations {
+        let pending_rewards = deps
+            .querier
+            .query_delegation(env.contract.address.clone(), validator)?
+            .map(|delegation| delegation.accumulated_rewards)
+            .unwrap_or_default();
+
+        for reward in pending_rewards
+            .iter()
+            .filter(|coin| config.is_reward_denom(&coin.denom))
+        {
+            add_staking_rewards(config, &mut state, &reward.denom, reward.amount);
+        }
+    }
 
-    let mut messages = vec![];
-    let response = Response::new()
-        .add_attribute("action", "claim_rewards_deposit_reply")
-        .add_attribute("depositor", active_deposit.depositor.to_string())
-        .add_attribute("rewards_received", rewards_received.to_string())
-        .add_attribute("user_rewards", user_rewards.to_string())
-        .add_attribute("new_staked_amount", staker.staked_amount.to_string());
+    Ok(state)
+}
 
-    // Send rewards to depositor if they have any
-    if !user_rewards.is_zero() {
-        let send_msg = CosmosMsg::Bank(BankMsg::Send {
-            to_address: active_deposit.depositor.to_string(),
-            amount: coins(user_rewards.u128(), config.staking_denom.clone()),
-        });
-        messages.push(send_msg);
+/// Build the info of a staker with the simulated state
+fn staker_info_response(
+    address: Addr,
+    staker: Staker,
+    config: &Config,
+    simulated_state: &State,
+) -> StakerInfoResponse {
+    let (pending_staking_rewards, pending_denom_rewards): (Vec<Coin>, Vec<Coin>) = staker
+        .calculate_pending_rewards(&simulated_state.reward_indices)
+        .into_iter()
+        .partition(|coin| coin.denom == config.staking_denom);
+    let pending_staking_rewards = pending_staking_rewards
+        .first()
+        .map(|coin| coin.amount)
+        .unwrap_or_default();
+    let pending_rental_income =
+        staker.calculate_pending_rental_income(simulated_state.rental_index);
+
+    StakerInfoResponse {
+        address,
+        staked_amount: staker.staked_amount,
+        staked_tokens: simulated_state.tokens_for_shares(staker.staked_amount),
+        reward_index: staker
+            .reward_indices
+            .get(&config.staking_denom)
+            .copied()
+            .unwrap_or_default(),
+        rental_index: staker.rental_index,
+        pending_rewards: pending_staking_rewards + pending_rental_income,
+        pending_staking_rewards,
+        pending_rental_income,
+        pending_denom_rewards,
+        auto_compound: staker.auto_compound,
     }
+}
 
-    // Clean up active claim
-    ACTIVE_CLAIM.remove(deps.storage);
-
-    // Now redeem the LSM shares
-    let redeem_msg = create_redeem_tokens_msg(
-        env.contract.address.to_string(),
-        active_deposit.lsm_denom.clone(),
-        active_deposit.amount,
-    )?;
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+    let is_paused = IS_PAUSED.load(deps.storage)?;
+    let unhealthy_validators = UNHEALTHY_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
 
-    Ok(response
-        .add_messages(messages)
-        .add_submessage(SubMsg::reply_on_success(
-            redeem_msg,
-            REPLY_REDEEM_SHARES_DEPOSIT,
-        )))
+    Ok(ConfigResponse {
+        global_reward_index: state.reward_index(&config.staking_denom),
+        fee_collector: config.fee_collector().clone(),
+        owner: config.owner,
+        staking_denom: config.staking_denom,
+        validator: config.validator,
+        validators: config.validators,
+        max_cap: config.max_cap,
+        locker_code_id: config.locker_code_id,
+        rental_pricing: config.rental_pricing,
+        keeper_tip: config.keeper_tip,
+        reward_denoms: config.reward_denoms,
+        rental_fee_rate: config.rental_fee_rate,
+        reward_fee_rate: config.reward_fee_rate,
+        fallback_validator: config.fallback_validator,
+        max_rent_fraction: config.max_rent_fraction,
+        total_staked: state.total_staked,
+        is_paused,
+        unhealthy_validators,
+    })
 }
 
-/// Reply handler after redeeming LSM shares for deposit
-/// This just cleans up the active deposit state (if it exists)
-fn reply_redeem_shares_deposit(deps: DepsMut, _env: Env) -> Result<Response, ContractError> {
-    // Try to load active deposit - it might not exist if this was a first deposit
-    let active_deposit_opt = ACTIVE_DEPOSIT.may_load(deps.storage)?;
+fn query_staker_info(deps: Deps, env: Env, address: String) -> StdResult<StakerInfoResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
 
-    // Clean up active deposit if it exists
-    if active_deposit_opt.is_some() {
-        ACTIVE_DEPOSIT.remove(deps.storage);
-    }
+    let staker = STAKERS.load(deps.storage, &addr)?;
 
-    let mut response = Response::new().add_attribute("action", "redeem_shares_deposit_reply");
+    // Calculate simulated indices including pending staking rewards
+    let simulated_state = calculate_simulated_state(deps, &env, &state, &config)?;
 
-    if let Some(active_deposit) = active_deposit_opt {
-        response = response
-            .add_attribute("depositor", active_deposit.depositor)
-            .add_attribute("lsm_denom", active_deposit.lsm_denom)
-            .add_attribute("amount", active_deposit.amount);
-    }
+    // Calculate pending rewards using the simulated indices
+    Ok(staker_info_response(
+        addr,
+        staker,
+        &config,
+        &simulated_state,
+    ))
+}
 
-    Ok(response)
+fn query_total_staked(deps: Deps) -> StdResult<TotalStakedResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(TotalStakedResponse {
+        total_staked: state.total_staked,
+    })
 }
 
-/// Reply handler after instantiating a locker contract
-/// This:
-/// 1. Parses the payload to get proposal_id and vote_option
-/// 2. Parses the reply to get the instantiated contract address
-/// 3. Updates the active voting session creation with this address
-/// 4. If all lockers are created, creates the VotingSession and cleans up
-fn reply_instantiate_locker(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
-    // Parse the payload to get the vote option
-    let payload: LockerInstantiatePayload = cosmwasm_std::from_json(&msg.payload)?;
+fn query_reward_index(deps: Deps) -> StdResult<RewardIndexResponse> {
+    let state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    Ok(RewardIndexResponse {
+        global_reward_index: state.reward_index(&config.staking_denom),
+        reward_indices: state.reward_indices,
+        rental_index: state.rental_index,
+    })
+}
 
-    // Parse the reply result to get the contract address
-    let res = msg
-        .result
-        .into_result()
-        .map_err(|e| ContractError::InvalidLsmShares {
-            reason: format!("Failed to instantiate locker: {}", e),
-        })?;
+/// Query the exchange rate of the pool shares, including slashing not yet recorded
+fn query_exchange_rate(deps: Deps, env: Env) -> StdResult<ExchangeRateResponse> {
+    let mut state = STATE.load(deps.storage)?;
+    let delegations = query_delegations(&deps.querier, &env)?;
+    record_slashing(deps.storage, &mut state, &delegations)?;
+
+    Ok(ExchangeRateResponse {
+        exchange_rate: state.exchange_rate(),
+        total_tokens: state.total_tokens,
+        total_shares: state.total_staked,
+        delegated_tokens: delegations.iter().map(|(_, amount)| *amount).sum(),
+    })
+}
 
-    // Extract contract address from MsgInstantiateContractResponse
-    // The address is in the events under wasm-instantiate with _contract_address attribute
-    let contract_address = res
-        .events
-        .iter()
-        .find(|e| e.ty == "instantiate")
-        .and_then(|e| {
-            e.attributes
-                .iter()
-                .find(|attr| attr.key == "_contract_address")
-                .map(|attr| attr.value.clone())
-        })
-        .ok_or(ContractError::InvalidLsmShares {
-            reason: "Could not find contract address in instantiate reply".to_string(),
-        })?;
+fn query_stakers(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<StakersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
 
-    let locker_addr = deps.api.addr_validate(&contract_address)?;
+    // Calculate simulated indices including pending staking rewards
+    let simulated_state = calculate_simulated_state(deps, &env, &state, &config)?;
 
-    // Load and update the active voting session creation
-    let mut active_creation = ACTIVE_VOTING_SESSION_CREATION.load(deps.storage)?;
+    let stakers: Vec<StakerInfoResponse> = if let Some(s) = start_after {
+        let addr = deps.api.addr_validate(&s)?;
+        STAKERS
+            .range(
+                deps.storage,
+                Some(Bound::exclusive(&addr)),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| {
+                let (addr, staker) = item?;
+                Ok(staker_info_response(
+                    addr,
+                    staker,
+                    &config,
+                    &simulated_state,
+                ))
+            })
+            .collect::<StdResult<Vec<_>>>()?
+    } else {
+        STAKERS
+            .range(deps.storage, None, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (addr, staker) = item?;
+                Ok(staker_info_response(
+                    addr,
+                    staker,
+                    &config,
+                    &simulated_state,
+                ))
+            })
+            .collect::<StdResult<Vec<_>>>()?
+    };
 
-    // Verify this is for the same proposal
-    if active_creation.proposal_id != payload.proposal_id {
-        return Err(ContractError::InvalidLsmShares {
-            reason: format!(
-                "Payload proposal_id {} does not match active creation proposal_id {}",
-                payload.proposal_id, active_creation.proposal_id
-            ),
-        });
+    Ok(StakersResponse { stakers })
+}
+
+fn query_rental_quote(
+    deps: Deps,
+    proposal_id: u64,
+    vote_option: i32,
+    vp_amount: Uint128,
+) -> StdResult<RentalQuoteResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+
+    // Only quote options that can actually be rented
+    let voting_session = VOTING_SESSIONS.load(deps.storage, proposal_id)?;
+    if voting_session.auction.is_some() {
+        return Err(cosmwasm_std::StdError::generic_err(format!(
+            "Voting power for proposal {} is allocated by sealed-bid auction",
+            proposal_id
+        )));
+    }
+    if !session_accepts_option(&config, &voting_session, vote_option) {
+        return Err(cosmwasm_std::StdError::generic_err(format!(
+            "Locker not found for proposal {} and vote option {}",
+            proposal_id, vote_option
+        )));
+    }
+
+    let cost = config.rental_pricing.for_proposal(proposal_id).cost(
+        vp_amount,
+        voting_session.rented_amount,
+        state.total_tokens,
+    )?;
+    let price_per_vp = if vp_amount.is_zero() {
+        cosmwasm_std::Decimal256::zero()
+    } else {
+        cosmwasm_std::Decimal256::from_ratio(cost, vp_amount)
+    };
+
+    Ok(RentalQuoteResponse {
+        proposal_id,
+        vote_option,
+        vp_amount,
+        cost,
+        price_per_vp,
+    })
+}
+
+fn query_bids(
+    deps: Deps,
+    proposal_id: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BidsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_addr = start_after
+        .map(|s| deps.api.addr_validate(&s))
+        .transpose()?;
+
+    let bids = BIDS
+        .prefix(proposal_id)
+        .range(
+            deps.storage,
+            start_addr.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(_, bid)| bid))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(BidsResponse { bids })
+}
+
+fn query_rentals_by_proposal(
+    deps: Deps,
+    proposal_id: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RentalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let rentals = rentals()
+        .idx
+        .proposal
+        .prefix(proposal_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, rental)| rental))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RentalsResponse { rentals })
+}
+
+fn query_rentals_by_renter(
+    deps: Deps,
+    renter: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RentalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let renter = deps.api.addr_validate(&renter)?;
+    let start = start_after.map(Bound::exclusive);
+
+    let rentals = rentals()
+        .idx
+        .renter
+        .prefix(renter)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, rental)| rental))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RentalsResponse { rentals })
+}
+
+fn query_rental_totals(deps: Deps, proposal_id: u64) -> StdResult<RentalTotalsResponse> {
+    let totals = RENTAL_TOTALS
+        .prefix(proposal_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, total)| total))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RentalTotalsResponse {
+        proposal_id,
+        totals,
+    })
+}
+
+fn query_fees(deps: Deps) -> StdResult<FeesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+
+    Ok(FeesResponse {
+        fee_collector: config.fee_collector().clone(),
+        rental_fee_rate: config.rental_fee_rate,
+        reward_fee_rate: config.reward_fee_rate,
+        accrued_fees: accrued_fees(&state),
+    })
+}
+
+fn query_reward_denoms(deps: Deps) -> StdResult<RewardDenomsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut denoms = vec![config.staking_denom];
+    denoms.extend(config.reward_denoms);
+
+    Ok(RewardDenomsResponse { denoms })
+}
+
+fn query_delegations_response(deps: Deps, env: Env) -> StdResult<DelegationsResponse> {
+    let delegations = query_delegations(&deps.querier, &env)?
+        .into_iter()
+        .map(|(validator, amount)| ValidatorDelegation { validator, amount })
+        .collect();
+
+    Ok(DelegationsResponse { delegations })
+}
+
+fn query_voting_session(deps: Deps, proposal_id: u64) -> StdResult<VotingSessionResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+    let voting_session = VOTING_SESSIONS.load(deps.storage, proposal_id)?;
+
+    Ok(VotingSessionResponse {
+        deployed_amount: voting_session.deployed_amount(),
+        max_rent_amount: config.max_rent_amount(state.total_tokens),
+        voting_session,
+    })
+}
+
+/// Parse LSM denom and validate format
+/// LSM denom format: {validator_address}/{record_id}
+/// Example: cosmosvaloper1abc.../123
+fn parse_lsm_denom(lsm_denom: &str) -> Result<LsmShareInfo, ContractError> {
+    let parts: Vec<&str> = lsm_denom.split('/').collect();
+
+    if parts.len() != 2 {
+        return Err(ContractError::InvalidLsmShares {
+            reason: format!(
+                "Invalid LSM denom format. Expected 'validator/record_id', got '{}'",
+                lsm_denom
+            ),
+        });
+    }
+
+    let validator = parts[0].to_string();
+    let record_id = parts[1].to_string();
+
+    // Validate validator address format (should start with valoper prefix)
+    if !validator.starts_with("cosmosvaloper") && !validator.starts_with("osmosisvaloper") {
+        return Err(ContractError::InvalidLsmShares {
+            reason: format!(
+                "Invalid validator address format. Expected valoper address, got '{}'",
+                validator
+            ),
+        });
+    }
+
+    // Validate record_id is numeric
+    if record_id.parse::<u64>().is_err() {
+        return Err(ContractError::InvalidLsmShares {
+            reason: format!(
+                "Invalid record_id. Expected numeric value, got '{}'",
+                record_id
+            ),
+        });
+    }
+
+    Ok(LsmShareInfo {
+        validator,
+        record_id,
+    })
+}
+
+/// Verify that the validator exists on chain
+fn verify_validator_exists(querier: &QuerierWrapper, validator: &str) -> Result<(), ContractError> {
+    // The staking module answers an unknown validator with an empty response
+    querier
+        .query_validator(validator)
+        .ok()
+        .flatten()
+        .ok_or(ContractError::ValidatorNotFound {
+            validator: validator.to_string(),
+        })?;
+
+    Ok(())
+}
+
+/// Verify that a proposal is in its voting period and return it
+fn verify_proposal_in_voting(
+    querier: &QuerierWrapper,
+    env: &Env,
+    proposal_id: u64,
+) -> Result<GovProposal, ContractError> {
+    let proposal = query_proposal(querier, proposal_id)
+        .map_err(|_| ContractError::ProposalNotFound { proposal_id })?
+        .ok_or(ContractError::ProposalNotFound { proposal_id })?;
+
+    if proposal.status != ProposalStatus::VotingPeriod {
+        return Err(ContractError::ProposalNotInVoting {
+            proposal_id,
+            status: proposal.status.to_string(),
+        });
+    }
+
+    // A proposal can still be in VOTING_PERIOD after its end time until the gov EndBlocker runs
+    if proposal.voting_end_time <= env.block.time {
+        return Err(ContractError::ProposalVotingEnded { proposal_id });
+    }
+
+    Ok(proposal)
+}
+
+/// Verify that a proposal is finished or doesn't exist anymore
+/// Finished means status is PASSED (3), REJECTED (4), or FAILED (5)
+/// If the proposal doesn't exist (query fails), we allow the destroy (proposal was purged)
+fn verify_proposal_finished(
+    querier: &QuerierWrapper,
+    proposal_id: u64,
+) -> Result<(), ContractError> {
+    match query_proposal(querier, proposal_id) {
+        Ok(Some(proposal)) if !proposal.status.is_finished() => {
+            // Proposal is still active (DEPOSIT_PERIOD or VOTING_PERIOD)
+            Err(ContractError::ProposalStillActive {
+                proposal_id,
+                status: proposal.status.to_string(),
+            })
+        }
+        // Proposal is finished, or doesn't exist anymore (was purged)
+        _ => Ok(()),
+    }
+}
+
+/// Create MsgRedeemTokensForShares message for redeeming LSM shares
+/// This uses the gaia.liquid.v1beta1.MsgRedeemTokensForShares proto
+fn create_redeem_tokens_msg(
+    delegator_address: String,
+    denom: String,
+    amount: Uint128,
+) -> Result<CosmosMsg, ContractError> {
+    use prost::Message;
+
+    // Proto definition for MsgRedeemTokensForShares
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgRedeemTokensForShares {
+        #[prost(string, tag = "1")]
+        pub delegator_address: String,
+        #[prost(message, required, tag = "2")]
+        pub amount: ProtoCoin,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ProtoCoin {
+        #[prost(string, tag = "1")]
+        pub denom: String,
+        #[prost(string, tag = "2")]
+        pub amount: String,
+    }
+
+    let msg = MsgRedeemTokensForShares {
+        delegator_address,
+        amount: ProtoCoin {
+            denom,
+            amount: amount.to_string(),
+        },
+    };
+
+    // Encode the message
+    let mut buf = Vec::new();
+    msg.encode(&mut buf)
+        .map_err(|e| ContractError::InvalidLsmShares {
+            reason: format!("Failed to encode MsgRedeemTokensForShares: {}", e),
+        })?;
+
+    // Create Any message with the correct type URL
+    // The package is gaia.liquid.v1beta1 as defined in the proto file
+    Ok(CosmosMsg::Any(cosmwasm_std::AnyMsg {
+        type_url: "/gaia.liquid.v1beta1.MsgRedeemTokensForShares".to_string(),
+        value: Binary::from(buf),
+    }))
+}
+
+/// Tokens delegated by a MsgRedeemTokensForShares, read from its response
+/// Chains that do not report msg_responses yet return it in the data field
+#[allow(deprecated)]
+fn parse_redeemed_tokens(msg: Reply) -> Result<Uint128, ContractError> {
+    use prost::Message;
+
+    // Proto definition for MsgRedeemTokensForSharesResponse
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgRedeemTokensForSharesResponse {
+        #[prost(message, required, tag = "1")]
+        pub amount: ProtoCoin,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ProtoCoin {
+        #[prost(string, tag = "1")]
+        pub denom: String,
+        #[prost(string, tag = "2")]
+        pub amount: String,
+    }
+
+    let res = msg
+        .result
+        .into_result()
+        .map_err(|e| ContractError::InvalidLsmShares {
+            reason: format!("Failed to redeem LSM shares: {}", e),
+        })?;
+    let data = res
+        .msg_responses
+        .into_iter()
+        .find(|response| {
+            response.type_url == "/gaia.liquid.v1beta1.MsgRedeemTokensForSharesResponse"
+        })
+        .map(|response| response.value)
+        .or(res.data)
+        .ok_or(ContractError::InvalidLsmShares {
+            reason: "Missing MsgRedeemTokensForSharesResponse".to_string(),
+        })?;
+
+    let response = MsgRedeemTokensForSharesResponse::decode(data.as_slice()).map_err(|e| {
+        ContractError::InvalidLsmShares {
+            reason: format!("Failed to decode MsgRedeemTokensForSharesResponse: {}", e),
+        }
+    })?;
+    response
+        .amount
+        .amount
+        .parse::<u128>()
+        .map(Uint128::new)
+        .map_err(|e| ContractError::InvalidLsmShares {
+            reason: format!("Invalid redeemed amount: {}", e),
+        })
+}
+
+/// Create MsgTokenizeShares message to convert delegation to LSM shares
+/// This uses the gaia.liquid.v1beta1.MsgTokenizeShares proto
+fn create_tokenize_shares_msg(
+    delegator_address: String,
+    validator_address: String,
+    denom: String,
+    amount: Uint128,
+    tokenized_share_owner: String,
+) -> Result<CosmosMsg, ContractError> {
+    use prost::Message;
+
+    // Proto definition for MsgTokenizeShares
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgTokenizeShares {
+        #[prost(string, tag = "1")]
+        pub delegator_address: String,
+        #[prost(string, tag = "2")]
+        pub validator_address: String,
+        #[prost(message, required, tag = "3")]
+        pub amount: ProtoCoin,
+        #[prost(string, tag = "4")]
+        pub tokenized_share_owner: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ProtoCoin {
+        #[prost(string, tag = "1")]
+        pub denom: String,
+        #[prost(string, tag = "2")]
+        pub amount: String,
+    }
+
+    let msg = MsgTokenizeShares {
+        delegator_address,
+        validator_address,
+        amount: ProtoCoin {
+            denom,
+            amount: amount.to_string(),
+        },
+        tokenized_share_owner,
+    };
+
+    // Encode the message
+    let mut buf = Vec::new();
+    msg.encode(&mut buf)
+        .map_err(|e| ContractError::InvalidLsmShares {
+            reason: format!("Failed to encode MsgTokenizeShares: {}", e),
+        })?;
+
+    // Create Any message with the correct type URL
+    Ok(CosmosMsg::Any(cosmwasm_std::AnyMsg {
+        type_url: "/gaia.liquid.v1beta1.MsgTokenizeShares".to_string(),
+        value: Binary::from(buf),
+    }))
+}
+
+#[entry_point]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        REPLY_CLAIM_REWARDS => reply_claim_rewards(deps, env),
+        REPLY_TOKENIZE_SHARES_RENTAL => reply_tokenize_shares_rental(deps, env),
+        REPLY_TOKENIZE_SHARES_WITHDRAW => reply_tokenize_shares_withdraw(deps, env, msg),
+        REPLY_CLAIM_REWARDS_DEPOSIT => reply_claim_rewards_deposit(deps, env),
+        REPLY_REDEEM_SHARES_DEPOSIT => reply_redeem_shares_deposit(deps, env, msg),
+        REPLY_INSTANTIATE_LOCKER => reply_instantiate_locker(deps, env, msg),
+        REPLY_TOKENIZE_SHARES_AUCTION => reply_tokenize_shares_auction(deps, env, msg),
+        REPLY_COMPOUND => reply_compound(deps, env),
+        REPLY_REBALANCE => reply_rebalance(deps, env),
+        _ => Err(ContractError::InvalidLsmShares {
+            reason: format!("Unknown reply ID: {}", msg.id),
+        }),
+    }
+}
+
+/// Reply handler after withdrawing rewards from the validators
+/// This:
+/// 1. Calculates the rewards received from the validators
+/// 2. Updates the global reward index with these rewards
+/// 3. Settles the user's pending rewards with the new index, compounding them for
+///    auto-compounders unless a voting session or emergency mode locks the delegation
+/// 4. Updates user state and sends rewards
+/// 5. If this is a withdraw (not just a claim), also tokenizes shares, largest delegation first
+fn reply_claim_rewards(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let active_claim = ACTIVE_CLAIM.load(deps.storage)?;
+
+    // Calculate actual rewards received from the validator in each reward denom
+    let balances_after = query_reward_balances(&deps.querier, &env, &config)?;
+    let rewards_received = rewards_received(&active_claim.balances_before, balances_after);
+
+    // Update the reward indices with the rewards received
+    let mut state = STATE.load(deps.storage)?;
+    for reward in &rewards_received {
+        add_staking_rewards(&config, &mut state, &reward.denom, reward.amount);
+    }
+
+    // NOW settle the user's pending rewards with the updated indices
+    // This includes both:
+    // 1. Rewards that were pending before the claim
+    // 2. Rewards from this claim (from rewards_received)
+    // Compounding delegates to the primary validator, so while a voting session locks the
+    // delegation or in emergency mode the rewards of auto-compounders are paid out instead
+    let mut staker = STAKERS.load(deps.storage, &active_claim.claimer)?;
+    let can_compound = !IS_PAUSED.load(deps.storage)?
+        && UNHEALTHY_VALIDATORS
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .is_empty();
+    let (compounded, user_rewards) = if can_compound {
+        settle_rewards(&config, &mut state, &mut staker)
+    } else {
+        let rewards = staker.calculate_pending_income(&state, &config.staking_denom);
+        staker.update_index(&state);
+        (Uint128::zero(), rewards)
+    };
+    STAKERS.save(deps.storage, &active_claim.claimer, &staker)?;
+    STATE.save(deps.storage, &state)?;
+
+    let mut messages = vec![];
+    let mut response = Response::new()
+        .add_attribute("action", "rewards_claimed")
+        .add_attribute("user", active_claim.claimer.to_string())
+        .add_attribute("rewards_received", coins_attribute(&rewards_received))
+        .add_attribute("user_amount", coins_attribute(&user_rewards))
+        .add_attribute("compounded", compounded);
+
+    // Send rewards to user if they have any
+    messages.extend(send_rewards(&active_claim.claimer, user_rewards));
+    messages.extend(delegate_compounded(&config, compounded));
+
+    // Check if this is part of a withdraw operation
+    if let Some(withdraw_amount) = active_claim.withdraw_amount {
+        let mut delegations = query_delegations(&deps.querier, &env)?;
+        let available: Uint128 = delegations.iter().map(|(_, amount)| *amount).sum();
+        let parts = split_across_validators(&mut delegations, withdraw_amount).ok_or(
+            ContractError::InsufficientStakedTokens {
+                available,
+                required: withdraw_amount,
+            },
+        )?;
+
+        // Create tokenize shares messages to convert the delegations to LSM shares
+        for (validator, amount) in parts {
+            let tokenize_msg = create_tokenize_shares_msg(
+                env.contract.address.to_string(),
+                validator.clone(),
+                config.staking_denom.clone(),
+                amount,
+                env.contract.address.to_string(), // Send to self first, then forward in reply
+            )?;
+            let payload = WithdrawTokenizePayload {
+                withdrawer: active_claim.claimer.clone(),
+                validator,
+            };
+
+            response = response.add_submessage(
+                SubMsg::reply_on_success(tokenize_msg, REPLY_TOKENIZE_SHARES_WITHDRAW)
+                    .with_payload(to_json_binary(&payload)?),
+            );
+        }
+
+        response = response.add_attribute("withdraw_amount", withdraw_amount);
+    }
+
+    // Clean up active claim
+    ACTIVE_CLAIM.remove(deps.storage);
+
+    Ok(response.add_messages(messages))
+}
+
+/// Reply handler after tokenizing shares for rental
+/// This sends the LSM shares to the corresponding locker via DepositLsmShares
+fn reply_tokenize_shares_rental(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let active_rental = ACTIVE_RENTAL.load(deps.storage)?;
+
+    let response = forward_lsm_shares_to_locker(
+        deps.as_ref(),
+        &env,
+        active_rental.proposal_id,
+        active_rental.vote_option,
+        &active_rental.validator,
+    )?;
+
+    // Clean up active rental
+    ACTIVE_RENTAL.remove(deps.storage);
+
+    Ok(response.add_attribute("action", "tokenize_shares_rental_reply"))
+}
+
+/// Reply handler after tokenizing the voting power allocated to an option by an auction
+/// This sends the LSM shares to the corresponding locker via DepositLsmShares
+fn reply_tokenize_shares_auction(
+    deps: DepsMut,
+    env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    let payload: AuctionTokenizePayload = cosmwasm_std::from_json(&msg.payload)?;
+
+    let response = forward_lsm_shares_to_locker(
+        deps.as_ref(),
+        &env,
+        payload.proposal_id,
+        payload.vote_option,
+        &payload.validator,
+    )?;
+
+    Ok(response.add_attribute("action", "tokenize_shares_auction_reply"))
+}
+
+/// Send the freshly tokenized LSM shares of a validator held by the contract
+/// to the locker of a vote option
+#[allow(deprecated)]
+fn forward_lsm_shares_to_locker(
+    deps: Deps,
+    env: &Env,
+    proposal_id: u64,
+    vote_option: i32,
+    validator: &str,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Load voting session to get locker address
+    let voting_session = VOTING_SESSIONS
+        .load(deps.storage, proposal_id)
+        .map_err(|_| ContractError::NoVotingSession { proposal_id })?;
+
+    // Find the locker address for this vote option and validator
+    let locker_addr = session_locker(&config, &voting_session, validator, vote_option).ok_or(
+        ContractError::LockerNotFound {
+            proposal_id,
+            vote_option,
+        },
+    )?;
+
+    // Query all token balances to find the LSM share
+    // LSM shares have format: {validator}/{record_id}
+    use cosmwasm_std::{AllBalanceResponse, BankQuery, QueryRequest};
+    let all_balances_response: AllBalanceResponse =
+        deps.querier
+            .query(&QueryRequest::Bank(BankQuery::AllBalances {
+                address: env.contract.address.to_string(),
+            }))?;
+    let all_balances = all_balances_response.amount;
+
+    // Find the LSM share token for the validator
+    // The denom should start with the validator address followed by '/'
+    let expected_prefix = format!("{}/", validator);
+    let lsm_share = all_balances
+        .iter()
+        .find(|coin| coin.denom.starts_with(&expected_prefix))
+        .ok_or(ContractError::InvalidLsmShares {
+            reason: format!(
+                "No LSM share found for validator {} after tokenization",
+                validator
+            ),
+        })?;
+
+    // Call DepositLsmShares on the locker with the LSM shares
+    use cosmwasm_std::WasmMsg;
+    use proposal_locker_types::ExecuteMsg as LockerExecuteMsg;
+
+    let deposit_msg = WasmMsg::Execute {
+        contract_addr: locker_addr.to_string(),
+        msg: to_json_binary(&LockerExecuteMsg::DepositLsmShares {})?,
+        funds: vec![lsm_share.clone()],
+    };
+
+    let mut response = Response::new().add_message(deposit_msg);
+
+    // Weighted lockers re-cast their vote following the rented amount of each option
+    if voting_session.weighted_options.is_some() {
+        let rented_amounts = RENTAL_TOTALS
+            .prefix(proposal_id)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(option, total)| (option, total.vp_amount)))
+            .collect::<StdResult<Vec<_>>>()?;
+        let update_vote_msg = to_json_binary(&LockerExecuteMsg::UpdateVote {
+            weights: vote_weights(rented_amounts),
+        })?;
+        for weighted_locker in voting_session.all_lockers() {
+            response = response.add_message(WasmMsg::Execute {
+                contract_addr: weighted_locker.to_string(),
+                msg: update_vote_msg.clone(),
+                funds: vec![],
+            });
+        }
+    }
+
+    Ok(response
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("vote_option", vote_option.to_string())
+        .add_attribute("validator", validator)
+        .add_attribute("locker", locker_addr)
+        .add_attribute("lsm_denom", &lsm_share.denom)
+        .add_attribute("amount", lsm_share.amount))
+}
+
+/// Reply handler after tokenizing the shares of a validator for withdrawal
+/// This sends the LSM shares directly to the user
+#[allow(deprecated)]
+fn reply_tokenize_shares_withdraw(
+    deps: DepsMut,
+    env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    let payload: WithdrawTokenizePayload = cosmwasm_std::from_json(&msg.payload)?;
+
+    // Query all token balances to find the LSM share
+    // LSM shares have format: {validator}/{record_id}
+    use cosmwasm_std::{AllBalanceResponse, BankQuery, QueryRequest};
+    let all_balances_response: AllBalanceResponse =
+        deps.querier
+            .query(&QueryRequest::Bank(BankQuery::AllBalances {
+                address: env.contract.address.to_string(),
+            }))?;
+    let all_balances = all_balances_response.amount;
+
+    // Find the LSM share token for the validator
+    // The denom should start with the validator address followed by '/'
+    let expected_prefix = format!("{}/", payload.validator);
+    let lsm_share = all_balances
+        .iter()
+        .find(|coin| coin.denom.starts_with(&expected_prefix))
+        .ok_or(ContractError::InvalidLsmShares {
+            reason: format!(
+                "No LSM share found for validator {} after tokenization",
+                payload.validator
+            ),
+        })?;
+
+    // Send the LSM shares directly to the withdrawer
+    let send_msg = CosmosMsg::Bank(BankMsg::Send {
+        to_address: payload.withdrawer.to_string(),
+        amount: vec![lsm_share.clone()],
+    });
+
+    Ok(Response::new()
+        .add_message(send_msg)
+        .add_attribute("action", "tokenize_shares_withdraw_reply")
+        .add_attribute("withdrawer", payload.withdrawer)
+        .add_attribute("validator", payload.validator)
+        .add_attribute("lsm_denom", &lsm_share.denom)
+        .add_attribute("amount", lsm_share.amount))
+}
+
+/// Reply handler after withdrawing rewards for compounding
+/// Adds the rewards received to the reward indices, then compounds the next batch
+fn reply_compound(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let active_compound = ACTIVE_COMPOUND.load(deps.storage)?;
+    ACTIVE_COMPOUND.remove(deps.storage);
+
+    let balances_after = query_reward_balances(&deps.querier, &env, &config)?;
+    let rewards_received = rewards_received(&active_compound.balances_before, balances_after);
+
+    let mut state = STATE.load(deps.storage)?;
+    for reward in &rewards_received {
+        add_staking_rewards(&config, &mut state, &reward.denom, reward.amount);
+    }
+    STATE.save(deps.storage, &state)?;
+
+    let response = compound_batch(deps, &config)?;
+    Ok(response
+        .add_attribute("action", "compound_reply")
+        .add_attribute("rewards_received", coins_attribute(&rewards_received)))
+}
+
+/// Reply handler after the redelegations of a rebalance
+/// Redelegating withdraws the pending rewards, they are added to the reward indices
+fn reply_rebalance(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let active_rebalance = ACTIVE_REBALANCE.load(deps.storage)?;
+    ACTIVE_REBALANCE.remove(deps.storage);
+
+    let balances_after = query_reward_balances(&deps.querier, &env, &config)?;
+    let rewards_received = rewards_received(&active_rebalance.balances_before, balances_after);
+
+    let mut state = STATE.load(deps.storage)?;
+    for reward in &rewards_received {
+        add_staking_rewards(&config, &mut state, &reward.denom, reward.amount);
+    }
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "rebalance_reply")
+        .add_attribute("rewards_received", coins_attribute(&rewards_received)))
+}
+
+/// Reply handler after claiming rewards for a deposit
+/// This:
+/// 1. Updates global reward index with rewards received
+/// 2. Settles pending rewards, compounding them for auto-compounders
+/// 3. Redeems the LSM shares, the deposit is credited in the redeem reply
+fn reply_claim_rewards_deposit(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let active_claim = ACTIVE_CLAIM.load(deps.storage)?;
+    let active_deposit = ACTIVE_DEPOSIT.load(deps.storage)?;
+
+    // Calculate actual rewards received from the validator in each reward denom
+    let balances_after = query_reward_balances(&deps.querier, &env, &config)?;
+    let rewards_received = rewards_received(&active_claim.balances_before, balances_after);
+
+    // Update the reward indices with the rewards received BEFORE adding new stake
+    let mut state = STATE.load(deps.storage)?;
+    for reward in &rewards_received {
+        add_staking_rewards(&config, &mut state, &reward.denom, reward.amount);
+    }
+
+    // Load or create staker
+    let mut staker = STAKERS
+        .may_load(deps.storage, &active_deposit.depositor)?
+        .unwrap_or_else(Staker::new);
+
+    // Settle pending rewards with the updated indices (before changing staked amount)
+    // The deposit is credited once the redeem reports the tokens actually delegated
+    let (compounded, user_rewards) = settle_rewards(&config, &mut state, &mut staker);
+
+    STAKERS.save(deps.storage, &active_deposit.depositor, &staker)?;
+    STATE.save(deps.storage, &state)?;
+
+    let mut messages = vec![];
+    let response = Response::new()
+        .add_attribute("action", "claim_rewards_deposit_reply")
+        .add_attribute("depositor", active_deposit.depositor.to_string())
+        .add_attribute("rewards_received", coins_attribute(&rewards_received))
+        .add_attribute("user_rewards", coins_attribute(&user_rewards))
+        .add_attribute("compounded", compounded);
+
+    // Send rewards to depositor if they have any
+    messages.extend(send_rewards(&active_deposit.depositor, user_rewards));
+    messages.extend(delegate_compounded(&config, compounded));
+
+    // Clean up active claim
+    ACTIVE_CLAIM.remove(deps.storage);
+
+    // Now redeem the LSM shares
+    let redeem_msg = create_redeem_tokens_msg(
+        env.contract.address.to_string(),
+        active_deposit.lsm_denom.clone(),
+        active_deposit.amount,
+    )?;
+
+    Ok(response
+        .add_messages(messages)
+        .add_submessage(SubMsg::reply_on_success(
+            redeem_msg,
+            REPLY_REDEEM_SHARES_DEPOSIT,
+        )))
+}
+
+/// Reply handler after redeeming LSM shares for deposit
+/// LSM amounts are delegation shares of the validator, worth less than a token each once it
+/// was slashed, so the depositor is credited with the tokens the redeem actually delegated
+/// and receives pool shares for them at the exchange rate
+fn reply_redeem_shares_deposit(
+    deps: DepsMut,
+    _env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    let active_deposit = ACTIVE_DEPOSIT.load(deps.storage)?;
+    let tokens = parse_redeemed_tokens(msg)?;
+
+    let mut state = STATE.load(deps.storage)?;
+    let mut staker = STAKERS
+        .may_load(deps.storage, &active_deposit.depositor)?
+        .unwrap_or_else(Staker::new);
+
+    // Rewards were settled before the redeem, so only the stake changes
+    let shares = state.shares_for_tokens(tokens);
+    state.total_staked += shares;
+    state.total_tokens += tokens;
+    staker.staked_amount += shares;
+
+    STAKERS.save(deps.storage, &active_deposit.depositor, &staker)?;
+    STATE.save(deps.storage, &state)?;
+    ACTIVE_DEPOSIT.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "redeem_shares_deposit_reply")
+        .add_attribute("depositor", active_deposit.depositor)
+        .add_attribute("lsm_denom", active_deposit.lsm_denom)
+        .add_attribute("amount", active_deposit.amount)
+        .add_attribute("tokens", tokens)
+        .add_attribute("shares", shares)
+        .add_attribute("new_staked_amount", staker.staked_amount))
+}
+
+/// Reply handler after instantiating a locker contract
+/// This:
+/// 1. Parses the payload to get proposal_id and vote_option
+/// 2. Parses the reply to get the instantiated contract address
+/// 3. Updates the active voting session creation with this address
+/// 4. If all lockers are created, creates the VotingSession and cleans up
+fn reply_instantiate_locker(
+    deps: DepsMut,
+    env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    // Parse the payload to get the vote option
+    let payload: LockerInstantiatePayload = cosmwasm_std::from_json(&msg.payload)?;
+
+    // Parse the reply result to get the contract address
+    let res = msg
+        .result
+        .into_result()
+        .map_err(|e| ContractError::InvalidLsmShares {
+            reason: format!("Failed to instantiate locker: {}", e),
+        })?;
+
+    // Extract contract address from MsgInstantiateContractResponse
+    // The address is in the events under wasm-instantiate with _contract_address attribute
+    let contract_address = res
+        .events
+        .iter()
+        .find(|e| e.ty == "instantiate")
+        .and_then(|e| {
+            e.attributes
+                .iter()
+                .find(|attr| attr.key == "_contract_address")
+                .map(|attr| attr.value.clone())
+        })
+        .ok_or(ContractError::InvalidLsmShares {
+            reason: "Could not find contract address in instantiate reply".to_string(),
+        })?;
+
+    let locker_addr = deps.api.addr_validate(&contract_address)?;
+
+    // Load and update the active voting session creation
+    let mut active_creation = ACTIVE_VOTING_SESSION_CREATION.load(deps.storage)?;
+
+    // Verify this is for the same proposal
+    if active_creation.proposal_id != payload.proposal_id {
+        return Err(ContractError::InvalidLsmShares {
+            reason: format!(
+                "Payload proposal_id {} does not match active creation proposal_id {}",
+                payload.proposal_id, active_creation.proposal_id
+            ),
+        });
+    }
+
+    // Add this locker address, lockers of the other validators are kept apart
+    let config = CONFIG.load(deps.storage)?;
+    if payload.validator == config.validator {
+        active_creation
+            .locker_addresses
+            .push((payload.vote_option, locker_addr.clone()));
+    } else {
+        active_creation.validator_lockers.push((
+            payload.validator.clone(),
+            payload.vote_option,
+            locker_addr.clone(),
+        ));
+    }
+    active_creation.created_count += 1;
+
+    let mut response = Response::new()
+        .add_attribute("action", "instantiate_locker_reply")
+        .add_attribute("proposal_id", payload.proposal_id.to_string())
+        .add_attribute("vote_option", payload.vote_option.to_string())
+        .add_attribute("validator", payload.validator)
+        .add_attribute("locker_address", locker_addr.to_string())
+        .add_attribute("created_count", active_creation.created_count.to_string())
+        .add_attribute(
+            "expected_count",
+            active_creation.expected_lockers.to_string(),
+        );
+
+    // Check if all lockers have been created
+    if active_creation.created_count == active_creation.expected_lockers {
+        // All lockers are created! Create the voting session
+        let voting_session = lsm_types::VotingSession {
+            proposal_id: active_creation.proposal_id,
+            locker_addresses: active_creation.locker_addresses.clone(),
+            validator_lockers: active_creation.validator_lockers.clone(),
+            is_active: true,
+            rented_amount: Uint128::zero(),
+            auction: active_creation.auction.as_ref().map(|params| {
+                let commit_end = env.block.time.plus_seconds(params.commit_duration);
+                Auction {
+                    commit_end,
+                    reveal_end: commit_end.plus_seconds(params.reveal_duration),
+                    settled: false,
+                }
+            }),
+            voting_end_time: active_creation.voting_end_time,
+            keeper_reserve: Uint128::zero(),
+            weighted_options: active_creation.weighted_options.clone(),
+            deployed: vec![],
+        };
+
+        VOTING_SESSIONS.save(deps.storage, active_creation.proposal_id, &voting_session)?;
+
+        // Clean up the active creation state
+        ACTIVE_VOTING_SESSION_CREATION.remove(deps.storage);
+
+        response = response
+            .add_attribute("voting_session_created", "true")
+            .add_attribute(
+                "num_lockers",
+                voting_session.all_lockers().count().to_string(),
+            );
+    } else {
+        // Still waiting for more lockers, save the updated state
+        ACTIVE_VOTING_SESSION_CREATION.save(deps.storage, &active_creation)?;
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, ContractResult, SystemResult, Timestamp};
+    use lsm_types::RentalPricing;
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies();
+        let owner_addr = deps.api.addr_make("owner");
+        let validator_addr = deps.api.addr_make("validator");
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[validator_addr.as_str()]), &[]);
+        let msg = InstantiateMsg {
+            staking_denom: "uatom".to_string(),
+            owner: owner_addr.to_string(),
+            validator: validator_addr.to_string(),
+            validators: None,
+            fallback_validator: None,
+            max_cap: None,
+            locker_code_id: 1,
+            rental_pricing: None,
+            keeper_tip: None,
+            reward_denoms: None,
+            fee_collector: None,
+            rental_fee_rate: None,
+            reward_fee_rate: None,
+            max_rent_fraction: None,
+        };
+
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // Check config
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.owner, owner_addr);
+        assert_eq!(config.staking_denom, "uatom");
+        assert_eq!(config.validator, validator_addr.to_string());
+        assert_eq!(config.max_cap, None);
+        assert_eq!(config.locker_code_id, 1);
+
+        // Check state
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::zero());
+        assert_eq!(state.reward_index("uatom"), Decimal256::zero());
+
+        // Check is_paused
+        let is_paused = IS_PAUSED.load(&deps.storage).unwrap();
+        assert!(!is_paused);
+    }
+
+    #[test]
+    fn test_parse_lsm_denom_valid() {
+        let valid_denom = "cosmosvaloper1abc123/456";
+        let result = parse_lsm_denom(valid_denom).unwrap();
+        assert_eq!(result.validator, "cosmosvaloper1abc123");
+        assert_eq!(result.record_id, "456");
+    }
+
+    #[test]
+    fn test_parse_lsm_denom_invalid_format() {
+        let invalid_denom = "cosmosvaloper1abc123";
+        let result = parse_lsm_denom(invalid_denom);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_lsm_denom_invalid_validator() {
+        let invalid_denom = "invalidprefix1abc123/456";
+        let result = parse_lsm_denom(invalid_denom);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_lsm_denom_invalid_record_id() {
+        let invalid_denom = "cosmosvaloper1abc123/notanumber";
+        let result = parse_lsm_denom(invalid_denom);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deposit_rewards_and_claim() {
+        let mut deps = mock_dependencies();
+
+        // Initialize
+        let owner_addr = deps.api.addr_make("owner");
+        let validator_addr = deps.api.addr_make("validator");
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[validator_addr.as_str()]), &[]);
+        let msg = InstantiateMsg {
+            staking_denom: "uatom".to_string(),
+            owner: owner_addr.to_string(),
+            validator: validator_addr.to_string(),
+            validators: None,
+            fallback_validator: None,
+            max_cap: None,
+            locker_code_id: 1,
+            rental_pricing: None,
+            keeper_tip: None,
+            reward_denoms: None,
+            fee_collector: None,
+            rental_fee_rate: None,
+            reward_fee_rate: None,
+            max_rent_fraction: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Simulate a user having staked tokens
+        let staker_addr = deps.api.addr_make("staker");
+        let mut staker = Staker::new();
+        staker.staked_amount = Uint128::new(1000);
+        STAKERS
+            .save(&mut deps.storage, &staker_addr, &staker)
+            .unwrap();
+
+        let mut state = STATE.load(&deps.storage).unwrap();
+        state.total_staked = Uint128::new(1000);
+        state.total_tokens = Uint128::new(1000);
+        STATE.save(&mut deps.storage, &state).unwrap();
+
+        // Deposit rewards
+        let info = message_info(&deps.api.addr_make("depositor"), &coins(100, "uatom"));
+        let msg = ExecuteMsg::DepositRewards {};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Check state updated
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(
+            state.reward_index("uatom"),
+            Decimal256::from_ratio(100u128, 1000u128)
+        );
+
+        // Claim rewards
+        let info = message_info(&staker_addr, &[]);
+        let msg = ExecuteMsg::ClaimRewards {};
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Check that withdraw message was created
+        assert_eq!(res.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_multi_denom_rewards() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let validator_addr = deps.api.addr_make("validator");
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[validator_addr.as_str()]), &[]);
+
+        let msg = InstantiateMsg {
+            staking_denom: "uatom".to_string(),
+            owner: deps.api.addr_make("owner").to_string(),
+            validator: validator_addr.to_string(),
+            validators: None,
+            fallback_validator: None,
+            max_cap: None,
+            locker_code_id: 1,
+            rental_pricing: None,
+            keeper_tip: None,
+            reward_denoms: Some(vec!["uosmo".to_string()]),
+            fee_collector: None,
+            rental_fee_rate: None,
+            reward_fee_rate: None,
+            max_rent_fraction: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let staker_addr = deps.api.addr_make("staker");
+        let mut staker = Staker::new();
+        staker.staked_amount = Uint128::new(1000);
+        STAKERS
+            .save(&mut deps.storage, &staker_addr, &staker)
+            .unwrap();
+        let mut state = STATE.load(&deps.storage).unwrap();
+        state.total_staked = Uint128::new(1000);
+        state.total_tokens = Uint128::new(1000);
+        STATE.save(&mut deps.storage, &state).unwrap();
+
+        // Only whitelisted denoms are accepted as rewards
+        let info = message_info(&deps.api.addr_make("locker"), &coins(10, "ujunk"));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidRewardDenom {
+                denom: "ujunk".to_string()
+            }
+        );
+
+        // A locker forwards rewards in several denoms
+        let funds = vec![Coin::new(100u128, "uatom"), Coin::new(50u128, "uosmo")];
+        let info = message_info(&deps.api.addr_make("locker"), &funds);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+
+        let staker_info: StakerInfoResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::StakerInfo {
+                    address: staker_addr.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(staker_info.pending_rewards, Uint128::new(100));
+        assert_eq!(
+            staker_info.pending_denom_rewards,
+            vec![Coin::new(50u128, "uosmo")]
+        );
+
+        // The validator pays rewards in the other denom while claiming
+        let info = message_info(&staker_addr, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        deps.querier
+            .bank
+            .update_balance(&env.contract.address, coins(20, "uosmo"));
+
+        #[allow(deprecated)]
+        let claim_reply = Reply {
+            id: REPLY_CLAIM_REWARDS,
+            payload: Binary::default(),
+            gas_used: 0,
+            result: cosmwasm_std::SubMsgResult::Ok(cosmwasm_std::SubMsgResponse {
+                events: vec![],
+                data: None,
+                msg_responses: vec![],
+            }),
+        };
+        let res = reply(deps.as_mut(), env, claim_reply).unwrap();
+        assert_eq!(
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>(),
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: staker_addr.to_string(),
+                amount: vec![Coin::new(100u128, "uatom"), Coin::new(70u128, "uosmo")],
+            })]
+        );
+        let staker = STAKERS.load(&deps.storage, &staker_addr).unwrap();
+        assert_eq!(staker.reward_indices["uosmo"], Decimal256::permille(70));
+    }
+
+    #[test]
+    fn test_migrate_from_baseline() {
+        let mut deps = mock_gov_dependencies();
+        let env = mock_env();
+        let owner = deps.api.addr_make("owner");
+        let validator = deps.api.addr_make("validator");
+        let staker_addr = deps.api.addr_make("staker");
+        let locker_addr = deps.api.addr_make("locker");
+        deps.querier
+            .proposals
+            .insert(5, (2, env.block.time.plus_days(3)));
+
+        // Storage as written by the first version, with a single reward index
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        let raw = [
+            (
+                CONFIG.as_slice().to_vec(),
+                format!(
+                    r#"{{"owner":"{owner}","staking_denom":"uatom","validator":"{validator}","max_cap":null,"locker_code_id":1}}"#
+                ),
+            ),
+            (
+                STATE.as_slice().to_vec(),
+                r#"{"total_staked":"1000","global_reward_index":"0.1"}"#.to_string(),
+            ),
+            (
+                STAKERS.key(&staker_addr).to_vec(),
+                r#"{"staked_amount":"1000","reward_index":"0.04"}"#.to_string(),
+            ),
+            (
+                VOTING_SESSIONS.key(5).to_vec(),
+                format!(
+                    r#"{{"proposal_id":5,"locker_addresses":[[1,"{locker_addr}"]],"is_active":true}}"#
+                ),
+            ),
+            (IS_PAUSED.as_slice().to_vec(), "true".to_string()),
+        ];
+        for (key, value) in raw {
+            deps.storage.set(&key, value.as_bytes());
+        }
+        let locker = locker_addr.to_string();
+        deps.querier.base.update_wasm(move |request| match request {
+            cosmwasm_std::WasmQuery::Smart { contract_addr, .. } if *contract_addr == locker => {
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&TotalVotingPowerResponse {
+                        total_staked: Uint128::new(400),
+                    })
+                    .unwrap(),
+                ))
+            }
+            _ => SystemResult::Err(cosmwasm_std::SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(attribute_value(&res, "from_version"), "0.1.0");
+        assert_eq!(attribute_value(&res, "migrated_stakers"), "1");
+        assert_eq!(attribute_value(&res, "migrated_sessions"), "1");
+        assert_eq!(
+            cw2::get_contract_version(&deps.storage).unwrap().version,
+            CONTRACT_VERSION
+        );
+
+        // Settings added since the first version take their defaults
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.owner, owner);
+        assert_eq!(config.rental_pricing, RentalPricing::default());
+        assert_eq!(config.keeper_tip, Uint128::zero());
+        assert!(config.reward_denoms.is_empty());
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(1000));
+        assert_eq!(state.total_tokens, Uint128::new(1000));
+        assert_eq!(state.reward_index("uatom"), Decimal256::percent(10));
+        let staker = STAKERS.load(&deps.storage, &staker_addr).unwrap();
+        assert_eq!(
+            staker.calculate_pending_rewards(&state.reward_indices),
+            coins(60, "uatom")
+        );
+
+        // The active session gets the voting end time of its proposal and the stake
+        // held by its locker
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.locker_addresses, vec![(1, locker_addr.clone())]);
+        assert_eq!(session.voting_end_time, env.block.time.plus_days(3));
+        assert_eq!(session.deployed, vec![(locker_addr, Uint128::new(400))]);
+        assert_eq!(session.rented_amount, Uint128::new(400));
+        assert_eq!(session.keeper_reserve, Uint128::zero());
+        assert!(IS_PAUSED.load(&deps.storage).unwrap());
+
+        // Migrating again leaves the new layout untouched
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(attribute_value(&res, "migrated_stakers"), "0");
+        assert_eq!(attribute_value(&res, "migrated_sessions"), "0");
+
+        // Only this contract can be migrated, and never to an older version
+        set_contract_version(&mut deps.storage, "crates.io:other", "0.1.0").unwrap();
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap_err();
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        migrate(deps.as_mut(), env, MigrateMsg {}).unwrap_err();
+    }
+
+    #[test]
+    fn test_auto_compound() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let validator_addr = deps.api.addr_make("validator");
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[validator_addr.as_str()]), &[]);
+
+        let msg = InstantiateMsg {
+            staking_denom: "uatom".to_string(),
+            owner: deps.api.addr_make("owner").to_string(),
+            validator: validator_addr.to_string(),
+            validators: None,
+            fallback_validator: None,
+            max_cap: None,
+            locker_code_id: 1,
+            rental_pricing: None,
+            keeper_tip: None,
+            reward_denoms: None,
+            fee_collector: None,
+            rental_fee_rate: None,
+            reward_fee_rate: None,
+            max_rent_fraction: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let compounder_addr = deps.api.addr_make("compounder");
+        let other_addr = deps.api.addr_make("other");
+
+        // Only stakers can opt in
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&compounder_addr, &[]),
+            ExecuteMsg::SetAutoCompound { enabled: true },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InsufficientStakedAmount {});
+
+        for addr in [&compounder_addr, &other_addr] {
+            let mut staker = Staker::new();
+            staker.staked_amount = Uint128::new(1000);
+            STAKERS.save(&mut deps.storage, addr, &staker).unwrap();
+        }
+        let mut state = STATE.load(&deps.storage).unwrap();
+        state.total_staked = Uint128::new(2000);
+        state.total_tokens = Uint128::new(2000);
+        STATE.save(&mut deps.storage, &state).unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&compounder_addr, &[]),
+            ExecuteMsg::SetAutoCompound { enabled: true },
+        )
+        .unwrap();
+
+        let info = message_info(&deps.api.addr_make("locker"), &coins(200, "uatom"));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+
+        // Anyone can trigger compounding, the rewards are delegated back to the validator
+        let info = message_info(&deps.api.addr_make("keeper"), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Compound {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: deps.api.addr_make("validator").to_string(),
+                amount: Coin::new(100u128, "uatom"),
+            }))]
+        );
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(2100));
+
+        let staker_info = |deps: Deps, addr: &Addr| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::StakerInfo {
+                    address: addr.to_string(),
+                },
+            )
+            .unwrap();
+            cosmwasm_std::from_json::<StakerInfoResponse>(res).unwrap()
+        };
+        let compounder = staker_info(deps.as_ref(), &compounder_addr);
+        assert!(compounder.auto_compound);
+        assert_eq!(compounder.staked_amount, Uint128::new(1100));
+        assert_eq!(compounder.pending_rewards, Uint128::zero());
+        let other = staker_info(deps.as_ref(), &other_addr);
+        assert!(!other.auto_compound);
+        assert_eq!(other.staked_amount, Uint128::new(1000));
+        assert_eq!(other.pending_rewards, Uint128::new(100));
+
+        let info = message_info(&deps.api.addr_make("locker"), &coins(210, "uatom"));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+
+        // Rewards earned before opting in are paid out rather than compounded
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&other_addr, &[]),
+            ExecuteMsg::SetAutoCompound { enabled: true },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: other_addr.to_string(),
+                amount: coins(200, "uatom"),
+            }))]
+        );
+        let other = staker_info(deps.as_ref(), &other_addr);
+        assert_eq!(other.staked_amount, Uint128::new(1000));
+        assert_eq!(other.pending_rewards, Uint128::zero());
+
+        // Rewards earned before opting out are still compounded
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&compounder_addr, &[]),
+            ExecuteMsg::SetAutoCompound { enabled: false },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: deps.api.addr_make("validator").to_string(),
+                amount: Coin::new(110u128, "uatom"),
+            }))]
+        );
+        let compounder = staker_info(deps.as_ref(), &compounder_addr);
+        assert!(!compounder.auto_compound);
+        assert_eq!(compounder.staked_amount, Uint128::new(1210));
+
+        let info = message_info(&deps.api.addr_make("locker"), &coins(221, "uatom"));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+
+        // Claiming compounds the rewards of an auto-compounder too
+        let claim = |deps: &mut cosmwasm_std::OwnedDeps<_, _, _>, addr: &Addr| {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(addr, &[]),
+                ExecuteMsg::ClaimRewards {},
+            )
+            .unwrap();
+            #[allow(deprecated)]
+            let claim_reply = Reply {
+                id: REPLY_CLAIM_REWARDS,
+                payload: Binary::default(),
+                gas_used: 0,
+                result: cosmwasm_std::SubMsgResult::Ok(cosmwasm_std::SubMsgResponse {
+                    events: vec![],
+                    data: None,
+                    msg_responses: vec![],
+                }),
+            };
+            reply(deps.as_mut(), env.clone(), claim_reply).unwrap()
+        };
+        let res = claim(&mut deps, &other_addr);
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: deps.api.addr_make("validator").to_string(),
+                amount: Coin::new(100u128, "uatom"),
+            }))]
+        );
+        let other = staker_info(deps.as_ref(), &other_addr);
+        assert_eq!(other.staked_amount, Uint128::new(1100));
+        assert_eq!(other.pending_rewards, Uint128::zero());
+
+        // Not while a voting session locks the delegation
+        IS_PAUSED.save(&mut deps.storage, &true).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&compounder_addr, &[]),
+            ExecuteMsg::SetAutoCompound { enabled: true },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ContractPaused {});
+
+        // Claimed rewards are paid out instead
+        let info = message_info(&deps.api.addr_make("locker"), &coins(231, "uatom"));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+        let res = claim(&mut deps, &other_addr);
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: other_addr.to_string(),
+                amount: coins(110, "uatom"),
+            }))]
+        );
+        let other = staker_info(deps.as_ref(), &other_addr);
+        assert_eq!(other.staked_amount, Uint128::new(1100));
+    }
+
+    #[test]
+    fn test_protocol_fees() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let validator_addr = deps.api.addr_make("validator");
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[validator_addr.as_str()]), &[]);
+
+        let owner_addr = deps.api.addr_make("owner");
+        let collector_addr = deps.api.addr_make("collector");
+        let msg = InstantiateMsg {
+            staking_denom: "uatom".to_string(),
+            owner: owner_addr.to_string(),
+            validator: validator_addr.to_string(),
+            validators: None,
+            fallback_validator: None,
+            max_cap: None,
+            locker_code_id: 1,
+            rental_pricing: None,
+            keeper_tip: None,
+            reward_denoms: None,
+            fee_collector: Some(collector_addr.to_string()),
+            rental_fee_rate: None,
+            reward_fee_rate: Some(Decimal::percent(10)),
+            max_rent_fraction: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let staker_addr = deps.api.addr_make("staker");
+        let mut staker = Staker::new();
+        staker.staked_amount = Uint128::new(1000);
+        STAKERS
+            .save(&mut deps.storage, &staker_addr, &staker)
+            .unwrap();
+        let mut state = STATE.load(&deps.storage).unwrap();
+        state.total_staked = Uint128::new(1000);
+        state.total_tokens = Uint128::new(1000);
+        STATE.save(&mut deps.storage, &state).unwrap();
+
+        // The fee rates are capped
+        let info = message_info(&owner_addr, &[]);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                reward_fee_rate: Some(Decimal::percent(30)),
+                ..Default::default()
+            })),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::FeeRateTooHigh {
+                rate: Decimal::percent(30),
+                max: MAX_REWARD_FEE_RATE,
+            }
+        );
+
+        // 10% of the rewards accrue as fees
+        let info = message_info(&deps.api.addr_make("locker"), &coins(200, "uatom"));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::DepositRewards {},
+        )
+        .unwrap();
+
+        let fees: FeesResponse =
+            cosmwasm_std::from_json(query(deps.as_ref(), env.clone(), QueryMsg::Fees {}).unwrap())
+                .unwrap();
+        assert_eq!(fees.fee_collector, collector_addr);
+        assert_eq!(fees.accrued_fees, coins(20, "uatom"));
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.reward_index("uatom"), Decimal256::permille(180));
+
+        let info = message_info(&staker_addr, &[]);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::WithdrawFees {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let info = message_info(&collector_addr, &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::WithdrawFees {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: collector_addr.to_string(),
+                amount: coins(20, "uatom"),
+            })]
+        );
+
+        let info = message_info(&collector_addr, &[]);
+        let err = execute(deps.as_mut(), env, info, ExecuteMsg::WithdrawFees {}).unwrap_err();
+        assert_eq!(err, ContractError::NoFees {});
+    }
+
+    type GovMockDeps = cosmwasm_std::OwnedDeps<
+        cosmwasm_std::MemoryStorage,
+        cosmwasm_std::testing::MockApi,
+        GovMockQuerier,
+    >;
+
+    /// MockQuerier that also answers the gov proposal queries of the contract
+    struct GovMockQuerier {
+        base: cosmwasm_std::testing::MockQuerier,
+        /// Map of proposal_id to (status, voting_end_time)
+        proposals: std::collections::HashMap<u64, (i32, Timestamp)>,
+        /// Validators that are jailed, all others are healthy
+        jailed: std::collections::HashSet<String>,
+        /// Validators whose staking module query fails, e.g. when it is not whitelisted
+        failing: std::collections::HashSet<String>,
+    }
+
+    impl std::ops::Deref for GovMockQuerier {
+        type Target = cosmwasm_std::testing::MockQuerier;
+
+        fn deref(&self) -> &Self::Target {
+            &self.base
+        }
+    }
+
+    impl std::ops::DerefMut for GovMockQuerier {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.base
+        }
+    }
+
+    impl cosmwasm_std::Querier for GovMockQuerier {
+        #[allow(deprecated)]
+        fn raw_query(&self, bin_request: &[u8]) -> cosmwasm_std::QuerierResult {
+            use cosmwasm_std::QueryRequest;
+            use gov_query::proto;
+            use prost::Message;
+
+            let request: QueryRequest = cosmwasm_std::from_json(bin_request).unwrap();
+            match request {
+                QueryRequest::Stargate { path, data }
+                    if path == "/cosmos.gov.v1.Query/Proposal" =>
+                {
+                    let proposal_id = proto::QueryProposalRequest::decode(data.as_slice())
+                        .unwrap()
+                        .proposal_id;
+                    let proposal =
+                        self.proposals
+                            .get(&proposal_id)
+                            .map(|(status, end)| proto::Proposal {
+                                id: proposal_id,
+                                status: *status,
+                                voting_start_time: None,
+                                voting_end_time: Some(proto::Timestamp {
+                                    seconds: end.seconds() as i64,
+                                    nanos: end.subsec_nanos() as i32,
+                                }),
+                            });
+                    // The contract reads the response as a Binary
+                    let response =
+                        Binary::from(proto::QueryProposalResponse { proposal }.encode_to_vec());
+                    SystemResult::Ok(ContractResult::Ok(to_json_binary(&response).unwrap()))
+                }
+                QueryRequest::Stargate { path, data } if path == VALIDATOR_QUERY_PATH => {
+                    let validator_addr =
+                        staking_proto::QueryValidatorRequest::decode(data.as_slice())
+                            .unwrap()
+                            .validator_addr;
+                    if self.failing.contains(&validator_addr) {
+                        return SystemResult::Ok(ContractResult::Err(format!(
+                            "query of validator {} failed",
+                            validator_addr
+                        )));
+                    }
+                    let validator = staking_proto::Validator {
+                        jailed: self.jailed.contains(&validator_addr),
+                        operator_address: validator_addr,
+                    };
+                    let response = Binary::from(
+                        staking_proto::QueryValidatorResponse {
+                            validator: Some(validator),
+                        }
+                        .encode_to_vec(),
+                    );
+                    SystemResult::Ok(ContractResult::Ok(to_json_binary(&response).unwrap()))
+                }
+                _ => self.base.raw_query(bin_request),
+            }
+        }
+    }
+
+    fn mock_gov_dependencies() -> GovMockDeps {
+        let deps = mock_dependencies();
+        cosmwasm_std::OwnedDeps {
+            storage: deps.storage,
+            api: deps.api,
+            querier: GovMockQuerier {
+                base: deps.querier,
+                proposals: std::collections::HashMap::new(),
+                jailed: std::collections::HashSet::new(),
+                failing: std::collections::HashSet::new(),
+            },
+            custom_query_type: std::marker::PhantomData,
+        }
+    }
+
+    /// Validators known to the staking module of the mock querier
+    fn mock_validators(addresses: &[&str]) -> Vec<cosmwasm_std::Validator> {
+        addresses
+            .iter()
+            .map(|address| {
+                cosmwasm_std::Validator::create(
+                    address.to_string(),
+                    Decimal::percent(5),
+                    Decimal::percent(10),
+                    Decimal::percent(1),
+                )
+            })
+            .collect()
+    }
+
+    /// Instantiate the contract with a delegation of 10_000 uatom to the validator
+    /// A second validator, cosmosvaloper1second, is known to the staking module
+    fn setup_pool(deps: &mut GovMockDeps, rental_pricing: RentalPricing) -> Env {
+        let env = mock_env();
+        let validator_addr = deps.api.addr_make("validator");
+        let validators = mock_validators(&[validator_addr.as_str(), "cosmosvaloper1second"]);
+        deps.querier.staking.update("uatom", &validators, &[]);
+        let msg = InstantiateMsg {
+            staking_denom: "uatom".to_string(),
+            owner: deps.api.addr_make("owner").to_string(),
+            validator: validator_addr.to_string(),
+            validators: None,
+            fallback_validator: None,
+            max_cap: None,
+            locker_code_id: 1,
+            rental_pricing: Some(rental_pricing),
+            keeper_tip: None,
+            reward_denoms: None,
+            fee_collector: None,
+            rental_fee_rate: None,
+            reward_fee_rate: None,
+            max_rent_fraction: None,
+        };
+        let info = message_info(&deps.api.addr_make("creator"), &[]);
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let mut state = STATE.load(&deps.storage).unwrap();
+        state.total_staked = Uint128::new(10_000);
+        state.total_tokens = Uint128::new(10_000);
+        STATE.save(&mut deps.storage, &state).unwrap();
+
+        deps.querier.staking.update(
+            "uatom",
+            &validators,
+            &[cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator_addr.to_string(),
+                cosmwasm_std::coin(10_000, "uatom"),
+                cosmwasm_std::coin(10_000, "uatom"),
+                vec![],
+            )],
+        );
+        env
+    }
+
+    /// Put the proposal in voting period for another week
+    fn set_proposal_in_voting(deps: &mut GovMockDeps, env: &Env, proposal_id: u64) {
+        deps.querier
+            .proposals
+            .insert(proposal_id, (2, env.block.time.plus_days(7)));
+    }
+
+    /// Open a voting session through CreateVotingLockers and the locker instantiation replies
+    /// Returns the (vote_option, validator, locker) of every locker created
+    fn create_voting_session(
+        deps: &mut GovMockDeps,
+        env: &Env,
+        proposal_id: u64,
+        options: Option<Vec<i32>>,
+        weighted: bool,
+    ) -> Vec<(i32, String, Addr)> {
+        set_proposal_in_voting(deps, env, proposal_id);
+        let owner = deps.api.addr_make("owner");
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::CreateVotingLockers {
+                proposal_id,
+                auction: None,
+                options,
+                weighted,
+            },
+        )
+        .unwrap();
+        instantiate_lockers(deps, env, &res)
+    }
+
+    /// Answer the locker instantiations of a CreateVotingLockers response
+    /// Returns the (vote_option, validator, locker) of every locker created
+    fn instantiate_lockers(
+        deps: &mut GovMockDeps,
+        env: &Env,
+        res: &Response,
+    ) -> Vec<(i32, String, Addr)> {
+        res.messages
+            .iter()
+            .map(|submsg| {
+                let payload: LockerInstantiatePayload =
+                    cosmwasm_std::from_json(&submsg.payload).unwrap();
+                let locker = deps.api.addr_make(&format!(
+                    "locker_{}_{}_{}",
+                    payload.proposal_id, payload.vote_option, payload.validator
+                ));
+                #[allow(deprecated)]
+                let instantiate_reply = Reply {
+                    id: REPLY_INSTANTIATE_LOCKER,
+                    payload: submsg.payload.clone(),
+                    gas_used: 0,
+                    result: cosmwasm_std::SubMsgResult::Ok(cosmwasm_std::SubMsgResponse {
+                        events: vec![cosmwasm_std::Event::new("instantiate")
+                            .add_attribute("_contract_address", locker.as_str())],
+                        data: None,
+                        msg_responses: vec![],
+                    }),
+                };
+                reply(deps.as_mut(), env.clone(), instantiate_reply).unwrap();
+                (payload.vote_option, payload.validator, locker)
+            })
+            .collect()
+    }
+
+    /// Seed a voting session for the proposal with a single locker for option 1
+    /// Only for narrow checks, flows involving the lockers use create_voting_session
+    fn open_voting_session(deps: &mut GovMockDeps, env: &Env, proposal_id: u64) {
+        let locker_addr = deps.api.addr_make("locker");
+        VOTING_SESSIONS
+            .save(
+                &mut deps.storage,
+                proposal_id,
+                &lsm_types::VotingSession {
+                    proposal_id,
+                    locker_addresses: vec![(1, locker_addr)],
+                    validator_lockers: vec![],
+                    is_active: true,
+                    rented_amount: Uint128::zero(),
+                    auction: None,
+                    voting_end_time: env.block.time.plus_days(7),
+                    keeper_reserve: Uint128::zero(),
+                    weighted_options: None,
+                    deployed: vec![],
+                },
+            )
+            .unwrap();
+        set_proposal_in_voting(deps, env, proposal_id);
+    }
+
+    fn attribute_value(res: &Response, key: &str) -> String {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rent_voting_power_uses_proposal_pricing() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(
+            &mut deps,
+            RentalPricing {
+                default: lsm_types::PricingModel::Flat {
+                    price_per_vp: Decimal256::percent(10),
+                },
+                overrides: vec![(
+                    5,
+                    lsm_types::PricingModel::Flat {
+                        price_per_vp: Decimal256::percent(50),
+                    },
+                )],
+            },
+        );
+        open_voting_session(&mut deps, &env, 5);
+
+        // The quote uses the per-proposal override: 1 VP = 0.5 uatom
+        let quote: RentalQuoteResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::RentalQuote {
+                    proposal_id: 5,
+                    vote_option: 1,
+                    vp_amount: Uint128::new(1000),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(quote.cost, Uint128::new(500));
+        assert_eq!(quote.price_per_vp, Decimal256::percent(50));
+
+        // Paying the quoted cost rents the quoted voting power
+        let info = message_info(&deps.api.addr_make("renter"), &coins(500, "uatom"));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: None,
+                max_price: None,
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "vp_amount"), "1000");
+
+        // Quotes for unknown options are rejected
+        let err = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::RentalQuote {
+                proposal_id: 5,
+                vote_option: 2,
+                vp_amount: Uint128::new(1000),
+            },
+        );
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_rent_voting_power_slippage_bounds() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let renter = deps.api.addr_make("renter");
+
+        // 100 uatom buys 1000 VP at the default flat price of 0.1
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(100, "uatom")),
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: Some(Uint128::new(1001)),
+                max_price: None,
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RentalSlippageExceeded { .. }));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(100, "uatom")),
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: None,
+                max_price: Some(Decimal256::permille(99)),
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RentalSlippageExceeded { .. }));
+
+        // Bounds that the rental satisfies go through
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&renter, &coins(100, "uatom")),
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: Some(Uint128::new(1000)),
+                max_price: Some(Decimal256::permille(100)),
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "vp_amount"), "1000");
+    }
+
+    #[test]
+    fn test_rent_voting_power_partial_fill() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let renter = deps.api.addr_make("renter");
+
+        // 2000 uatom asks for 20_000 VP but only 10_000 is delegated
+        let rent = |allow_partial| ExecuteMsg::RentVotingPower {
+            proposal_id: 5,
+            vote_option: 1,
+            min_vp_amount: None,
+            max_price: None,
+            allow_partial,
+            validator: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(2000, "uatom")),
+            rent(false),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientStakedTokens { .. }
+        ));
+
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&renter, &coins(2000, "uatom")),
+            rent(true),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "filled"), "10000");
+        assert_eq!(attribute_value(&res, "refunded"), "1000");
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: renter.to_string(),
+                amount: coins(1000, "uatom"),
+            })
+        );
+
+        // Only the filled part of the payment is distributed as rental income
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.rental_index, Decimal256::percent(10));
+        assert_eq!(state.reward_index("uatom"), Decimal256::zero());
+    }
+
+    #[test]
+    fn test_rent_cap() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let owner = deps.api.addr_make("owner");
+        let renter = deps.api.addr_make("renter");
+        let update_rent_fraction = |fraction| {
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                max_rent_fraction: Some(fraction),
+                ..Default::default()
+            }))
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            update_rent_fraction(Decimal::zero()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidRentFraction {
+                fraction: Decimal::zero()
+            }
+        );
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            update_rent_fraction(Decimal::percent(30)),
+        )
+        .unwrap();
+
+        let rent = |allow_partial| ExecuteMsg::RentVotingPower {
+            proposal_id: 5,
+            vote_option: 1,
+            min_vp_amount: None,
+            max_price: None,
+            allow_partial,
+            validator: None,
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(100, "uatom")),
+            rent(false),
+        )
+        .unwrap();
+
+        // Only 3000 of the 10_000 pool tokens may be rented on the proposal
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(300, "uatom")),
+            rent(false),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RentCapReached {
+                proposal_id: 5,
+                cap: Uint128::new(3000),
+                rented: Uint128::new(1000),
+                attempting: Uint128::new(3000),
+            }
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(300, "uatom")),
+            rent(true),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "filled"), "2000");
+        assert_eq!(attribute_value(&res, "refunded"), "100");
+
+        // The rented stake is recorded as deployed in the locker
+        let res: VotingSessionResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::VotingSession { proposal_id: 5 },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.deployed_amount, Uint128::new(3000));
+        assert_eq!(res.max_rent_amount, Some(Uint128::new(3000)));
+        assert_eq!(
+            res.voting_session.deployed,
+            vec![(deps.api.addr_make("locker"), Uint128::new(3000))]
+        );
+    }
+
+    #[test]
+    fn test_auction_rent_cap() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.max_rent_fraction = Some(Decimal::percent(30));
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        let locker = deps.api.addr_make("locker");
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.auction = Some(Auction {
+            commit_end: env.block.time,
+            reveal_end: env.block.time,
+            settled: false,
+        });
+        session.rented_amount = Uint128::new(1000);
+        session.deploy(&locker, Uint128::new(1000));
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let alice_bid = RevealedBid {
+            vote_option: 1,
+            vp_amount: Uint128::new(6000),
+            price_per_vp: Decimal256::percent(50),
+        };
+        BIDS.save(
+            &mut deps.storage,
+            (5, &alice),
+            &Bid {
+                bidder: alice.clone(),
+                commitment: bid_commitment(&alice, 5, &alice_bid, "salt"),
+                deposit: Uint128::new(3000),
+                committed_at: env.block.height,
+                revealed: Some(alice_bid),
+            },
+        )
+        .unwrap();
+
+        // Only the 2000 left under the cap of 3000 pool tokens are allocated
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::SettleAuction { proposal_id: 5 },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "allocated_vp"), "2000");
+        assert_eq!(attribute_value(&res, "income"), "1000");
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(2000, "uatom"),
+            })
+        );
+
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.rented_amount, Uint128::new(3000));
+        assert_eq!(session.deployed, vec![(locker, Uint128::new(3000))]);
+    }
+
+    #[test]
+    fn test_settle_auction_after_destroy() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.keeper_tip = Uint128::new(50);
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.auction = Some(Auction {
+            commit_end: env.block.time,
+            reveal_end: env.block.time,
+            settled: false,
+        });
+        session.is_active = false;
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let alice_bid = RevealedBid {
+            vote_option: 1,
+            vp_amount: Uint128::new(1000),
+            price_per_vp: Decimal256::percent(50),
+        };
+        for (bidder, revealed, deposit) in [(&alice, Some(alice_bid), 500), (&bob, None, 200)] {
+            BIDS.save(
+                &mut deps.storage,
+                (5, bidder),
+                &Bid {
+                    bidder: bidder.clone(),
+                    commitment: HexBinary::from([7u8; 32]),
+                    deposit: Uint128::new(deposit),
+                    committed_at: env.block.height,
+                    revealed,
+                },
+            )
+            .unwrap();
+        }
+
+        // Nothing is allocated, the revealed bid is refunded and the forfeited deposit
+        // goes to the stakers in full
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::SettleAuction { proposal_id: 5 },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "allocated_vp"), "0");
+        assert_eq!(attribute_value(&res, "income"), "200");
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(500, "uatom"),
+            })
+        );
+
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.keeper_reserve, Uint128::zero());
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(
+            state.rental_index,
+            Decimal256::from_ratio(200u128, 10_000u128)
+        );
+    }
+
+    #[test]
+    fn test_settle_auction_after_voting_end() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.auction = Some(Auction {
+            commit_end: env.block.time,
+            reveal_end: env.block.time,
+            settled: false,
+        });
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        BIDS.save(
+            &mut deps.storage,
+            (5, &alice),
+            &Bid {
+                bidder: alice.clone(),
+                commitment: HexBinary::from([7u8; 32]),
+                deposit: Uint128::new(500),
+                committed_at: env.block.height,
+                revealed: Some(RevealedBid {
+                    vote_option: 1,
+                    vp_amount: Uint128::new(1000),
+                    price_per_vp: Decimal256::percent(50),
+                }),
+            },
+        )
+        .unwrap();
+
+        // The lockers can no longer vote, the bid is refunded and nothing is tokenized
+        env.block.time = session.voting_end_time;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::SettleAuction { proposal_id: 5 },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "allocated_vp"), "0");
+        assert_eq!(attribute_value(&res, "income"), "0");
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(500, "uatom"),
+            })
+        );
+
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.rented_amount, Uint128::zero());
+        assert!(session.deployed.is_empty());
+        assert!(!RENTAL_TOTALS.has(&deps.storage, (5, 1)));
+    }
+
+    #[test]
+    fn test_return_lsm_shares_releases_deployed() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        let locker = deps.api.addr_make("locker");
+        let locker2 = deps.api.addr_make("locker2");
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session
+            .validator_lockers
+            .push((validator2.clone(), 1, locker2.clone()));
+        session.deploy(&locker, Uint128::new(1000));
+        session.deploy(&locker2, Uint128::new(500));
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        let shares = coins(500, format!("{}/7", validator2));
+        let return_shares = ExecuteMsg::ReturnLsmShares {
+            proposal_id: 5,
+            vote_option: 1,
+        };
+
+        // Only the locker of the validator can return its shares
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&locker, &shares),
+            return_shares.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidLocker { .. }));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&locker2, &shares),
+            return_shares,
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "released"), "500");
+
+        // The stake of the other locker stays deployed
+        let res: VotingSessionResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::VotingSession { proposal_id: 5 },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.deployed_amount, Uint128::new(1000));
+        assert_eq!(
+            res.voting_session.deployed,
+            vec![(locker, Uint128::new(1000))]
+        );
+    }
+
+    #[test]
+    fn test_multiple_validators() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        let owner = deps.api.addr_make("owner");
+        let validator = deps.api.addr_make("validator").to_string();
+        let validator2 = "cosmosvaloper1second".to_string();
+        let update_validators = |validators: Vec<String>| {
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                validators: Some(validators),
+                ..Default::default()
+            }))
+        };
+
+        // The primary validator can't be listed again
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            update_validators(vec![validator.clone()]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DuplicateValidator {
+                validator: validator.clone(),
+            }
+        );
+
+        // Validators unknown to the staking module are rejected
+        let unknown = "cosmosvaloper1other".to_string();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            update_validators(vec![validator2.clone(), unknown.clone()]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotFound {
+                validator: unknown.clone(),
+            }
+        );
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            update_validators(vec![validator2.clone()]),
+        )
+        .unwrap();
+
+        // Shares of a validator that is not accepted are rejected
+        let staker = deps.api.addr_make("staker");
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&staker, &coins(100, "cosmosvaloper1other/1")),
+            ExecuteMsg::DepositLsmShares {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidValidator { .. }));
+
+        // Rewards are withdrawn from every delegation before a deposit
+        let delegation = |validator: &str, amount: u128| {
+            cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator.to_string(),
+                cosmwasm_std::coin(amount, "uatom"),
+                cosmwasm_std::coin(amount, "uatom"),
+                vec![],
+            )
+        };
+        deps.querier.staking.update(
+            "uatom",
+            &[],
+            &[
+                delegation(&validator, 10_000),
+                delegation(&validator2, 4_000),
+            ],
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&staker, &coins(4_000, format!("{}/1", validator2))),
+            ExecuteMsg::DepositLsmShares {},
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward {
+                validator: validator.clone(),
+            })
+        );
+        assert_eq!(res.messages[1].id, REPLY_CLAIM_REWARDS_DEPOSIT);
+
+        let delegations: DelegationsResponse = cosmwasm_std::from_json(
+            query(deps.as_ref(), env.clone(), QueryMsg::Delegations {}).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            delegations.delegations,
+            vec![
+                ValidatorDelegation {
+                    validator: validator.clone(),
+                    amount: Uint128::new(10_000),
+                },
+                ValidatorDelegation {
+                    validator: validator2.clone(),
+                    amount: Uint128::new(4_000),
+                },
+            ]
+        );
+
+        // Each validator the contract delegates to gets its own locker
+        let lockers = create_voting_session(&mut deps, &env, 5, Some(vec![1]), false);
+        assert_eq!(lockers.len(), 2);
+        let (_, _, locker) = lockers[0].clone();
+        let (_, _, locker2) = lockers[1].clone();
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.locker_addresses, vec![(1, locker.clone())]);
+        assert_eq!(
+            session.validator_lockers,
+            vec![(validator2.clone(), 1, locker2.clone())]
+        );
+
+        let renter = deps.api.addr_make("renter");
+        let rent = |validator: Option<String>| ExecuteMsg::RentVotingPower {
+            proposal_id: 5,
+            vote_option: 1,
+            min_vp_amount: None,
+            max_price: None,
+            allow_partial: false,
+            validator,
+        };
+
+        // Without a choice the validator with the most delegated tokens is used
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(100, "uatom")),
+            rent(None),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "validator"), validator);
+        assert_eq!(attribute_value(&res, "locker"), locker.to_string());
+
+        // The renter can pick the validator, limited to its delegation
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(500, "uatom")),
+            rent(Some(validator2.clone())),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientStakedTokens {
+                available: Uint128::new(4_000),
+                required: Uint128::new(5_000),
+            }
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(300, "uatom")),
+            rent(Some(validator2.clone())),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "validator"), validator2);
+        assert_eq!(attribute_value(&res, "locker"), locker2.to_string());
+        assert_eq!(
+            ACTIVE_RENTAL.load(&deps.storage).unwrap().validator,
+            validator2
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &coins(100, "uatom")),
+            rent(Some(unknown.clone())),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotInSession {
+                validator: unknown,
+                proposal_id: 5,
+            }
+        );
+
+        // Once the voting period is over both lockers are destroyed
+        env.block.time = env.block.time.plus_days(7);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&renter, &[]),
+            ExecuteMsg::DestroyVotingLockers { proposal_id: 5 },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "num_lockers"), "2");
+        assert_eq!(attribute_value(&res, "unpaused"), "true");
+
+        // Each locker can only return the shares of its own validator
+        let return_shares = ExecuteMsg::ReturnLsmShares {
+            proposal_id: 5,
+            vote_option: 1,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&locker, &coins(3_000, format!("{}/7", validator2))),
+            return_shares.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidLocker { .. }));
+        execute(
+            deps.as_mut(),
+            env,
+            message_info(&locker2, &coins(3_000, format!("{}/7", validator2))),
+            return_shares,
+        )
+        .unwrap();
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.deployed, vec![(locker, Uint128::new(1_000))]);
+    }
+
+    #[test]
+    fn test_rebalance() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let owner = deps.api.addr_make("owner");
+        let validator = deps.api.addr_make("validator").to_string();
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        // 4_000 of the 10_000 delegated were redelegated recently
+        deps.querier.staking.update(
+            "uatom",
+            &[],
+            &[cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator.clone(),
+                cosmwasm_std::coin(10_000, "uatom"),
+                cosmwasm_std::coin(6_000, "uatom"),
+                vec![],
+            )],
+        );
+        let rebalance = |moves: Vec<(&str, &str, u128)>| ExecuteMsg::Rebalance {
+            moves: moves
+                .into_iter()
+                .map(|(from, to, amount)| (from.to_string(), to.to_string(), Uint128::new(amount)))
+                .collect(),
+        };
+
+        // Stake can't move while the lockers of session 5 hold voting power
+        IS_PAUSED.save(&mut deps.storage, &true).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            rebalance(vec![(&validator, &validator2, 1_000)]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ContractPaused {});
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.is_active = false;
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+        IS_PAUSED.save(&mut deps.storage, &false).unwrap();
+
+        let anyone = deps.api.addr_make("anyone");
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&anyone, &[]),
+            rebalance(vec![(&validator, &validator2, 1_000)]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            rebalance(vec![(&validator, "cosmosvaloper1other", 1_000)]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidValidator { .. }));
+
+        // The moves from a validator are limited to what can be redelegated in total
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            rebalance(vec![
+                (&validator, &validator2, 4_000),
+                (&validator, &validator2, 3_000),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RedelegationLimit {
+                validator: validator.clone(),
+                available: Uint128::new(2_000),
+                required: Uint128::new(3_000),
+            }
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            rebalance(vec![(&validator, &validator2, 6_000)]),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, REPLY_REBALANCE);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Staking(StakingMsg::Redelegate {
+                src_validator: validator,
+                dst_validator: validator2,
+                amount: cosmwasm_std::coin(6_000, "uatom"),
+            })
+        );
+
+        // The rewards withdrawn by the redelegation go to stakers, shares are unchanged
+        deps.querier
+            .bank
+            .update_balance(&env.contract.address, coins(50, "uatom"));
+        #[allow(deprecated)]
+        let rebalance_reply = Reply {
+            id: REPLY_REBALANCE,
+            payload: Binary::default(),
+            gas_used: 0,
+            result: cosmwasm_std::SubMsgResult::Ok(cosmwasm_std::SubMsgResponse {
+                events: vec![],
+                data: None,
+                msg_responses: vec![],
+            }),
+        };
+        reply(deps.as_mut(), env, rebalance_reply).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(10_000));
+        assert_eq!(state.reward_index("uatom"), Decimal256::permille(5));
+    }
+
+    #[test]
+    fn test_validator_health_emergency() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let owner = deps.api.addr_make("owner");
+        let validator = deps.api.addr_make("validator").to_string();
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        let delegation = |validator: &str, amount: u128| {
+            cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator.to_string(),
+                cosmwasm_std::coin(amount, "uatom"),
+                cosmwasm_std::coin(amount, "uatom"),
+                vec![],
+            )
+        };
+        let update_staking = |deps: &mut GovMockDeps, validators: &[&str]| {
+            deps.querier.staking.update(
+                "uatom",
+                &mock_validators(validators),
+                &[
+                    delegation(&validator, 10_000),
+                    delegation(&validator2, 4_000),
+                ],
+            );
+        };
+        update_staking(&mut deps, &[&validator, &validator2]);
+        let anyone = deps.api.addr_make("anyone");
+        let check = |deps: &mut GovMockDeps| {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&anyone, &[]),
+                ExecuteMsg::CheckValidatorHealth {},
+            )
+            .unwrap()
+        };
+
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "emergency"), "false");
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::EmergencyRedelegate {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NotInEmergency {});
+
+        // A jailed validator blocks deposits and rentals
+        deps.querier.jailed.insert(validator2.clone());
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "emergency"), "true");
+        assert_eq!(attribute_value(&res, "unhealthy_validators"), validator2);
+
+        let emergency = ContractError::EmergencyMode {
+            validators: validator2.clone(),
+        };
+        let staker = deps.api.addr_make("staker");
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&staker, &coins(100, format!("{}/1", validator2))),
+            ExecuteMsg::DepositLsmShares {},
+        )
+        .unwrap_err();
+        assert_eq!(err, emergency);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&anyone, &coins(100, "uatom")),
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: None,
+                max_price: None,
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, emergency);
+
+        // Compounding would delegate the rewards to the jailed validator
+        for msg in [
+            ExecuteMsg::Compound {},
+            ExecuteMsg::SetAutoCompound { enabled: true },
+        ] {
+            let err =
+                execute(deps.as_mut(), env.clone(), message_info(&staker, &[]), msg).unwrap_err();
+            assert_eq!(err, emergency);
+        }
+
+        // Not while a voting session holds the delegations
+        IS_PAUSED.save(&mut deps.storage, &true).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::EmergencyRedelegate {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ContractPaused {});
+        IS_PAUSED.save(&mut deps.storage, &false).unwrap();
+
+        // Only the owner or stakers move the stake to the first healthy validator
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&anyone, &[]),
+            ExecuteMsg::EmergencyRedelegate {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let mut staker_info = Staker::new();
+        staker_info.staked_amount = Uint128::new(100);
+        STAKERS
+            .save(&mut deps.storage, &staker, &staker_info)
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&staker, &[]),
+            ExecuteMsg::EmergencyRedelegate {},
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, REPLY_REBALANCE);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Staking(StakingMsg::Redelegate {
+                src_validator: validator2.clone(),
+                dst_validator: validator.clone(),
+                amount: cosmwasm_std::coin(4_000, "uatom"),
+            })
+        );
+
+        // The mode is lifted once the validator is unjailed
+        deps.querier.jailed.remove(&validator2);
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "emergency"), "false");
+
+        // A validator that no longer exists is unhealthy
+        update_staking(&mut deps, &[&validator]);
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "emergency"), "true");
+        assert_eq!(attribute_value(&res, "unhealthy_validators"), validator2);
+        update_staking(&mut deps, &[&validator, &validator2]);
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "emergency"), "false");
+
+        // A failing query fails the check instead of flagging the validator
+        deps.querier.failing.insert(validator2.clone());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&anyone, &[]),
+            ExecuteMsg::CheckValidatorHealth {},
+        )
+        .unwrap_err();
+        deps.querier.failing.remove(&validator2);
+        let config: ConfigResponse =
+            cosmwasm_std::from_json(query(deps.as_ref(), env, QueryMsg::Config {}).unwrap())
+                .unwrap();
+        assert!(config.unhealthy_validators.is_empty());
+    }
+
+    #[test]
+    fn test_is_validator_healthy() {
+        let mut deps = mock_gov_dependencies();
+        let validator = "cosmosvaloper1second".to_string();
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[&validator]), &[]);
+        let healthy = |deps: &GovMockDeps| is_validator_healthy(&deps.as_ref().querier, &validator);
+        assert!(healthy(&deps).unwrap());
+
+        // Jailed, which includes tombstoned validators
+        deps.querier.jailed.insert(validator.clone());
+        assert!(!healthy(&deps).unwrap());
+        deps.querier.jailed.remove(&validator);
+
+        // Missing from the staking module
+        deps.querier.staking.update("uatom", &[], &[]);
+        assert!(!healthy(&deps).unwrap());
+
+        // Any other failure is an error
+        deps.querier
+            .staking
+            .update("uatom", &mock_validators(&[&validator]), &[]);
+        deps.querier.failing.insert(validator.clone());
+        healthy(&deps).unwrap_err();
+    }
+
+    #[test]
+    fn test_clear_fallback_validator() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        let owner = message_info(&deps.api.addr_make("owner"), &[]);
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        config.fallback_validator = Some(validator2.clone());
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        // The fallback validator can't be removed from the accepted validators
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                validators: Some(vec![]),
+                ..Default::default()
+            })),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidValidator { .. }));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                fallback_validator: Some(validator2),
+                clear_fallback_validator: true,
+                ..Default::default()
+            })),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ConflictingFallbackValidator {});
+
+        // Unless it is unset in the same update
+        let res = execute(
+            deps.as_mut(),
+            env,
+            owner,
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                validators: Some(vec![]),
+                clear_fallback_validator: true,
+                ..Default::default()
+            })),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "new_fallback_validator"), "none");
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert!(config.validators.is_empty());
+        assert_eq!(config.fallback_validator, None);
+    }
+
+    #[test]
+    fn test_exchange_rate_after_slash() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        let validator = deps.api.addr_make("validator").to_string();
+        let staker_addr = deps.api.addr_make("staker");
+        let mut staker = Staker::new();
+        staker.staked_amount = Uint128::new(10_000);
+        STAKERS
+            .save(&mut deps.storage, &staker_addr, &staker)
+            .unwrap();
+
+        // A 10% slash of the delegation lowers the tokens backing every share
+        deps.querier.staking.update(
+            "uatom",
+            &[],
+            &[cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator,
+                cosmwasm_std::coin(9_000, "uatom"),
+                cosmwasm_std::coin(9_000, "uatom"),
+                vec![],
+            )],
+        );
+        let rate: ExchangeRateResponse = cosmwasm_std::from_json(
+            query(deps.as_ref(), env.clone(), QueryMsg::ExchangeRate {}).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(rate.exchange_rate, Decimal256::percent(90));
+        assert_eq!(rate.total_tokens, Uint128::new(9_000));
+        assert_eq!(rate.total_shares, Uint128::new(10_000));
+        let info = query_staker_info(deps.as_ref(), env.clone(), staker_addr.to_string()).unwrap();
+        assert_eq!(info.staked_tokens, Uint128::new(9_000));
+
+        // The staker can't withdraw more than the slashed value of their shares
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&staker_addr, &[]),
+            ExecuteMsg::Withdraw {
+                amount: Uint128::new(9_001),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InsufficientStakedAmount {});
+
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&staker_addr, &[]),
+            ExecuteMsg::Withdraw {
+                amount: Uint128::new(4_500),
+            },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "slashed_tokens"), "1000");
+        assert_eq!(attribute_value(&res, "shares_deducted"), "5000");
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(5_000));
+        assert_eq!(state.total_tokens, Uint128::new(4_500));
+        assert_eq!(state.exchange_rate(), Decimal256::percent(90));
+    }
+
+    #[test]
+    fn test_deposit_lsm_shares_of_slashed_validator() {
+        use prost::Message;
+
+        #[derive(Clone, PartialEq, Message)]
+        struct ProtoCoin {
+            #[prost(string, tag = "1")]
+            pub denom: String,
+            #[prost(string, tag = "2")]
+            pub amount: String,
+        }
+
+        #[derive(Clone, PartialEq, Message)]
+        struct MsgRedeemTokensForSharesResponse {
+            #[prost(message, required, tag = "1")]
+            pub amount: ProtoCoin,
+        }
+
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+        let depositor = deps.api.addr_make("depositor");
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&depositor, &coins(1_000, format!("{}/1", validator2))),
+            ExecuteMsg::DepositLsmShares {},
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].id, REPLY_CLAIM_REWARDS_DEPOSIT);
+
+        #[allow(deprecated)]
+        let reply_with = |id: u64, msg_responses: Vec<cosmwasm_std::MsgResponse>| Reply {
+            id,
+            payload: Binary::default(),
+            gas_used: 0,
+            result: cosmwasm_std::SubMsgResult::Ok(cosmwasm_std::SubMsgResponse {
+                events: vec![],
+                data: None,
+                msg_responses,
+            }),
+        };
+        let res = reply(
+            deps.as_mut(),
+            env.clone(),
+            reply_with(REPLY_CLAIM_REWARDS_DEPOSIT, vec![]),
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].id, REPLY_REDEEM_SHARES_DEPOSIT);
+
+        // The validator was slashed, so the 1000 LSM shares only redeem 800 tokens
+        let redeemed = MsgRedeemTokensForSharesResponse {
+            amount: ProtoCoin {
+                denom: "uatom".to_string(),
+                amount: "800".to_string(),
+            },
+        };
+        let res = reply(
+            deps.as_mut(),
+            env.clone(),
+            reply_with(
+                REPLY_REDEEM_SHARES_DEPOSIT,
+                vec![cosmwasm_std::MsgResponse {
+                    type_url: "/gaia.liquid.v1beta1.MsgRedeemTokensForSharesResponse".to_string(),
+                    value: Binary::from(redeemed.encode_to_vec()),
+                }],
+            ),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "tokens"), "800");
+        assert_eq!(attribute_value(&res, "shares"), "800");
+        assert!(!ACTIVE_DEPOSIT.exists(&deps.storage));
+
+        let staker = STAKERS.load(&deps.storage, &depositor).unwrap();
+        assert_eq!(staker.staked_amount, Uint128::new(800));
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_tokens, Uint128::new(10_800));
+        assert_eq!(state.total_staked, Uint128::new(10_800));
+
+        // The delegation grew by the redeemed tokens, which is not mistaken for a slash
+        set_delegated(&mut deps, &env, 10_800);
+        let rate = query_rate(&deps, &env);
+        assert_eq!(rate.exchange_rate, Decimal256::one());
+        assert_eq!(rate.total_tokens, Uint128::new(10_800));
+    }
+
+    /// Delegate the given amount to the pool validator, replacing any delegation
+    fn set_delegated(deps: &mut GovMockDeps, env: &Env, amount: u128) {
+        let validator = deps.api.addr_make("validator").to_string();
+        deps.querier.staking.update(
+            "uatom",
+            &[],
+            &[cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator,
+                cosmwasm_std::coin(amount, "uatom"),
+                cosmwasm_std::coin(amount, "uatom"),
+                vec![],
+            )],
+        );
+    }
+
+    fn query_rate(deps: &GovMockDeps, env: &Env) -> ExchangeRateResponse {
+        cosmwasm_std::from_json(
+            query(deps.as_ref(), env.clone(), QueryMsg::ExchangeRate {}).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_slash_during_voting_session() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        IS_PAUSED.save(&mut deps.storage, &true).unwrap();
+        let locker = deps.api.addr_make("locker");
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.deploy(&locker, Uint128::new(3_000));
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        // The stake deployed in the locker still backs the shares
+        set_delegated(&mut deps, &env, 7_000);
+        let rate = query_rate(&deps, &env);
+        assert_eq!(rate.total_tokens, Uint128::new(10_000));
+        assert_eq!(rate.exchange_rate, Decimal256::one());
+
+        // A 10% slash of the delegation left with the pool is booked while the session is open
+        set_delegated(&mut deps, &env, 6_300);
+        let rate = query_rate(&deps, &env);
+        assert_eq!(rate.total_tokens, Uint128::new(9_300));
+        let anyone = deps.api.addr_make("anyone");
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&anyone, &[]),
+            ExecuteMsg::CheckValidatorHealth {},
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "slashed_tokens"), "700");
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_tokens, Uint128::new(9_300));
+        assert_eq!(state.exchange_rate(), Decimal256::percent(93));
+    }
+
+    #[test]
+    fn test_destroy_with_deployed_stake() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        IS_PAUSED.save(&mut deps.storage, &true).unwrap();
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+        let locker2 = deps.api.addr_make("locker2");
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session
+            .validator_lockers
+            .push((validator2.clone(), 1, locker2.clone()));
+        session.deploy(&locker2, Uint128::new(3_000));
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+        set_delegated(&mut deps, &env, 7_000);
+
+        let owner = deps.api.addr_make("owner");
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&owner, &[]),
+            ExecuteMsg::EmergencyDestroyVotingLockers { proposal_id: 5 },
+        )
+        .unwrap();
+        assert!(!IS_PAUSED.load(&deps.storage).unwrap());
+
+        // The destroyed session still counts the stake its locker hasn't returned yet
+        let rate = query_rate(&deps, &env);
+        assert_eq!(rate.total_tokens, Uint128::new(10_000));
+        let anyone = deps.api.addr_make("anyone");
+        let check = |deps: &mut GovMockDeps| {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(&anyone, &[]),
+                ExecuteMsg::CheckValidatorHealth {},
+            )
+            .unwrap()
+        };
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "slashed_tokens"), "0");
+
+        // Once returned, the redeemed shares are delegated again
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&locker2, &coins(3_000, format!("{}/1", validator2))),
+            ExecuteMsg::ReturnLsmShares {
+                proposal_id: 5,
+                vote_option: 1,
+            },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "released"), "3000");
+        set_delegated(&mut deps, &env, 10_000);
+        let res = check(&mut deps);
+        assert_eq!(attribute_value(&res, "slashed_tokens"), "0");
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.total_tokens, Uint128::new(10_000));
     }
 
-    // Add this locker address
-    active_creation
-        .locker_addresses
-        .push((payload.vote_option, locker_addr.clone()));
-    active_creation.created_count += 1;
+    #[test]
+    fn test_rental_ledger() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+
+        let rentals = [
+            (&alice, 100),
+            (&bob, 50),
+            (&alice, 20),
+            (&bob, 30),
+            (&alice, 10),
+        ];
+        for (renter, amount) in rentals {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(renter, &coins(amount, "uatom")),
+                ExecuteMsg::RentVotingPower {
+                    proposal_id: 5,
+                    vote_option: 1,
+                    min_vp_amount: None,
+                    max_price: None,
+                    allow_partial: false,
+                    validator: None,
+                },
+            )
+            .unwrap();
+        }
 
-    let mut response = Response::new()
-        .add_attribute("action", "instantiate_locker_reply")
-        .add_attribute("proposal_id", payload.proposal_id.to_string())
-        .add_attribute("vote_option", payload.vote_option.to_string())
-        .add_attribute("locker_address", locker_addr.to_string())
-        .add_attribute("created_count", active_creation.created_count.to_string())
-        .add_attribute(
-            "expected_count",
-            active_creation.expected_lockers.to_string(),
+        // Each rental keeps its own record
+        let res: RentalsResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::RentalsByRenter {
+                    renter: alice.to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let alice_rental = |id, amount: u128| Rental {
+            id,
+            renter: alice.clone(),
+            proposal_id: 5,
+            vote_option: 1,
+            amount_paid: Uint128::new(amount),
+            vp_amount: Uint128::new(amount * 10),
+            height: env.block.height,
+        };
+        assert_eq!(
+            res.rentals,
+            vec![
+                alice_rental(0, 100),
+                alice_rental(2, 20),
+                alice_rental(4, 10)
+            ]
         );
 
-    // Check if all lockers have been created
-    if active_creation.created_count == active_creation.expected_lockers {
-        // All lockers are created! Create the voting session
-        let voting_session = lsm_types::VotingSession {
-            proposal_id: active_creation.proposal_id,
-            locker_addresses: active_creation.locker_addresses.clone(),
-            is_active: true,
-        };
+        // Page through the rentals of the proposal two at a time
+        let mut pages = vec![];
+        let mut start_after = None;
+        loop {
+            let res: RentalsResponse = cosmwasm_std::from_json(
+                query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::RentalsByProposal {
+                        proposal_id: 5,
+                        start_after,
+                        limit: Some(2),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            let Some(last) = res.rentals.last() else {
+                break;
+            };
+            start_after = Some(last.id);
+            pages.push(
+                res.rentals
+                    .iter()
+                    .map(|rental| (rental.renter.clone(), rental.amount_paid.u128()))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(
+            pages,
+            vec![
+                vec![(alice.clone(), 100), (bob.clone(), 50)],
+                vec![(alice.clone(), 20), (bob.clone(), 30)],
+                vec![(alice.clone(), 10)],
+            ]
+        );
 
-        VOTING_SESSIONS.save(deps.storage, active_creation.proposal_id, &voting_session)?;
+        // The renter index pages the same way
+        let res: RentalsResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::RentalsByRenter {
+                    renter: bob.to_string(),
+                    start_after: Some(1),
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.rentals.len(), 1);
+        assert_eq!(res.rentals[0].id, 3);
+
+        let res: RentalTotalsResponse = cosmwasm_std::from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::RentalTotals { proposal_id: 5 },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.totals,
+            vec![RentalTotal {
+                vote_option: 1,
+                amount_paid: Uint128::new(210),
+                vp_amount: Uint128::new(2100),
+            }]
+        );
+    }
 
-        // Clean up the active creation state
-        ACTIVE_VOTING_SESSION_CREATION.remove(deps.storage);
+    #[test]
+    fn test_create_voting_lockers_with_explicit_options() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        set_proposal_in_voting(&mut deps, &env, 6);
+        let owner = message_info(&deps.api.addr_make("owner"), &[]);
+        let create = |options| ExecuteMsg::CreateVotingLockers {
+            proposal_id: 6,
+            auction: None,
+            options,
+            weighted: false,
+        };
 
-        response = response
-            .add_attribute("voting_session_created", "true")
-            .add_attribute(
-                "num_lockers",
-                voting_session.locker_addresses.len().to_string(),
-            );
-    } else {
-        // Still waiting for more lockers, save the updated state
-        ACTIVE_VOTING_SESSION_CREATION.save(deps.storage, &active_creation)?;
+        // Options outside of the proposal options are rejected
+        for options in [vec![], vec![1, 7], vec![1, 1]] {
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                owner.clone(),
+                create(Some(options)),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidVoteOptions { .. }));
+        }
+
+        // Only the requested lockers are instantiated
+        let res = execute(deps.as_mut(), env, owner, create(Some(vec![1, 3]))).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(attribute_value(&res, "num_lockers"), "2");
+        let active = ACTIVE_VOTING_SESSION_CREATION.load(&deps.storage).unwrap();
+        assert_eq!(active.expected_lockers, 2);
     }
 
-    Ok(response)
-}
+    #[test]
+    fn test_create_voting_lockers_requires_voting_period() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        set_proposal_in_voting(&mut deps, &env, 6);
+        let owner = message_info(&deps.api.addr_make("owner"), &[]);
+        let create = |proposal_id, auction| ExecuteMsg::CreateVotingLockers {
+            proposal_id,
+            auction,
+            options: None,
+            weighted: false,
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
-    use cosmwasm_std::{coins, Decimal256};
+        deps.querier
+            .proposals
+            .insert(7, (1, env.block.time.plus_days(7)));
+        deps.querier
+            .proposals
+            .insert(8, (2, env.block.time.minus_seconds(1)));
+
+        let err = execute(deps.as_mut(), env.clone(), owner.clone(), create(7, None)).unwrap_err();
+        assert!(matches!(err, ContractError::ProposalNotInVoting { .. }));
+        let err = execute(deps.as_mut(), env.clone(), owner.clone(), create(8, None)).unwrap_err();
+        assert_eq!(err, ContractError::ProposalVotingEnded { proposal_id: 8 });
+        let err = execute(deps.as_mut(), env.clone(), owner.clone(), create(9, None)).unwrap_err();
+        assert_eq!(err, ContractError::ProposalNotFound { proposal_id: 9 });
+
+        // The auction must be settled before voting ends
+        let auction = AuctionParams {
+            commit_duration: 4 * 86_400,
+            reveal_duration: 3 * 86_400,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            create(6, Some(auction)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAuction { .. }));
+
+        // The voting end time is kept for the voting session
+        execute(deps.as_mut(), env.clone(), owner, create(6, None)).unwrap();
+        let active = ACTIVE_VOTING_SESSION_CREATION.load(&deps.storage).unwrap();
+        assert_eq!(active.voting_end_time, env.block.time.plus_days(7));
+    }
 
     #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies();
-        let owner_addr = deps.api.addr_make("owner");
-        let validator_addr = deps.api.addr_make("validator");
-        let msg = InstantiateMsg {
-            staking_denom: "uatom".to_string(),
-            owner: owner_addr.to_string(),
-            validator: validator_addr.to_string(),
-            max_cap: None,
-            locker_code_id: 1,
+    fn test_rent_requires_active_session() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let renter = message_info(&deps.api.addr_make("renter"), &coins(100, "uatom"));
+        let rent = ExecuteMsg::RentVotingPower {
+            proposal_id: 5,
+            vote_option: 1,
+            min_vp_amount: None,
+            max_price: None,
+            allow_partial: false,
+            validator: None,
         };
 
-        let info = message_info(&deps.api.addr_make("creator"), &[]);
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        // The payment is rejected once the voting period is over
+        let voting_end = env.block.time.plus_days(7);
+        env.block.time = voting_end;
+        let err = execute(deps.as_mut(), env.clone(), renter.clone(), rent.clone()).unwrap_err();
+        assert_eq!(err, ContractError::ProposalVotingEnded { proposal_id: 5 });
+
+        // Or once the session is destroyed
+        env.block.time = voting_end.minus_seconds(1);
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.is_active = false;
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+        let err = execute(deps.as_mut(), env, renter, rent).unwrap_err();
+        assert_eq!(err, ContractError::VotingSessionInactive { proposal_id: 5 });
+    }
 
-        // Check config
-        let config = CONFIG.load(&deps.storage).unwrap();
-        assert_eq!(config.owner, owner_addr);
-        assert_eq!(config.staking_denom, "uatom");
-        assert_eq!(config.validator, validator_addr.to_string());
-        assert_eq!(config.max_cap, None);
-        assert_eq!(config.locker_code_id, 1);
+    #[test]
+    fn test_permissionless_destroy_after_voting_end() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let owner = message_info(&deps.api.addr_make("owner"), &[]);
+        let keeper = deps.api.addr_make("keeper");
+        let update_keeper_tip = |keeper_tip| {
+            ExecuteMsg::UpdateConfig(Box::new(ConfigUpdate {
+                keeper_tip: Some(keeper_tip),
+                ..Default::default()
+            }))
+        };
 
-        // Check state
+        // The keeper tip is capped
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            update_keeper_tip(MAX_KEEPER_TIP + Uint128::one()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::KeeperTipTooHigh {
+                tip: MAX_KEEPER_TIP + Uint128::one(),
+                max: MAX_KEEPER_TIP,
+            }
+        );
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            owner,
+            update_keeper_tip(Uint128::new(10)),
+        )
+        .unwrap();
+
+        // The keeper tip is withheld from the rental income
+        let renter = message_info(&deps.api.addr_make("renter"), &coins(100, "uatom"));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            renter,
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: None,
+                max_price: None,
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap();
         let state = STATE.load(&deps.storage).unwrap();
-        assert_eq!(state.total_staked, Uint128::zero());
-        assert_eq!(state.global_reward_index, Decimal256::zero());
+        assert_eq!(state.rental_index, Decimal256::permille(9));
 
-        // Check is_paused
-        let is_paused = IS_PAUSED.load(&deps.storage).unwrap();
-        assert_eq!(is_paused, false);
+        // Another session keeps the contract paused
+        let mut other_session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        other_session.proposal_id = 6;
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 6, &other_session)
+            .unwrap();
+        IS_PAUSED.save(&mut deps.storage, &true).unwrap();
+
+        // The proposal is still in voting period
+        let destroy = ExecuteMsg::DestroyVotingLockers { proposal_id: 5 };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &[]),
+            destroy.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ProposalStillActive { .. }));
+
+        // Anyone can destroy once the voting end time has passed
+        env.block.time = env.block.time.plus_days(7);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&keeper, &[]),
+            destroy.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: keeper.to_string(),
+                amount: coins(10, "uatom"),
+            })
+        );
+        assert_eq!(attribute_value(&res, "unpaused"), "false");
+        assert!(IS_PAUSED.load(&deps.storage).unwrap());
+
+        let err = execute(deps.as_mut(), env, message_info(&keeper, &[]), destroy).unwrap_err();
+        assert_eq!(err, ContractError::VotingSessionInactive { proposal_id: 5 });
     }
 
     #[test]
-    fn test_parse_lsm_denom_valid() {
-        let valid_denom = "cosmosvaloper1abc123/456";
-        let result = parse_lsm_denom(valid_denom).unwrap();
-        assert_eq!(result.validator, "cosmosvaloper1abc123");
-        assert_eq!(result.record_id, "456");
+    fn test_emergency_destroy_voting_lockers() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+        let owner = message_info(&deps.api.addr_make("owner"), &[]);
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.rental_fee_rate = Decimal::percent(10);
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.keeper_reserve = Uint128::new(100);
+        session.validator_lockers.push((
+            "cosmosvaloper1second".to_string(),
+            1,
+            deps.api.addr_make("locker2"),
+        ));
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        // Only the owner can skip the proposal status check
+        let destroy = ExecuteMsg::EmergencyDestroyVotingLockers { proposal_id: 5 };
+        let err = execute(deps.as_mut(), env.clone(), keeper, destroy.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // The proposal is still in voting period, every locker returns its shares in the
+        // same transaction through EmergencyDestroy
+        let res = execute(deps.as_mut(), env, owner, destroy).unwrap();
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert!(!session.is_active);
+        let emergency_destroy = |locker: &Addr| {
+            CosmosMsg::Wasm(cosmwasm_std::WasmMsg::Execute {
+                contract_addr: locker.to_string(),
+                msg: to_json_binary(&proposal_locker_types::ExecuteMsg::EmergencyDestroy {})
+                    .unwrap(),
+                funds: vec![],
+            })
+        };
+        assert_eq!(
+            res.messages
+                .iter()
+                .map(|msg| msg.msg.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                emergency_destroy(&deps.api.addr_make("locker")),
+                emergency_destroy(&deps.api.addr_make("locker2")),
+            ]
+        );
+        assert_eq!(attribute_value(&res, "emergency"), "true");
+
+        // The keeper reserve goes back to stakers as rental income, minus the protocol fee
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(accrued_fees(&state), coins(10, "uatom"));
+        assert_eq!(
+            state.rental_index,
+            Decimal256::from_ratio(90u128, 10_000u128)
+        );
+        assert!(!IS_PAUSED.load(&deps.storage).unwrap());
     }
 
     #[test]
-    fn test_parse_lsm_denom_invalid_format() {
-        let invalid_denom = "cosmosvaloper1abc123";
-        let result = parse_lsm_denom(invalid_denom);
-        assert!(result.is_err());
+    fn test_weighted_voting_session() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        set_proposal_in_voting(&mut deps, &env, 6);
+        let owner = message_info(&deps.api.addr_make("owner"), &[]);
+        let validator = deps.api.addr_make("validator").to_string();
+        let validator2 = "cosmosvaloper1second".to_string();
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.validators = vec![validator2.clone()];
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+        let delegation = |validator: &str, amount: u128| {
+            cosmwasm_std::FullDelegation::create(
+                env.contract.address.clone(),
+                validator.to_string(),
+                cosmwasm_std::coin(amount, "uatom"),
+                cosmwasm_std::coin(amount, "uatom"),
+                vec![],
+            )
+        };
+        deps.querier.staking.update(
+            "uatom",
+            &mock_validators(&[validator.as_str(), validator2.as_str()]),
+            &[
+                delegation(&validator, 10_000),
+                delegation(&validator2, 4_000),
+            ],
+        );
+
+        // A single locker per validator is created, splitting its vote evenly until the
+        // first rental
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner,
+            ExecuteMsg::CreateVotingLockers {
+                proposal_id: 6,
+                auction: None,
+                options: Some(vec![1, 3]),
+                weighted: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        let CosmosMsg::Wasm(cosmwasm_std::WasmMsg::Instantiate { msg, .. }) = &res.messages[0].msg
+        else {
+            panic!("expected locker instantiation");
+        };
+        let init: proposal_locker_types::InstantiateMsg = cosmwasm_std::from_json(msg).unwrap();
+        assert_eq!(init.vote_option, WEIGHTED_LOCKER_OPTION);
+        assert_eq!(
+            init.weights,
+            Some(vec![
+                WeightedVoteOption {
+                    option: 1,
+                    weight: Decimal::percent(50),
+                },
+                WeightedVoteOption {
+                    option: 3,
+                    weight: Decimal::percent(50),
+                },
+            ])
+        );
+
+        let lockers = instantiate_lockers(&mut deps, &env, &res);
+        let (_, _, locker) = lockers[0].clone();
+        let (_, _, locker2) = lockers[1].clone();
+        let session = VOTING_SESSIONS.load(&deps.storage, 6).unwrap();
+        assert_eq!(session.weighted_options, Some(vec![1, 3]));
+        assert_eq!(
+            session.locker_addresses,
+            vec![(WEIGHTED_LOCKER_OPTION, locker.clone())]
+        );
+        assert_eq!(
+            session.validator_lockers,
+            vec![(validator2.clone(), WEIGHTED_LOCKER_OPTION, locker2.clone())]
+        );
+
+        let rent = |vote_option: i32, validator: Option<String>| ExecuteMsg::RentVotingPower {
+            proposal_id: 6,
+            vote_option,
+            min_vp_amount: None,
+            max_price: None,
+            allow_partial: false,
+            validator,
+        };
+        for (vote_option, amount) in [(1, 300), (3, 100)] {
+            let renter = message_info(&deps.api.addr_make("renter"), &coins(amount, "uatom"));
+            let res = execute(deps.as_mut(), env.clone(), renter, rent(vote_option, None)).unwrap();
+            assert_eq!(attribute_value(&res, "locker"), locker.to_string());
+        }
+        let renter = message_info(&deps.api.addr_make("renter"), &coins(100, "uatom"));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            renter,
+            rent(1, Some(validator2.clone())),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "locker"), locker2.to_string());
+
+        // The locker option and options outside the session are not votes
+        for vote_option in [WEIGHTED_LOCKER_OPTION, 2] {
+            let renter = message_info(&deps.api.addr_make("renter"), &coins(100, "uatom"));
+            let err =
+                execute(deps.as_mut(), env.clone(), renter, rent(vote_option, None)).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::LockerNotFound {
+                    proposal_id: 6,
+                    vote_option,
+                }
+            );
+        }
+
+        // The locker receives the shares and every locker re-casts its vote following the
+        // rented amounts
+        deps.querier.bank.update_balance(
+            &env.contract.address,
+            coins(1000, format!("{}/1", validator)),
+        );
+        let res = forward_lsm_shares_to_locker(deps.as_ref(), &env, 6, 3, &validator).unwrap();
+        let update_vote = |locker: &Addr| {
+            CosmosMsg::Wasm(cosmwasm_std::WasmMsg::Execute {
+                contract_addr: locker.to_string(),
+                msg: to_json_binary(&proposal_locker_types::ExecuteMsg::UpdateVote {
+                    weights: vec![
+                        WeightedVoteOption {
+                            option: 1,
+                            weight: Decimal::percent(80),
+                        },
+                        WeightedVoteOption {
+                            option: 3,
+                            weight: Decimal::percent(20),
+                        },
+                    ],
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        };
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(res.messages[1].msg, update_vote(&locker));
+        assert_eq!(res.messages[2].msg, update_vote(&locker2));
+
+        // Once the voting period is over the lockers are destroyed
+        env.block.time = env.block.time.plus_days(7);
+        let keeper = message_info(&deps.api.addr_make("keeper"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            keeper,
+            ExecuteMsg::DestroyVotingLockers { proposal_id: 6 },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "num_lockers"), "2");
+        assert_eq!(attribute_value(&res, "unpaused"), "true");
+
+        // A weighted locker returns its shares under the option it was created with
+        let shares = coins(1000, format!("{}/3", validator2));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&locker2, &shares),
+            ExecuteMsg::ReturnLsmShares {
+                proposal_id: 6,
+                vote_option: 1,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidLocker { .. }));
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&locker2, &shares),
+            ExecuteMsg::ReturnLsmShares {
+                proposal_id: 6,
+                vote_option: WEIGHTED_LOCKER_OPTION,
+            },
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "released"), "1000");
+        let session = VOTING_SESSIONS.load(&deps.storage, 6).unwrap();
+        assert_eq!(session.deployed, vec![(locker, Uint128::new(4000))]);
     }
 
     #[test]
-    fn test_parse_lsm_denom_invalid_validator() {
-        let invalid_denom = "invalidprefix1abc123/456";
-        let result = parse_lsm_denom(invalid_denom);
-        assert!(result.is_err());
+    fn test_rent_voting_power_follows_pricing_curve() {
+        let mut deps = mock_gov_dependencies();
+        let env = setup_pool(
+            &mut deps,
+            RentalPricing {
+                default: lsm_types::PricingModel::Linear {
+                    base_price: Decimal256::percent(10),
+                    slope: Decimal256::one(),
+                },
+                overrides: vec![],
+            },
+        );
+        open_voting_session(&mut deps, &env, 5);
+
+        // The same payment buys less voting power as the proposal fills up
+        let mut rented = vec![];
+        for _ in 0..2 {
+            let info = message_info(&deps.api.addr_make("renter"), &coins(1000, "uatom"));
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                info,
+                ExecuteMsg::RentVotingPower {
+                    proposal_id: 5,
+                    vote_option: 1,
+                    min_vp_amount: None,
+                    max_price: None,
+                    allow_partial: false,
+                    validator: None,
+                },
+            )
+            .unwrap();
+            rented.push(attribute_value(&res, "vp_amount").parse::<u128>().unwrap());
+        }
+        assert!(rented[1] < rented[0]);
+
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.rented_amount.u128(), rented[0] + rented[1]);
     }
 
     #[test]
-    fn test_parse_lsm_denom_invalid_record_id() {
-        let invalid_denom = "cosmosvaloper1abc123/notanumber";
-        let result = parse_lsm_denom(invalid_denom);
-        assert!(result.is_err());
+    fn test_bid_and_quote_need_a_locker_for_every_validator() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+
+        // Option 2 only has a locker for the primary validator
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session
+            .locker_addresses
+            .push((2, deps.api.addr_make("locker_no")));
+        session.validator_lockers = vec![(
+            "cosmosvaloper1second".to_string(),
+            1,
+            deps.api.addr_make("locker2"),
+        )];
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        let quote = |deps: &GovMockDeps, vote_option| {
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::RentalQuote {
+                    proposal_id: 5,
+                    vote_option,
+                    vp_amount: Uint128::new(1000),
+                },
+            )
+        };
+        quote(&deps, 1).unwrap();
+        quote(&deps, 2).unwrap_err();
+
+        session.auction = Some(Auction {
+            commit_end: env.block.time.plus_seconds(100),
+            reveal_end: env.block.time.plus_seconds(200),
+            settled: false,
+        });
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let bid = RevealedBid {
+            vote_option: 2,
+            vp_amount: Uint128::new(1000),
+            price_per_vp: Decimal256::percent(50),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &coins(500, "uatom")),
+            ExecuteMsg::CommitBid {
+                proposal_id: 5,
+                commitment: bid_commitment(&alice, 5, &bid, "salt"),
+            },
+        )
+        .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(100);
+        let err = execute(
+            deps.as_mut(),
+            env,
+            message_info(&alice, &[]),
+            ExecuteMsg::RevealBid {
+                proposal_id: 5,
+                vote_option: bid.vote_option,
+                vp_amount: bid.vp_amount,
+                price_per_vp: bid.price_per_vp,
+                salt: "salt".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::LockerNotFound {
+                proposal_id: 5,
+                vote_option: 2,
+            }
+        );
     }
 
     #[test]
-    fn test_deposit_rewards_and_claim() {
-        let mut deps = mock_dependencies();
+    fn test_sealed_bid_auction() {
+        let mut deps = mock_gov_dependencies();
+        let mut env = setup_pool(&mut deps, RentalPricing::default());
+        open_voting_session(&mut deps, &env, 5);
+
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session
+            .locker_addresses
+            .push((2, deps.api.addr_make("locker_no")));
+        session.auction = Some(Auction {
+            commit_end: env.block.time.plus_seconds(100),
+            reveal_end: env.block.time.plus_seconds(200),
+            settled: false,
+        });
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
 
-        // Initialize
-        let owner_addr = deps.api.addr_make("owner");
-        let validator_addr = deps.api.addr_make("validator");
-        let msg = InstantiateMsg {
-            staking_denom: "uatom".to_string(),
-            owner: owner_addr.to_string(),
-            validator: validator_addr.to_string(),
-            max_cap: None,
-            locker_code_id: 1,
+        // Fixed-price rentals are disabled for auction sessions
+        let info = message_info(&deps.api.addr_make("renter"), &coins(100, "uatom"));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::RentVotingPower {
+                proposal_id: 5,
+                vote_option: 1,
+                min_vp_amount: None,
+                max_price: None,
+                allow_partial: false,
+                validator: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::AuctionMode { proposal_id: 5 });
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        let alice_bid = RevealedBid {
+            vote_option: 1,
+            vp_amount: Uint128::new(6000),
+            price_per_vp: Decimal256::percent(50),
+        };
+        let bob_bid = RevealedBid {
+            vote_option: 2,
+            vp_amount: Uint128::new(6000),
+            price_per_vp: Decimal256::percent(30),
         };
-        let info = message_info(&deps.api.addr_make("creator"), &[]);
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // Simulate a user having staked tokens
-        let staker_addr = deps.api.addr_make("staker");
-        let mut staker = Staker::new();
-        staker.staked_amount = Uint128::new(1000);
-        STAKERS
-            .save(&mut deps.storage, &staker_addr, &staker)
+        for (bidder, commitment, deposit) in [
+            (&alice, bid_commitment(&alice, 5, &alice_bid, "salt"), 3000),
+            (&bob, bid_commitment(&bob, 5, &bob_bid, "pepper"), 2000),
+            (&carol, HexBinary::from([7u8; 32]), 100),
+        ] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                message_info(bidder, &coins(deposit, "uatom")),
+                ExecuteMsg::CommitBid {
+                    proposal_id: 5,
+                    commitment,
+                },
+            )
             .unwrap();
+        }
 
-        let mut state = STATE.load(&deps.storage).unwrap();
-        state.total_staked = Uint128::new(1000);
-        STATE.save(&mut deps.storage, &state).unwrap();
+        let reveal = |bid: &RevealedBid, salt: &str| ExecuteMsg::RevealBid {
+            proposal_id: 5,
+            vote_option: bid.vote_option,
+            vp_amount: bid.vp_amount,
+            price_per_vp: bid.price_per_vp,
+            salt: salt.to_string(),
+        };
 
-        // Deposit rewards
-        let info = message_info(&deps.api.addr_make("depositor"), &coins(100, "uatom"));
-        let msg = ExecuteMsg::DepositRewards {};
-        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        // Bids cannot be revealed during the commit phase
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            reveal(&alice_bid, "salt"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::WrongAuctionPhase { .. }));
 
-        // Check state updated
-        let state = STATE.load(&deps.storage).unwrap();
+        env.block.time = env.block.time.plus_seconds(150);
+
+        // Not once the session is destroyed
+        let mut session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        session.is_active = false;
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            reveal(&alice_bid, "salt"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::WrongAuctionPhase { .. }));
+        session.is_active = true;
+        VOTING_SESSIONS
+            .save(&mut deps.storage, 5, &session)
+            .unwrap();
+
+        // A reveal must match the commitment
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            reveal(&alice_bid, "wrong"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBidReveal { .. }));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            reveal(&alice_bid, "salt"),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&bob, &[]),
+            reveal(&bob_bid, "pepper"),
+        )
+        .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(100);
+        let settle = ExecuteMsg::SettleAuction { proposal_id: 5 };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&carol, &[]),
+            settle.clone(),
+        )
+        .unwrap();
+
+        // Alice wins 6000 VP for 3000, Bob gets the remaining 4000 VP for 1200
+        // and is refunded 800, Carol never revealed and forfeits 100
+        assert_eq!(attribute_value(&res, "allocated_vp"), "10000");
+        assert_eq!(attribute_value(&res, "income"), "4300");
+        assert_eq!(attribute_value(&res, "forfeited"), "100");
+        assert_eq!(res.messages.len(), 3);
         assert_eq!(
-            state.global_reward_index,
-            Decimal256::from_ratio(100u128, 1000u128)
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: bob.to_string(),
+                amount: coins(800, "uatom"),
+            })
         );
 
-        // Claim rewards
-        let info = message_info(&staker_addr, &[]);
-        let msg = ExecuteMsg::ClaimRewards {};
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(
+            state.rental_index,
+            Decimal256::from_ratio(4300u128, 10_000u128)
+        );
+        let session = VOTING_SESSIONS.load(&deps.storage, 5).unwrap();
+        assert_eq!(session.rented_amount, Uint128::new(10_000));
+        assert!(session.auction.unwrap().settled);
 
-        // Check that withdraw message was created
-        assert_eq!(res.messages.len(), 1);
+        let err = execute(deps.as_mut(), env, message_info(&carol, &[]), settle).unwrap_err();
+        assert_eq!(err, ContractError::AuctionAlreadySettled { proposal_id: 5 });
     }
 }
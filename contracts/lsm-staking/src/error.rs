@@ -1,4 +1,4 @@
-use cosmwasm_std::{StdError, Uint128};
+use cosmwasm_std::{Decimal, Decimal256, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -18,18 +18,51 @@ pub enum ContractError {
     #[error("Invalid validator: {validator}, expected: {expected}")]
     InvalidValidator { validator: String, expected: String },
 
+    #[error("Duplicate validator: {validator}")]
+    DuplicateValidator { validator: String },
+
+    #[error("Invalid rebalance: {reason}")]
+    InvalidRebalance { reason: String },
+
+    #[error("Cannot redelegate {required} from {validator}, only {available} can be redelegated")]
+    RedelegationLimit {
+        validator: String,
+        available: Uint128,
+        required: Uint128,
+    },
+
+    #[error(
+        "Validator {validator} has no lockers in the voting session of proposal {proposal_id}"
+    )]
+    ValidatorNotInSession { validator: String, proposal_id: u64 },
+
     #[error("Invalid validator address: {address}")]
     InvalidValidatorAddress { address: String },
 
     #[error("Invalid funds: expected {expected} denom")]
     InvalidFunds { expected: String },
 
+    #[error("Invalid reward denom: {denom}")]
+    InvalidRewardDenom { denom: String },
+
     #[error("Insufficient staked amount")]
     InsufficientStakedAmount {},
 
     #[error("No rewards to claim")]
     NoRewards {},
 
+    #[error("No fees to withdraw")]
+    NoFees {},
+
+    #[error("Fee rate {rate} exceeds the maximum of {max}")]
+    FeeRateTooHigh { rate: Decimal, max: Decimal },
+
+    #[error("Keeper tip {tip} exceeds the maximum of {max}")]
+    KeeperTipTooHigh { tip: Uint128, max: Uint128 },
+
+    #[error("Max rent fraction {fraction} must be greater than zero and at most one")]
+    InvalidRentFraction { fraction: Decimal },
+
     #[error("Amount cannot be zero")]
     ZeroAmount {},
 
@@ -52,14 +85,26 @@ pub enum ContractError {
     #[error("Contract is paused")]
     ContractPaused {},
 
+    #[error("Contract is in emergency mode, unhealthy validators: {validators}")]
+    EmergencyMode { validators: String },
+
+    #[error("Contract is not in emergency mode")]
+    NotInEmergency {},
+
+    #[error("No healthy validator to redelegate to")]
+    NoFallbackValidator {},
+
+    #[error("Cannot both set and clear the fallback validator")]
+    ConflictingFallbackValidator {},
+
     #[error("Voting session already exists for proposal {proposal_id}")]
     VotingSessionExists { proposal_id: u64 },
 
     #[error("Voting session not found for proposal {proposal_id}")]
     VotingSessionNotFound { proposal_id: u64 },
 
-    #[error("Cannot unpause: {active_count} voting sessions still active")]
-    CannotUnpause { active_count: u64 },
+    #[error("Voting session for proposal {proposal_id} is already destroyed")]
+    VotingSessionInactive { proposal_id: u64 },
 
     #[error("Invalid locker: sender {sender} is not registered for proposal {proposal_id} option {vote_option}")]
     InvalidLocker {
@@ -68,6 +113,15 @@ pub enum ContractError {
         vote_option: i32,
     },
 
+    #[error("Proposal {proposal_id} is not in voting period (status: {status})")]
+    ProposalNotInVoting { proposal_id: u64, status: String },
+
+    #[error("Voting period of proposal {proposal_id} has ended")]
+    ProposalVotingEnded { proposal_id: u64 },
+
+    #[error("Proposal {proposal_id} not found")]
+    ProposalNotFound { proposal_id: u64 },
+
     #[error("Proposal {proposal_id} is still active (status: {status})")]
     ProposalStillActive { proposal_id: u64, status: String },
 
@@ -77,9 +131,58 @@ pub enum ContractError {
         required: Uint128,
     },
 
+    #[error("Rent cap of proposal {proposal_id} reached: cap {cap}, rented {rented}, attempting to rent {attempting}")]
+    RentCapReached {
+        proposal_id: u64,
+        cap: Uint128,
+        rented: Uint128,
+        attempting: Uint128,
+    },
+
     #[error("No voting session found for proposal {proposal_id}")]
     NoVotingSession { proposal_id: u64 },
 
     #[error("Locker not found for proposal {proposal_id} and vote option {vote_option}")]
     LockerNotFound { proposal_id: u64, vote_option: i32 },
+
+    #[error("Slippage exceeded: received {vp_amount} voting power at price {price}, bounds are min {min_vp_amount} voting power and max price {max_price}")]
+    RentalSlippageExceeded {
+        vp_amount: Uint128,
+        price: Decimal256,
+        min_vp_amount: Uint128,
+        max_price: Decimal256,
+    },
+
+    #[error("Invalid rental pricing: {reason}")]
+    InvalidPricing { reason: String },
+
+    #[error("Invalid vote options: {reason}")]
+    InvalidVoteOptions { reason: String },
+
+    #[error("Invalid auction: {reason}")]
+    InvalidAuction { reason: String },
+
+    #[error("Voting power for proposal {proposal_id} is allocated by sealed-bid auction")]
+    AuctionMode { proposal_id: u64 },
+
+    #[error("No auction for proposal {proposal_id}")]
+    NoAuction { proposal_id: u64 },
+
+    #[error("Auction for proposal {proposal_id} is not in the {phase} phase")]
+    WrongAuctionPhase { proposal_id: u64, phase: String },
+
+    #[error("Auction for proposal {proposal_id} is already settled")]
+    AuctionAlreadySettled { proposal_id: u64 },
+
+    #[error("Auction for proposal {proposal_id} reached the maximum of {max} bids")]
+    TooManyBids { proposal_id: u64, max: u32 },
+
+    #[error("Bid already committed by {bidder} for proposal {proposal_id}")]
+    BidAlreadyCommitted { proposal_id: u64, bidder: String },
+
+    #[error("No bid committed by {bidder} for proposal {proposal_id}")]
+    BidNotFound { proposal_id: u64, bidder: String },
+
+    #[error("Invalid bid reveal: {reason}")]
+    InvalidBidReveal { reason: String },
 }
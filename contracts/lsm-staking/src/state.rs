@@ -1,38 +1,103 @@
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::{Item, Map};
-use lsm_types::{Config, Staker, State, VotingSession};
+use cosmwasm_std::{Addr, Coin, Decimal256, Empty, Timestamp, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use lsm_types::{AuctionParams, Bid, Config, Rental, RentalTotal, Staker, State, VotingSession};
 use serde::{Deserialize, Serialize};
 
 /// Contract configuration
 pub const CONFIG: Item<Config> = Item::new("config");
 
-/// Global state (total staked, global reward index)
+/// Global state (total staked, reward indices, rental income index)
 pub const STATE: Item<State> = Item::new("state");
 
 /// Map of staker address to their staking info
 pub const STAKERS: Map<&Addr, Staker> = Map::new("stakers");
 
+/// Set of stakers with auto-compounding enabled
+pub const AUTO_COMPOUNDERS: Map<&Addr, Empty> = Map::new("auto_compounders");
+
+/// Last staker compounded by Compound, the next batch starts after it
+pub const COMPOUND_CURSOR: Item<Addr> = Item::new("compound_cursor");
+
 /// Map of proposal_id to VotingSession
 pub const VOTING_SESSIONS: Map<u64, VotingSession> = Map::new("voting_sessions");
 
+/// Map of (proposal_id, bidder) to sealed bid for voting session auctions
+pub const BIDS: Map<(u64, &Addr), Bid> = Map::new("bids");
+
+/// Secondary indexes of the rental ledger
+pub struct RentalIndexes<'a> {
+    pub proposal: MultiIndex<'a, u64, Rental, u64>,
+    pub renter: MultiIndex<'a, Addr, Rental, u64>,
+}
+
+impl IndexList<Rental> for RentalIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Rental>> + '_> {
+        let v: Vec<&dyn Index<Rental>> = vec![&self.proposal, &self.renter];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Rental ledger keyed by rental id, indexed by proposal and by renter
+pub fn rentals<'a>() -> IndexedMap<u64, Rental, RentalIndexes<'a>> {
+    let indexes = RentalIndexes {
+        proposal: MultiIndex::new(
+            |_pk, rental| rental.proposal_id,
+            "rentals",
+            "rentals__proposal",
+        ),
+        renter: MultiIndex::new(
+            |_pk, rental| rental.renter.clone(),
+            "rentals",
+            "rentals__renter",
+        ),
+    };
+    IndexedMap::new("rentals", indexes)
+}
+
+/// Number of rentals recorded, used as the id of the next rental
+pub const RENTAL_COUNT: Item<u64> = Item::new("rental_count");
+
+/// Map of (proposal_id, vote_option) to the aggregate of its rentals
+pub const RENTAL_TOTALS: Map<(u64, i32), RentalTotal> = Map::new("rental_totals");
+
 /// Global pause flag - true when any voting session is active
 pub const IS_PAUSED: Item<bool> = Item::new("is_paused");
 
+/// Validators found jailed by the last health check, the contract is in emergency mode
+/// while this is not empty
+pub const UNHEALTHY_VALIDATORS: Item<Vec<String>> = Item::new("unhealthy_validators");
+
 /// Temporary state for active reward claim
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ActiveClaim {
     /// User who initiated the claim
     pub claimer: Addr,
-    /// Contract balance before claiming rewards
-    pub balance_before: Uint128,
-    /// Global reward index before claiming
-    pub global_index_before: cosmwasm_std::Decimal256,
+    /// Contract balances in the reward denoms before claiming rewards
+    pub balances_before: Vec<Coin>,
     /// If this is part of a withdrawal (Some(amount)) or just a claim (None)
     pub withdraw_amount: Option<Uint128>,
 }
 
 pub const ACTIVE_CLAIM: Item<ActiveClaim> = Item::new("active_claim");
 
+/// Temporary state for an active compounding batch
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActiveCompound {
+    /// Contract balances in the reward denoms before claiming rewards
+    pub balances_before: Vec<Coin>,
+}
+
+pub const ACTIVE_COMPOUND: Item<ActiveCompound> = Item::new("active_compound");
+
+/// Temporary state for an active rebalance
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActiveRebalance {
+    /// Contract balances in the reward denoms before redelegating
+    pub balances_before: Vec<Coin>,
+}
+
+pub const ACTIVE_REBALANCE: Item<ActiveRebalance> = Item::new("active_rebalance");
+
 /// Temporary state for active voting power rental
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ActiveRental {
@@ -40,21 +105,12 @@ pub struct ActiveRental {
     pub proposal_id: u64,
     /// Vote option for the rental
     pub vote_option: i32,
+    /// Validator the voting power is tokenized from
+    pub validator: String,
 }
 
 pub const ACTIVE_RENTAL: Item<ActiveRental> = Item::new("active_rental");
 
-/// Temporary state for active withdrawal
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct ActiveWithdraw {
-    /// User who initiated the withdrawal
-    pub withdrawer: Addr,
-    /// Amount of tokens being withdrawn
-    pub amount: Uint128,
-}
-
-pub const ACTIVE_WITHDRAW: Item<ActiveWithdraw> = Item::new("active_withdraw");
-
 /// Temporary state for active deposit
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ActiveDeposit {
@@ -79,7 +135,33 @@ pub struct ActiveVotingSessionCreation {
     pub created_count: u32,
     /// Map of vote_option to locker address (as we receive replies)
     pub locker_addresses: Vec<(i32, Addr)>,
+    /// Lockers of the validators other than the primary one
+    pub validator_lockers: Vec<(String, i32, Addr)>,
+    /// Auction parameters if the session allocates voting power by sealed-bid auction
+    pub auction: Option<AuctionParams>,
+    /// End of the proposal's voting period
+    pub voting_end_time: Timestamp,
+    /// Vote options of a weighted session, None when each option has its own locker
+    pub weighted_options: Option<Vec<i32>>,
 }
 
 pub const ACTIVE_VOTING_SESSION_CREATION: Item<ActiveVotingSessionCreation> =
     Item::new("active_voting_session_creation");
+
+/// State before the reward index was kept per denom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LegacyState {
+    pub total_staked: Uint128,
+    pub global_reward_index: Decimal256,
+}
+
+pub const LEGACY_STATE: Item<LegacyState> = Item::new("state");
+
+/// Staker before the reward index was kept per denom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LegacyStaker {
+    pub staked_amount: Uint128,
+    pub reward_index: Decimal256,
+}
+
+pub const LEGACY_STAKERS: Map<&Addr, LegacyStaker> = Map::new("stakers");
@@ -1,11 +1,13 @@
 use cosmwasm_std::{
     entry_point, to_json_binary, Binary, Coin, CosmosMsg, Deps, DepsMut, DistributionMsg, Env,
-    MessageInfo, QuerierWrapper, Response, StakingMsg, StdResult, SubMsg, Uint128, WasmMsg,
+    Event, MessageInfo, QuerierWrapper, Response, StdResult, SubMsg, SubMsgResult, Uint128,
+    WasmMsg,
 };
 use cw2::set_contract_version;
+use gov_query::{query_proposal, ProposalStatus};
 use proposal_locker_types::{
-    Config, ConfigResponse, ExecuteMsg, InstantiateMsg, LsmShareInfo, QueryMsg, State,
-    TotalVotingPowerResponse,
+    validate_weights, Config, ConfigResponse, ExecuteMsg, InstantiateMsg, LsmShareInfo, QueryMsg,
+    State, TotalVotingPowerResponse, WeightedVoteOption,
 };
 
 use crate::error::ContractError;
@@ -17,6 +19,8 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 // Reply IDs
 const REPLY_CLAIM_REWARDS: u64 = 1;
 const REPLY_TOKENIZE_SHARES: u64 = 2;
+const REPLY_VOTE: u64 = 3;
+const REPLY_DEPOSIT_REWARDS: u64 = 4;
 
 #[entry_point]
 pub fn instantiate(
@@ -36,14 +40,31 @@ pub fn instantiate(
     // Verify that the validator exists on chain
     verify_validator_exists(&deps.querier, &msg.validator)?;
 
+    // Verify that the staking denom is the bonded denom of the chain
+    let bonded_denom = deps.querier.query_bonded_denom()?;
+    if msg.staking_denom != bonded_denom {
+        return Err(ContractError::InvalidStakingDenom {
+            denom: msg.staking_denom,
+            expected: bonded_denom,
+        });
+    }
+
     // Verify that the proposal is in VOTING_PERIOD before voting
     verify_proposal_in_voting(&deps.querier, msg.proposal_id)?;
 
+    if let Some(weights) = &msg.weights {
+        validate_weights(weights).map_err(|e| ContractError::InvalidWeights {
+            reason: e.to_string(),
+        })?;
+    }
+
     let config = Config {
         proposal_id: msg.proposal_id,
         vote_option: msg.vote_option,
+        weights: msg.weights,
         validator: msg.validator.clone(),
         manager,
+        staking_denom: msg.staking_denom,
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -51,19 +72,18 @@ pub fn instantiate(
 
     // Cast initial vote
     // The vote will be weighted as more LSM shares are deposited
-    let vote_msg = create_vote_msg(
-        env.contract.address.to_string(),
-        msg.proposal_id,
-        msg.vote_option,
-    )?;
+    // A failed vote doesn't abort the instantiation, it is re-cast on the next deposit
+    let vote_msg = create_vote_submsg(&env, &config)?;
 
     Ok(Response::new()
-        .add_message(vote_msg)
+        .add_submessage(vote_msg)
         .add_attribute("method", "instantiate")
         .add_attribute("proposal_id", msg.proposal_id.to_string())
         .add_attribute("vote_option", msg.vote_option.to_string())
+        .add_attribute("weighted", config.weights.is_some().to_string())
         .add_attribute("validator", msg.validator)
-        .add_attribute("manager", config.manager))
+        .add_attribute("manager", config.manager)
+        .add_attribute("staking_denom", config.staking_denom))
 }
 
 #[entry_point]
@@ -75,7 +95,11 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::DepositLsmShares {} => execute_deposit_lsm_shares(deps, env, info),
+        ExecuteMsg::UpdateVote { weights } => execute_update_vote(deps, env, info, weights),
+        ExecuteMsg::ReVote {} => execute_re_vote(deps, env, info),
+        ExecuteMsg::ForwardRewards {} => execute_forward_rewards(deps, env),
         ExecuteMsg::Destroy {} => execute_destroy(deps, env, info),
+        ExecuteMsg::EmergencyDestroy {} => execute_emergency_destroy(deps, env, info),
     }
 }
 
@@ -130,9 +154,16 @@ pub fn execute_deposit_lsm_shares(
         lsm_share.amount,
     )?;
 
-    Ok(Response::new()
-        .add_message(redeem_msg)
+    let mut response = Response::new().add_message(redeem_msg);
+
+    // Re-cast the vote if it was never recorded so the deposited voting power counts
+    if !state.has_voted {
+        response = response.add_submessage(create_vote_submsg(&env, &config)?);
+    }
+
+    Ok(response
         .add_attribute("method", "deposit_lsm_shares")
+        .add_attribute("revote", (!state.has_voted).to_string())
         .add_attribute("manager", config.manager)
         .add_attribute("validator", lsm_info.validator)
         .add_attribute("record_id", lsm_info.record_id)
@@ -140,6 +171,58 @@ pub fn execute_deposit_lsm_shares(
         .add_attribute("total_voting_power", state.total_staked))
 }
 
+/// Replace the split vote of the locker and re-cast it as MsgVoteWeighted
+/// Only callable by manager
+pub fn execute_update_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    weights: Vec<WeightedVoteOption>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Only manager can update the vote
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    validate_weights(&weights).map_err(|e| ContractError::InvalidWeights {
+        reason: e.to_string(),
+    })?;
+
+    config.weights = Some(weights);
+    CONFIG.save(deps.storage, &config)?;
+
+    let vote_msg = create_vote_submsg(&env, &config)?;
+
+    Ok(Response::new()
+        .add_submessage(vote_msg)
+        .add_attribute("method", "update_vote")
+        .add_attribute("proposal_id", config.proposal_id.to_string()))
+}
+
+/// Re-cast the vote of the locker
+/// Only callable by manager
+pub fn execute_re_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // Only manager can re-cast the vote
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let vote_msg = create_vote_submsg(&env, &config)?;
+
+    Ok(Response::new()
+        .add_submessage(vote_msg)
+        .add_attribute("method", "re_vote")
+        .add_attribute("proposal_id", config.proposal_id.to_string()))
+}
+
 /// Destroy the contract after proposal is finished
 /// Claims rewards, tokenizes all delegations, sends everything to manager
 /// Only callable by manager
@@ -156,10 +239,57 @@ pub fn execute_destroy(
         return Err(ContractError::Unauthorized {});
     }
 
-    // TODO: Verify that proposal is finished
-    // This requires querying the gov module to check proposal status
-    // For now, we allow destruction at any time
+    // Verify that proposal has left the voting period
+    verify_proposal_finished(&deps.querier, &env, config.proposal_id)?;
+
+    let submessages = create_destroy_submsgs(&env, &config, &state)?;
 
+    Ok(Response::new()
+        .add_submessages(submessages)
+        .add_attribute("method", "destroy")
+        .add_attribute("manager", config.manager)
+        .add_attribute("total_staked", state.total_staked)
+        .add_attribute("rewards_claimed", "true"))
+}
+
+/// Destroy the contract while the proposal is still ongoing
+/// Gated separately from Destroy: only the manager is checked, the proposal status is not
+pub fn execute_emergency_destroy(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+
+    // Only manager can destroy in an emergency
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // No verify_proposal_finished, the stake leaves while the proposal is still ongoing
+
+    let submessages = create_destroy_submsgs(&env, &config, &state)?;
+
+    Ok(Response::new()
+        .add_submessages(submessages)
+        .add_event(
+            Event::new("emergency_destroy")
+                .add_attribute("proposal_id", config.proposal_id.to_string())
+                .add_attribute("manager", config.manager.clone()),
+        )
+        .add_attribute("method", "emergency_destroy")
+        .add_attribute("manager", config.manager)
+        .add_attribute("total_staked", state.total_staked)
+        .add_attribute("rewards_claimed", "true"))
+}
+
+/// Create the submessages that claim the rewards and tokenize all delegations of the locker
+fn create_destroy_submsgs(
+    env: &Env,
+    config: &Config,
+    state: &State,
+) -> Result<Vec<SubMsg>, ContractError> {
     let mut submessages: Vec<SubMsg> = Vec::new();
 
     // 1. Claim all delegation rewards with reply
@@ -180,18 +310,17 @@ pub fn execute_destroy(
         let tokenize_msg = create_tokenize_shares_msg(
             env.contract.address.to_string(),
             config.validator.clone(),
+            config.staking_denom.clone(),
             state.total_staked,
             env.contract.address.to_string(), // Send to self first
         )?;
-        submessages.push(SubMsg::reply_on_success(tokenize_msg, REPLY_TOKENIZE_SHARES));
+        submessages.push(SubMsg::reply_on_success(
+            tokenize_msg,
+            REPLY_TOKENIZE_SHARES,
+        ));
     }
 
-    Ok(Response::new()
-        .add_submessages(submessages)
-        .add_attribute("method", "destroy")
-        .add_attribute("manager", config.manager)
-        .add_attribute("total_staked", state.total_staked)
-        .add_attribute("rewards_claimed", "true"))
+    Ok(submessages)
 }
 
 #[entry_point]
@@ -209,8 +338,10 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     Ok(ConfigResponse {
         proposal_id: config.proposal_id,
         vote_option: config.vote_option,
+        weights: config.weights,
         validator: config.validator,
         manager: config.manager,
+        staking_denom: config.staking_denom,
         total_staked: state.total_staked,
         has_voted: state.has_voted,
     })
@@ -279,102 +410,66 @@ fn verify_validator_exists(querier: &QuerierWrapper, validator: &str) -> Result<
 
 /// Verify that a proposal is in VOTING_PERIOD (status = 2)
 /// This ensures we can vote on the proposal
-fn verify_proposal_in_voting(querier: &QuerierWrapper, proposal_id: u64) -> Result<(), ContractError> {
-    use cosmwasm_std::QueryRequest;
-    use prost::Message;
-
-    // Proto definition for QueryProposalRequest
-    #[derive(Clone, PartialEq, Message)]
-    struct QueryProposalRequest {
-        #[prost(uint64, tag = "1")]
-        pub proposal_id: u64,
-    }
-
-    // Proto definition for QueryProposalResponse
-    #[derive(Clone, PartialEq, Message)]
-    struct QueryProposalResponse {
-        #[prost(message, optional, tag = "1")]
-        pub proposal: Option<Proposal>,
-    }
+fn verify_proposal_in_voting(
+    querier: &QuerierWrapper,
+    proposal_id: u64,
+) -> Result<(), ContractError> {
+    let proposal = query_proposal(querier, proposal_id)
+        .map_err(|e| ContractError::GovQueryFailed {
+            proposal_id,
+            reason: e.to_string(),
+        })?
+        .ok_or_else(|| ContractError::GovQueryFailed {
+            proposal_id,
+            reason: "proposal not found".to_string(),
+        })?;
 
-    // Proto definition for Proposal (simplified, only fields we need)
-    #[derive(Clone, PartialEq, Message)]
-    struct Proposal {
-        #[prost(uint64, tag = "1")]
-        pub proposal_id: u64,
-        #[prost(int32, tag = "3")]
-        pub status: i32,
-        // We skip other fields we don't need
+    if proposal.status != ProposalStatus::VotingPeriod {
+        return Err(ContractError::ProposalNotInVoting {
+            proposal_id,
+            status: proposal.status.to_string(),
+        });
     }
 
-    // Encode the query request
-    let request = QueryProposalRequest { proposal_id };
-    let mut query_data = Vec::new();
-    request.encode(&mut query_data).map_err(|e| {
-        ContractError::InvalidLsmShares {
-            reason: format!("Failed to encode proposal query: {}", e),
-        }
-    })?;
-
-    // Query the gov module using Stargate
-    let stargate_response: Binary = querier.query(&QueryRequest::Stargate {
-        path: "/cosmos.gov.v1beta1.Query/Proposal".to_string(),
-        data: Binary::from(query_data),
-    }).map_err(|_| ContractError::InvalidLsmShares {
-        reason: format!("Failed to query proposal {}", proposal_id),
-    })?;
-
-    // Decode the response
-    let response = QueryProposalResponse::decode(stargate_response.as_slice()).map_err(|e| {
-        ContractError::InvalidLsmShares {
-            reason: format!("Failed to decode proposal query response: {}", e),
-        }
-    })?;
+    Ok(())
+}
 
-    // Check proposal status
-    if let Some(proposal) = response.proposal {
-        // Status codes:
-        // 0 = UNSPECIFIED
-        // 1 = DEPOSIT_PERIOD
-        // 2 = VOTING_PERIOD
-        // 3 = PASSED
-        // 4 = REJECTED
-        // 5 = FAILED
-        if proposal.status == 2 {
-            // Proposal is in VOTING_PERIOD - OK to vote
-            Ok(())
-        } else {
-            // Proposal is not in voting period
-            Err(ContractError::ProposalNotInVoting {
-                proposal_id,
-                status: match proposal.status {
-                    0 => "UNSPECIFIED".to_string(),
-                    1 => "DEPOSIT_PERIOD".to_string(),
-                    2 => "VOTING_PERIOD".to_string(),
-                    3 => "PASSED".to_string(),
-                    4 => "REJECTED".to_string(),
-                    5 => "FAILED".to_string(),
-                    _ => format!("UNKNOWN({})", proposal.status),
-                },
-            })
+/// Verify that the proposal has left the voting period
+/// The gov module may not have processed the proposal yet when its end time has passed
+/// A proposal that can't be found anymore was purged after it finished
+fn verify_proposal_finished(
+    querier: &QuerierWrapper,
+    env: &Env,
+    proposal_id: u64,
+) -> Result<(), ContractError> {
+    match query_proposal(querier, proposal_id) {
+        Ok(Some(proposal)) => {
+            let voting_ended = proposal.status == ProposalStatus::VotingPeriod
+                && env.block.time >= proposal.voting_end_time;
+            if proposal.status.is_finished() || voting_ended {
+                Ok(())
+            } else {
+                Err(ContractError::ProposalNotFinished { proposal_id })
+            }
         }
-    } else {
-        // Proposal not found
-        Err(ContractError::InvalidLsmShares {
-            reason: format!("Proposal {} not found", proposal_id),
-        })
+        _ => Ok(()),
     }
 }
 
-/// Create MsgVote message for governance
-fn create_vote_msg(
-    voter: String,
-    proposal_id: u64,
-    vote_option: i32,
-) -> Result<CosmosMsg, ContractError> {
+/// Create the vote of the locker as a submessage
+/// The reply records whether the gov module accepted it, without failing the transaction
+fn create_vote_submsg(env: &Env, config: &Config) -> Result<SubMsg, ContractError> {
+    let vote_msg = create_vote_msg(env.contract.address.to_string(), config)?;
+    Ok(SubMsg::reply_always(vote_msg, REPLY_VOTE))
+}
+
+/// Create the gov v1 vote message of the locker
+/// Casts MsgVoteWeighted for a split vote, MsgVote otherwise
+/// The metadata records the voting session the vote belongs to
+fn create_vote_msg(voter: String, config: &Config) -> Result<CosmosMsg, ContractError> {
     use prost::Message;
 
-    // Proto definition for MsgVote
+    // Proto definition for gov v1 MsgVote
     #[derive(Clone, PartialEq, Message)]
     struct MsgVote {
         #[prost(uint64, tag = "1")]
@@ -383,23 +478,76 @@ fn create_vote_msg(
         pub voter: String,
         #[prost(int32, tag = "3")]
         pub option: i32,
+        #[prost(string, tag = "4")]
+        pub metadata: String,
     }
 
-    let msg = MsgVote {
-        proposal_id,
-        voter,
-        option: vote_option,
-    };
+    // Proto definition for gov v1 MsgVoteWeighted
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgVoteWeighted {
+        #[prost(uint64, tag = "1")]
+        pub proposal_id: u64,
+        #[prost(string, tag = "2")]
+        pub voter: String,
+        #[prost(message, repeated, tag = "3")]
+        pub options: Vec<ProtoWeightedVoteOption>,
+        #[prost(string, tag = "4")]
+        pub metadata: String,
+    }
+
+    // Proto definition for gov v1 WeightedVoteOption
+    #[derive(Clone, PartialEq, Message)]
+    struct ProtoWeightedVoteOption {
+        #[prost(int32, tag = "1")]
+        pub option: i32,
+        #[prost(string, tag = "2")]
+        pub weight: String,
+    }
+
+    let metadata = format!(
+        "lsm-staking voting session for proposal {} managed by {}",
+        config.proposal_id, config.manager
+    );
 
     // Encode the message
     let mut buf = Vec::new();
-    msg.encode(&mut buf)
-        .map_err(|e| ContractError::InvalidLsmShares {
-            reason: format!("Failed to encode MsgVote: {}", e),
-        })?;
+    let type_url = match &config.weights {
+        Some(weights) => {
+            let msg = MsgVoteWeighted {
+                proposal_id: config.proposal_id,
+                voter,
+                options: weights
+                    .iter()
+                    .map(|weighted| ProtoWeightedVoteOption {
+                        option: weighted.option,
+                        weight: weighted.weight.to_string(),
+                    })
+                    .collect(),
+                metadata,
+            };
+            msg.encode(&mut buf)
+                .map_err(|e| ContractError::InvalidLsmShares {
+                    reason: format!("Failed to encode MsgVoteWeighted: {}", e),
+                })?;
+            "/cosmos.gov.v1.MsgVoteWeighted"
+        }
+        None => {
+            let msg = MsgVote {
+                proposal_id: config.proposal_id,
+                voter,
+                option: config.vote_option,
+                metadata,
+            };
+            msg.encode(&mut buf)
+                .map_err(|e| ContractError::InvalidLsmShares {
+                    reason: format!("Failed to encode MsgVote: {}", e),
+                })?;
+            "/cosmos.gov.v1.MsgVote"
+        }
+    };
 
     Ok(CosmosMsg::Any(cosmwasm_std::AnyMsg {
-        type_url: "/cosmos.gov.v1beta1.MsgVote".to_string(),
+        type_url: type_url.to_string(),
         value: Binary::from(buf),
     }))
 }
@@ -454,6 +602,7 @@ fn create_redeem_tokens_msg(
 fn create_tokenize_shares_msg(
     delegator_address: String,
     validator_address: String,
+    denom: String,
     amount: Uint128,
     tokenized_share_owner: String,
 ) -> Result<CosmosMsg, ContractError> {
@@ -484,7 +633,7 @@ fn create_tokenize_shares_msg(
         delegator_address,
         validator_address,
         amount: ProtoCoin {
-            denom: "uatom".to_string(), // TODO: make configurable
+            denom,
             amount: amount.to_string(),
         },
         tokenized_share_owner,
@@ -508,52 +657,122 @@ pub fn reply(deps: DepsMut, env: Env, msg: cosmwasm_std::Reply) -> Result<Respon
     match msg.id {
         REPLY_CLAIM_REWARDS => reply_claim_rewards(deps, env),
         REPLY_TOKENIZE_SHARES => reply_tokenize_shares(deps, env),
+        REPLY_VOTE => reply_vote(deps, msg),
+        REPLY_DEPOSIT_REWARDS => reply_deposit_rewards(msg),
         _ => Err(ContractError::InvalidLsmShares {
             reason: format!("Unknown reply ID: {}", msg.id),
         }),
     }
 }
 
+/// Reply handler after casting a vote
+/// Marks the vote as recorded on success, a failed vote leaves the previous state untouched
+fn reply_vote(deps: DepsMut, msg: cosmwasm_std::Reply) -> Result<Response, ContractError> {
+    match msg.result {
+        SubMsgResult::Ok(_) => {
+            let mut state = STATE.load(deps.storage)?;
+            state.has_voted = true;
+            STATE.save(deps.storage, &state)?;
+
+            Ok(Response::new()
+                .add_attribute("action", "vote_reply")
+                .add_attribute("vote_recorded", "true"))
+        }
+        SubMsgResult::Err(err) => Ok(Response::new()
+            .add_attribute("action", "vote_reply")
+            .add_attribute("vote_recorded", "false")
+            .add_attribute("vote_error", err)),
+    }
+}
+
 /// Reply handler after claiming rewards
-/// Deposits the rewards to the manager contract
+/// Deposits the rewards in every reward denom of the manager to the manager contract
 fn reply_claim_rewards(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    forward_rewards(deps.as_ref(), &env, "claim_rewards_reply")
+}
+
+/// Forward the non-LSM balances of the locker to the manager as rewards
+/// Balances the manager rejects stay in the locker until it accepts them
+/// Callable by anyone, the rewards can only go to the manager
+pub fn execute_forward_rewards(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    forward_rewards(deps.as_ref(), &env, "forward_rewards")
+}
+
+/// Call DepositRewards on the manager with each non-LSM balance of the locker
+/// Each denom is deposited on its own, so a denom the manager doesn't accept is rejected
+/// without reverting the other deposits or the destroy flow
+#[allow(deprecated)]
+fn forward_rewards(deps: Deps, env: &Env, action: &str) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    // Query balance to see how much rewards we received
-    let balance = deps.querier.query_balance(env.contract.address, "uatom")?; // TODO: make denom configurable
+    let rewards: Vec<Coin> = deps
+        .querier
+        .query_all_balances(env.contract.address.clone())?
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero() && parse_lsm_denom(&coin.denom).is_err())
+        .collect();
 
-    if balance.amount.is_zero() {
+    if rewards.is_empty() {
         return Ok(Response::new()
-            .add_attribute("action", "claim_rewards_reply")
+            .add_attribute("action", action)
             .add_attribute("rewards", "0"));
     }
 
     // Call DepositRewards on the manager with the rewards
     use lsm_types::ExecuteMsg as ManagerExecuteMsg;
 
-    let deposit_msg = WasmMsg::Execute {
-        contract_addr: config.manager.to_string(),
-        msg: to_json_binary(&ManagerExecuteMsg::DepositRewards {})?,
-        funds: vec![balance.clone()],
+    let rewards_attribute = rewards
+        .iter()
+        .map(|coin| coin.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut submessages = Vec::with_capacity(rewards.len());
+    for reward in rewards {
+        let payload = to_json_binary(&reward.denom)?;
+        let deposit_msg = WasmMsg::Execute {
+            contract_addr: config.manager.to_string(),
+            msg: to_json_binary(&ManagerExecuteMsg::DepositRewards {})?,
+            funds: vec![reward],
+        };
+        submessages
+            .push(SubMsg::reply_on_error(deposit_msg, REPLY_DEPOSIT_REWARDS).with_payload(payload));
+    }
+
+    Ok(Response::new()
+        .add_submessages(submessages)
+        .add_attribute("action", action)
+        .add_attribute("rewards", rewards_attribute))
+}
+
+/// Reply handler after the manager rejected a reward deposit
+/// The balance stays in the locker and can be forwarded again with ForwardRewards
+fn reply_deposit_rewards(msg: cosmwasm_std::Reply) -> Result<Response, ContractError> {
+    let denom: String = cosmwasm_std::from_json(&msg.payload)?;
+    let error = match msg.result {
+        SubMsgResult::Err(err) => err,
+        SubMsgResult::Ok(_) => String::new(),
     };
 
     Ok(Response::new()
-        .add_message(deposit_msg)
-        .add_attribute("action", "claim_rewards_reply")
-        .add_attribute("rewards", balance.amount))
+        .add_attribute("action", "deposit_rewards_reply")
+        .add_attribute("rejected_denom", denom)
+        .add_attribute("error", error))
 }
 
 /// Reply handler after tokenizing shares
 /// Sends the LSM shares back to the manager via ReturnLsmShares
+#[allow(deprecated)]
 fn reply_tokenize_shares(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
     // Query all token balances to find the LSM share
     // LSM shares have format: {validator}/{record_id}
     use cosmwasm_std::{AllBalanceResponse, BankQuery, QueryRequest};
-    let all_balances_response: AllBalanceResponse = deps.querier.query(&QueryRequest::Bank(BankQuery::AllBalances {
-        address: env.contract.address.to_string(),
-    }))?;
+    let all_balances_response: AllBalanceResponse =
+        deps.querier
+            .query(&QueryRequest::Bank(BankQuery::AllBalances {
+                address: env.contract.address.to_string(),
+            }))?;
     let all_balances = all_balances_response.amount;
 
     // Find the LSM share token for our specific validator
@@ -587,3 +806,453 @@ fn reply_tokenize_shares(deps: DepsMut, env: Env) -> Result<Response, ContractEr
         .add_attribute("lsm_denom", &lsm_share.denom)
         .add_attribute("amount", lsm_share.amount))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{message_info, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{Decimal, OwnedDeps, Validator};
+    use prost::Message;
+
+    type LockerMockDeps = OwnedDeps<MockStorage, MockApi, GovMockQuerier>;
+
+    /// MockQuerier that also answers the gov proposal query of the locker
+    struct GovMockQuerier {
+        base: MockQuerier,
+        /// Map of proposal_id to status
+        proposals: std::collections::HashMap<u64, i32>,
+    }
+
+    impl cosmwasm_std::Querier for GovMockQuerier {
+        #[allow(deprecated)]
+        fn raw_query(&self, bin_request: &[u8]) -> cosmwasm_std::QuerierResult {
+            use cosmwasm_std::{ContractResult, QueryRequest, SystemResult};
+            use gov_query::proto;
+
+            let request: QueryRequest = cosmwasm_std::from_json(bin_request).unwrap();
+            match request {
+                QueryRequest::Stargate { path, data }
+                    if path == "/cosmos.gov.v1.Query/Proposal" =>
+                {
+                    let proposal_id = proto::QueryProposalRequest::decode(data.as_slice())
+                        .unwrap()
+                        .proposal_id;
+                    let proposal = self
+                        .proposals
+                        .get(&proposal_id)
+                        .map(|status| proto::Proposal {
+                            id: proposal_id,
+                            status: *status,
+                            voting_start_time: None,
+                            voting_end_time: Some(proto::Timestamp {
+                                seconds: mock_env().block.time.plus_days(7).seconds() as i64,
+                                nanos: 0,
+                            }),
+                        });
+                    // The contract reads the response as a Binary
+                    let response =
+                        Binary::from(proto::QueryProposalResponse { proposal }.encode_to_vec());
+                    SystemResult::Ok(ContractResult::Ok(to_json_binary(&response).unwrap()))
+                }
+                _ => self.base.raw_query(bin_request),
+            }
+        }
+    }
+
+    /// Proto definition of gov v1 MsgVoteWeighted, to decode the vote of the locker
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgVoteWeighted {
+        #[prost(uint64, tag = "1")]
+        pub proposal_id: u64,
+        #[prost(string, tag = "2")]
+        pub voter: String,
+        #[prost(message, repeated, tag = "3")]
+        pub options: Vec<ProtoWeightedVoteOption>,
+        #[prost(string, tag = "4")]
+        pub metadata: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ProtoWeightedVoteOption {
+        #[prost(int32, tag = "1")]
+        pub option: i32,
+        #[prost(string, tag = "2")]
+        pub weight: String,
+    }
+
+    /// Mock dependencies with proposal 1 in voting period for another week and a bonded validator
+    /// Addresses use the valoper prefix, LSM denoms must start with one
+    fn mock_locker_dependencies() -> LockerMockDeps {
+        let api = MockApi::default().with_prefix("cosmosvaloper");
+        let mut base = MockQuerier::default();
+        base.staking.update(
+            "uatom",
+            &[Validator::create(
+                api.addr_make("validator").to_string(),
+                Decimal::percent(5),
+                Decimal::percent(10),
+                Decimal::percent(1),
+            )],
+            &[],
+        );
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api,
+            querier: GovMockQuerier {
+                base,
+                proposals: [(1, 2)].into(),
+            },
+            custom_query_type: std::marker::PhantomData,
+        }
+    }
+
+    fn instantiate_locker(
+        deps: &mut LockerMockDeps,
+        weights: Option<Vec<WeightedVoteOption>>,
+    ) -> Result<Response, ContractError> {
+        let msg = InstantiateMsg {
+            proposal_id: 1,
+            vote_option: 1,
+            validator: deps.api.addr_make("validator").to_string(),
+            manager: deps.api.addr_make("manager").to_string(),
+            staking_denom: "uatom".to_string(),
+            weights,
+        };
+        let info = message_info(&deps.api.addr_make("manager"), &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    fn weighted(option: i32, percent: u64) -> WeightedVoteOption {
+        WeightedVoteOption {
+            option,
+            weight: Decimal::percent(percent),
+        }
+    }
+
+    /// Decode the split vote cast by a vote submessage
+    fn decode_vote_weighted(submsg: &SubMsg) -> MsgVoteWeighted {
+        assert_eq!(submsg.id, REPLY_VOTE);
+        let CosmosMsg::Any(msg) = &submsg.msg else {
+            panic!("expected a vote message");
+        };
+        assert_eq!(msg.type_url, "/cosmos.gov.v1.MsgVoteWeighted");
+        MsgVoteWeighted::decode(msg.value.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_instantiate_weighted() {
+        let mut deps = mock_locker_dependencies();
+
+        let err = instantiate_locker(&mut deps, Some(vec![weighted(1, 50)])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidWeights { .. }));
+
+        let weights = vec![weighted(1, 70), weighted(3, 30)];
+        let res = instantiate_locker(&mut deps, Some(weights.clone())).unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "weighted" && attr.value == "true"));
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().weights, Some(weights));
+
+        // The initial vote is cast as MsgVoteWeighted
+        assert_eq!(res.messages.len(), 1);
+        let vote = decode_vote_weighted(&res.messages[0]);
+        assert_eq!(
+            vote,
+            MsgVoteWeighted {
+                proposal_id: 1,
+                voter: mock_env().contract.address.to_string(),
+                options: vec![
+                    ProtoWeightedVoteOption {
+                        option: 1,
+                        weight: "0.7".to_string(),
+                    },
+                    ProtoWeightedVoteOption {
+                        option: 3,
+                        weight: "0.3".to_string(),
+                    },
+                ],
+                metadata: format!(
+                    "lsm-staking voting session for proposal 1 managed by {}",
+                    deps.api.addr_make("manager")
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn test_update_vote() {
+        let mut deps = mock_locker_dependencies();
+        instantiate_locker(&mut deps, Some(vec![weighted(1, 100)])).unwrap();
+        let update = |weights| ExecuteMsg::UpdateVote { weights };
+
+        let info = message_info(&deps.api.addr_make("anyone"), &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            update(vec![weighted(1, 50), weighted(2, 50)]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let manager = message_info(&deps.api.addr_make("manager"), &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            manager.clone(),
+            update(vec![weighted(1, 50), weighted(1, 50)]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidWeights { .. }));
+
+        // The new split replaces the previous one and is re-cast
+        let weights = vec![weighted(1, 50), weighted(2, 50)];
+        let res = execute(deps.as_mut(), mock_env(), manager, update(weights.clone())).unwrap();
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().weights, Some(weights));
+        assert_eq!(res.messages.len(), 1);
+        let vote = decode_vote_weighted(&res.messages[0]);
+        assert_eq!(
+            vote.options
+                .iter()
+                .map(|option| (option.option, option.weight.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "0.5"), (2, "0.5")]
+        );
+    }
+
+    /// Reply to the vote submessage, an error when the gov module rejected the vote
+    fn vote_reply(result: Result<(), &str>) -> cosmwasm_std::Reply {
+        #[allow(deprecated)]
+        let result = match result {
+            Ok(()) => SubMsgResult::Ok(cosmwasm_std::SubMsgResponse {
+                events: vec![],
+                data: None,
+                msg_responses: vec![],
+            }),
+            Err(err) => SubMsgResult::Err(err.to_string()),
+        };
+        cosmwasm_std::Reply {
+            id: REPLY_VOTE,
+            payload: Binary::default(),
+            gas_used: 0,
+            result,
+        }
+    }
+
+    fn attribute_value(res: &Response, key: &str) -> String {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reply_vote() {
+        let mut deps = mock_locker_dependencies();
+        instantiate_locker(&mut deps, None).unwrap();
+
+        // A rejected vote is reported without failing and leaves the vote unrecorded
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            vote_reply(Err("inactive proposal")),
+        )
+        .unwrap();
+        assert_eq!(attribute_value(&res, "vote_recorded"), "false");
+        assert_eq!(attribute_value(&res, "vote_error"), "inactive proposal");
+        assert!(!STATE.load(&deps.storage).unwrap().has_voted);
+
+        let res = reply(deps.as_mut(), mock_env(), vote_reply(Ok(()))).unwrap();
+        assert_eq!(attribute_value(&res, "vote_recorded"), "true");
+        assert!(STATE.load(&deps.storage).unwrap().has_voted);
+    }
+
+    #[test]
+    fn test_re_vote() {
+        let mut deps = mock_locker_dependencies();
+        instantiate_locker(&mut deps, None).unwrap();
+
+        let info = message_info(&deps.api.addr_make("anyone"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::ReVote {}).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // The vote is re-cast as a single option MsgVote whose reply is always handled
+        let info = message_info(&deps.api.addr_make("manager"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::ReVote {}).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, REPLY_VOTE);
+        assert_eq!(res.messages[0].reply_on, cosmwasm_std::ReplyOn::Always);
+        let CosmosMsg::Any(msg) = &res.messages[0].msg else {
+            panic!("expected a vote message");
+        };
+        assert_eq!(msg.type_url, "/cosmos.gov.v1.MsgVote");
+    }
+
+    #[test]
+    fn test_deposit_re_votes_until_recorded() {
+        let mut deps = mock_locker_dependencies();
+        instantiate_locker(&mut deps, None).unwrap();
+        let lsm_denom = format!("{}/1", deps.api.addr_make("validator"));
+        let deposit = |deps: &mut LockerMockDeps| {
+            let info = message_info(
+                &deps.api.addr_make("manager"),
+                &cosmwasm_std::coins(100, &lsm_denom),
+            );
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::DepositLsmShares {},
+            )
+            .unwrap()
+        };
+
+        // The initial vote was not recorded, the deposit re-casts it
+        let res = deposit(&mut deps);
+        assert_eq!(attribute_value(&res, "revote"), "true");
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.messages[1].id, REPLY_VOTE);
+
+        // The re-cast vote is rejected again, the next deposit retries
+        reply(deps.as_mut(), mock_env(), vote_reply(Err("out of gas"))).unwrap();
+        let res = deposit(&mut deps);
+        assert_eq!(attribute_value(&res, "revote"), "true");
+        assert_eq!(res.messages.len(), 2);
+
+        // Once recorded, deposits only redeem the shares
+        reply(deps.as_mut(), mock_env(), vote_reply(Ok(()))).unwrap();
+        let res = deposit(&mut deps);
+        assert_eq!(attribute_value(&res, "revote"), "false");
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(attribute_value(&res, "total_voting_power"), "300");
+    }
+
+    #[test]
+    fn test_destroy_authorization() {
+        let mut deps = mock_locker_dependencies();
+        instantiate_locker(&mut deps, None).unwrap();
+        let manager = message_info(&deps.api.addr_make("manager"), &[]);
+        let anyone = message_info(&deps.api.addr_make("anyone"), &[]);
+
+        // Both are reserved to the manager
+        for msg in [ExecuteMsg::Destroy {}, ExecuteMsg::EmergencyDestroy {}] {
+            let err = execute(deps.as_mut(), mock_env(), anyone.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+        }
+
+        // Destroy waits for the proposal to finish
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            manager.clone(),
+            ExecuteMsg::Destroy {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ProposalNotFinished { proposal_id: 1 });
+
+        // EmergencyDestroy skips the status check and emits its own event
+        let emergency = execute(
+            deps.as_mut(),
+            mock_env(),
+            manager.clone(),
+            ExecuteMsg::EmergencyDestroy {},
+        )
+        .unwrap();
+        assert_eq!(emergency.events[0].ty, "emergency_destroy");
+        assert_eq!(attribute_value(&emergency, "method"), "emergency_destroy");
+
+        // Once the proposal passed the manager destroys the locker the same way
+        deps.querier.proposals.insert(1, 3);
+        let res = execute(deps.as_mut(), mock_env(), manager, ExecuteMsg::Destroy {}).unwrap();
+        assert_eq!(res.messages, emergency.messages);
+        assert!(res.events.is_empty());
+
+        // The manager gate doesn't depend on the proposal status
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            anyone,
+            ExecuteMsg::EmergencyDestroy {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn test_forward_rewards() {
+        let mut deps = mock_locker_dependencies();
+        instantiate_locker(&mut deps, None).unwrap();
+        let manager = deps.api.addr_make("manager");
+        let lsm_denom = format!("{}/3", deps.api.addr_make("validator"));
+        deps.querier.base.bank.update_balance(
+            mock_env().contract.address,
+            vec![
+                cosmwasm_std::coin(50, "uatom"),
+                cosmwasm_std::coin(5, "uairdrop"),
+                cosmwasm_std::coin(7, &lsm_denom),
+            ],
+        );
+        let deposit = |denom: &str, amount| {
+            SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: manager.to_string(),
+                    msg: to_json_binary(&lsm_types::ExecuteMsg::DepositRewards {}).unwrap(),
+                    funds: vec![cosmwasm_std::coin(amount, denom)],
+                },
+                REPLY_DEPOSIT_REWARDS,
+            )
+            .with_payload(to_json_binary(denom).unwrap())
+        };
+
+        // Every non-LSM balance is deposited on its own
+        let claim_reply = cosmwasm_std::Reply {
+            id: REPLY_CLAIM_REWARDS,
+            ..vote_reply(Ok(()))
+        };
+        let res = reply(deps.as_mut(), mock_env(), claim_reply).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![deposit("uatom", 50), deposit("uairdrop", 5)]
+        );
+
+        // A denom the manager rejects stays in the locker without reverting the rest
+        let rejected = cosmwasm_std::Reply {
+            id: REPLY_DEPOSIT_REWARDS,
+            payload: to_json_binary("uairdrop").unwrap(),
+            ..vote_reply(Err("Invalid reward denom: uairdrop"))
+        };
+        let res = reply(deps.as_mut(), mock_env(), rejected).unwrap();
+        assert_eq!(attribute_value(&res, "rejected_denom"), "uairdrop");
+
+        // Anyone can forward it again
+        deps.querier.base.bank.update_balance(
+            mock_env().contract.address,
+            vec![cosmwasm_std::coin(5, "uairdrop")],
+        );
+        let info = message_info(&deps.api.addr_make("anyone"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::ForwardRewards {},
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![deposit("uairdrop", 5)]);
+
+        // Nothing to forward
+        deps.querier
+            .base
+            .bank
+            .update_balance(mock_env().contract.address, vec![]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ForwardRewards {},
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+    }
+}
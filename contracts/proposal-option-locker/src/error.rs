@@ -18,6 +18,9 @@ pub enum ContractError {
     #[error("Invalid validator: {validator}, expected: {expected}")]
     InvalidValidator { validator: String, expected: String },
 
+    #[error("Invalid staking denom: {denom}, expected: {expected}")]
+    InvalidStakingDenom { denom: String, expected: String },
+
     #[error("Amount cannot be zero")]
     ZeroAmount {},
 
@@ -29,4 +32,10 @@ pub enum ContractError {
 
     #[error("Proposal {proposal_id} is not in voting period (status: {status})")]
     ProposalNotInVoting { proposal_id: u64, status: String },
+
+    #[error("Failed to query proposal {proposal_id}: {reason}")]
+    GovQueryFailed { proposal_id: u64, reason: String },
+
+    #[error("Invalid vote weights: {reason}")]
+    InvalidWeights { reason: String },
 }
@@ -0,0 +1,392 @@
+use std::fmt;
+
+use cosmwasm_std::{Binary, QuerierWrapper, QueryRequest, StdError, StdResult, Timestamp};
+use prost::Message;
+
+/// Standard vote options: 1=Yes, 2=Abstain, 3=No, 4=NoWithVeto
+pub const STANDARD_VOTE_OPTIONS: [i32; 4] = [1, 2, 3, 4];
+
+const PROPOSAL_V1_PATH: &str = "/cosmos.gov.v1.Query/Proposal";
+const PROPOSAL_V1BETA1_PATH: &str = "/cosmos.gov.v1beta1.Query/Proposal";
+const PROPOSAL_VOTE_OPTIONS_PATH: &str = "/cosmos.gov.v1.Query/ProposalVoteOptions";
+
+/// Proto definitions of the gov queries (simplified, only fields we need)
+pub mod proto {
+    use prost::Message;
+
+    /// QueryProposalRequest, identical in gov v1 and v1beta1
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QueryProposalRequest {
+        #[prost(uint64, tag = "1")]
+        pub proposal_id: u64,
+    }
+
+    /// QueryProposalResponse, identical in gov v1 and v1beta1
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QueryProposalResponse {
+        #[prost(message, optional, tag = "1")]
+        pub proposal: Option<Proposal>,
+    }
+
+    /// Proposal, the fields we need have the same tags in gov v1 and v1beta1
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Proposal {
+        #[prost(uint64, tag = "1")]
+        pub id: u64,
+        #[prost(int32, tag = "3")]
+        pub status: i32,
+        #[prost(message, optional, tag = "8")]
+        pub voting_start_time: Option<Timestamp>,
+        #[prost(message, optional, tag = "9")]
+        pub voting_end_time: Option<Timestamp>,
+    }
+
+    /// google.protobuf.Timestamp
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Timestamp {
+        #[prost(int64, tag = "1")]
+        pub seconds: i64,
+        #[prost(int32, tag = "2")]
+        pub nanos: i32,
+    }
+
+    /// QueryProposalVoteOptionsRequest (gov v1)
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QueryProposalVoteOptionsRequest {
+        #[prost(uint64, tag = "1")]
+        pub proposal_id: u64,
+    }
+
+    /// QueryProposalVoteOptionsResponse (gov v1)
+    #[derive(Clone, PartialEq, Message)]
+    pub struct QueryProposalVoteOptionsResponse {
+        #[prost(message, optional, tag = "1")]
+        pub vote_options: Option<ProposalVoteOptions>,
+    }
+
+    /// ProposalVoteOptions (gov v1)
+    /// Each field holds the text of the option, empty if the option is not available
+    /// The spam option (tag 5) is not a real choice and is skipped
+    #[derive(Clone, PartialEq, Message)]
+    pub struct ProposalVoteOptions {
+        #[prost(string, tag = "1")]
+        pub option_one: String,
+        #[prost(string, tag = "2")]
+        pub option_two: String,
+        #[prost(string, tag = "3")]
+        pub option_three: String,
+        #[prost(string, tag = "4")]
+        pub option_four: String,
+    }
+}
+
+/// Status of a governance proposal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposalStatus {
+    Unspecified,
+    DepositPeriod,
+    VotingPeriod,
+    Passed,
+    Rejected,
+    Failed,
+    Unknown(i32),
+}
+
+impl ProposalStatus {
+    pub fn from_i32(status: i32) -> Self {
+        match status {
+            0 => ProposalStatus::Unspecified,
+            1 => ProposalStatus::DepositPeriod,
+            2 => ProposalStatus::VotingPeriod,
+            3 => ProposalStatus::Passed,
+            4 => ProposalStatus::Rejected,
+            5 => ProposalStatus::Failed,
+            _ => ProposalStatus::Unknown(status),
+        }
+    }
+
+    /// Whether the proposal is done (PASSED, REJECTED, or FAILED)
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            ProposalStatus::Passed | ProposalStatus::Rejected | ProposalStatus::Failed
+        )
+    }
+}
+
+impl fmt::Display for ProposalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProposalStatus::Unspecified => write!(f, "UNSPECIFIED"),
+            ProposalStatus::DepositPeriod => write!(f, "DEPOSIT_PERIOD"),
+            ProposalStatus::VotingPeriod => write!(f, "VOTING_PERIOD"),
+            ProposalStatus::Passed => write!(f, "PASSED"),
+            ProposalStatus::Rejected => write!(f, "REJECTED"),
+            ProposalStatus::Failed => write!(f, "FAILED"),
+            ProposalStatus::Unknown(status) => write!(f, "UNKNOWN({})", status),
+        }
+    }
+}
+
+/// Governance proposal as seen by the contracts
+#[derive(Clone, Debug, PartialEq)]
+pub struct GovProposal {
+    pub id: u64,
+    pub status: ProposalStatus,
+    /// Start of the voting period, zero while in deposit period
+    pub voting_start_time: Timestamp,
+    /// End of the voting period, zero while in deposit period
+    pub voting_end_time: Timestamp,
+}
+
+/// Query a governance proposal
+/// Uses gov v1 and falls back to v1beta1 on chains that don't expose it
+/// Returns an error if neither query succeeds, which is how the gov module reports a
+/// proposal that doesn't exist, and None if the response holds no proposal
+pub fn query_proposal(
+    querier: &QuerierWrapper,
+    proposal_id: u64,
+) -> StdResult<Option<GovProposal>> {
+    let request = proto::QueryProposalRequest { proposal_id }.encode_to_vec();
+
+    let response = stargate_query(querier, PROPOSAL_V1_PATH, request.clone())
+        .or_else(|_| stargate_query(querier, PROPOSAL_V1BETA1_PATH, request))?;
+
+    let Some(proposal) = proto::QueryProposalResponse::decode(response.as_slice())
+        .map_err(|e| {
+            StdError::parse_err(
+                "QueryProposalResponse",
+                format!("Failed to decode proposal query response: {}", e),
+            )
+        })?
+        .proposal
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(GovProposal {
+        id: proposal.id,
+        status: ProposalStatus::from_i32(proposal.status),
+        voting_start_time: to_timestamp(proposal.voting_start_time),
+        voting_end_time: to_timestamp(proposal.voting_end_time),
+    }))
+}
+
+/// Query the vote options of a governance proposal
+/// Uses the gov v1 ProposalVoteOptions query, which also covers multiple-choice proposals
+/// Falls back to the standard options on chains that don't support it
+pub fn query_vote_options(querier: &QuerierWrapper, proposal_id: u64) -> StdResult<Vec<i32>> {
+    let request = proto::QueryProposalVoteOptionsRequest { proposal_id }.encode_to_vec();
+
+    // The query is not available on this chain, use the standard options
+    let Ok(response) = stargate_query(querier, PROPOSAL_VOTE_OPTIONS_PATH, request) else {
+        return Ok(STANDARD_VOTE_OPTIONS.to_vec());
+    };
+
+    let options = proto::QueryProposalVoteOptionsResponse::decode(response.as_slice())
+        .map_err(|e| {
+            StdError::parse_err(
+                "QueryProposalVoteOptionsResponse",
+                format!("Failed to decode proposal vote options response: {}", e),
+            )
+        })?
+        .vote_options
+        .map(|options| {
+            [
+                options.option_one,
+                options.option_two,
+                options.option_three,
+                options.option_four,
+            ]
+            .iter()
+            .zip(STANDARD_VOTE_OPTIONS)
+            .filter(|(text, _)| !text.is_empty())
+            .map(|(_, option)| option)
+            .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if options.is_empty() {
+        return Ok(STANDARD_VOTE_OPTIONS.to_vec());
+    }
+
+    Ok(options)
+}
+
+#[allow(deprecated)]
+fn stargate_query(querier: &QuerierWrapper, path: &str, data: Vec<u8>) -> StdResult<Binary> {
+    querier.query(&QueryRequest::Stargate {
+        path: path.to_string(),
+        data: Binary::from(data),
+    })
+}
+
+fn to_timestamp(timestamp: Option<proto::Timestamp>) -> Timestamp {
+    timestamp
+        .map(|ts| Timestamp::from_seconds(ts.seconds as u64).plus_nanos(ts.nanos as u64))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{
+        to_json_binary, ContractResult, Empty, Querier, QuerierResult, SystemError, SystemResult,
+    };
+
+    /// Querier of a chain that only exposes gov v1beta1
+    struct V1beta1Querier;
+
+    impl Querier for V1beta1Querier {
+        #[allow(deprecated)]
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = cosmwasm_std::from_json(bin_request).unwrap();
+            match request {
+                QueryRequest::Stargate { path, data } if path == PROPOSAL_V1BETA1_PATH => {
+                    let proposal_id = proto::QueryProposalRequest::decode(data.as_slice())
+                        .unwrap()
+                        .proposal_id;
+                    let proposal = (proposal_id == 1).then_some(proto::Proposal {
+                        id: 1,
+                        status: 2,
+                        voting_start_time: Some(proto::Timestamp {
+                            seconds: 100,
+                            nanos: 0,
+                        }),
+                        voting_end_time: Some(proto::Timestamp {
+                            seconds: 200,
+                            nanos: 5,
+                        }),
+                    });
+                    let response =
+                        Binary::from(proto::QueryProposalResponse { proposal }.encode_to_vec());
+                    SystemResult::Ok(ContractResult::Ok(to_json_binary(&response).unwrap()))
+                }
+                _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: "Stargate".to_string(),
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_proposal_falls_back_to_v1beta1() {
+        let querier = V1beta1Querier;
+        let querier = QuerierWrapper::<Empty>::new(&querier);
+
+        let proposal = query_proposal(&querier, 1).unwrap().unwrap();
+        assert_eq!(
+            proposal,
+            GovProposal {
+                id: 1,
+                status: ProposalStatus::VotingPeriod,
+                voting_start_time: Timestamp::from_seconds(100),
+                voting_end_time: Timestamp::from_seconds(200).plus_nanos(5),
+            }
+        );
+        assert_eq!(
+            query_vote_options(&querier, 1).unwrap(),
+            STANDARD_VOTE_OPTIONS.to_vec()
+        );
+
+        assert_eq!(query_proposal(&querier, 2).unwrap(), None);
+    }
+
+    /// Querier of a chain exposing gov v1, with the text of each vote option
+    struct V1Querier {
+        vote_options: Option<proto::ProposalVoteOptions>,
+    }
+
+    impl Querier for V1Querier {
+        #[allow(deprecated)]
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = cosmwasm_std::from_json(bin_request).unwrap();
+            let response = match request {
+                QueryRequest::Stargate { path, .. } if path == PROPOSAL_V1_PATH => {
+                    let proposal = Some(proto::Proposal {
+                        id: 1,
+                        status: 3,
+                        voting_start_time: Some(proto::Timestamp {
+                            seconds: 100,
+                            nanos: 0,
+                        }),
+                        voting_end_time: Some(proto::Timestamp {
+                            seconds: 300,
+                            nanos: 0,
+                        }),
+                    });
+                    proto::QueryProposalResponse { proposal }.encode_to_vec()
+                }
+                QueryRequest::Stargate { path, .. } if path == PROPOSAL_VOTE_OPTIONS_PATH => {
+                    proto::QueryProposalVoteOptionsResponse {
+                        vote_options: self.vote_options.clone(),
+                    }
+                    .encode_to_vec()
+                }
+                _ => {
+                    return SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: "Stargate".to_string(),
+                    })
+                }
+            };
+            SystemResult::Ok(ContractResult::Ok(
+                to_json_binary(&Binary::from(response)).unwrap(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_query_proposal_v1() {
+        let querier = V1Querier { vote_options: None };
+        let querier = QuerierWrapper::<Empty>::new(&querier);
+
+        let proposal = query_proposal(&querier, 1).unwrap().unwrap();
+        assert_eq!(
+            proposal,
+            GovProposal {
+                id: 1,
+                status: ProposalStatus::Passed,
+                voting_start_time: Timestamp::from_seconds(100),
+                voting_end_time: Timestamp::from_seconds(300),
+            }
+        );
+    }
+
+    #[test]
+    fn test_query_vote_options() {
+        let options = |texts: [&str; 4]| {
+            let querier = V1Querier {
+                vote_options: Some(proto::ProposalVoteOptions {
+                    option_one: texts[0].to_string(),
+                    option_two: texts[1].to_string(),
+                    option_three: texts[2].to_string(),
+                    option_four: texts[3].to_string(),
+                }),
+            };
+            query_vote_options(&QuerierWrapper::<Empty>::new(&querier), 1).unwrap()
+        };
+
+        // Only the options with a text are available
+        assert_eq!(options(["Yes", "", "No", ""]), vec![1, 3]);
+        assert_eq!(
+            options(["A", "B", "C", "D"]),
+            STANDARD_VOTE_OPTIONS.to_vec()
+        );
+
+        // Without any option text, the standard options apply
+        assert_eq!(options(["", "", "", ""]), STANDARD_VOTE_OPTIONS.to_vec());
+        let querier = V1Querier { vote_options: None };
+        assert_eq!(
+            query_vote_options(&QuerierWrapper::<Empty>::new(&querier), 1).unwrap(),
+            STANDARD_VOTE_OPTIONS.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_proposal_status() {
+        assert!(ProposalStatus::from_i32(3).is_finished());
+        assert!(!ProposalStatus::from_i32(2).is_finished());
+        assert_eq!(ProposalStatus::from_i32(1).to_string(), "DEPOSIT_PERIOD");
+        assert_eq!(ProposalStatus::from_i32(9).to_string(), "UNKNOWN(9)");
+    }
+}
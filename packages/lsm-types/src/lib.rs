@@ -1,5 +1,9 @@
+use std::collections::BTreeMap;
+
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Decimal256, Uint128, Uint256};
+use cosmwasm_std::{
+    Addr, Coin, Decimal, Decimal256, HexBinary, StdError, StdResult, Timestamp, Uint128, Uint256,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -9,12 +13,35 @@ pub struct InstantiateMsg {
     pub owner: String,
     /// The validator address that this contract will manage LSM shares for
     pub validator: String,
+    /// Optional validators whose LSM shares are also accepted (defaults to none)
+    pub validators: Option<Vec<String>>,
+    /// Optional accepted validator receiving the stake of unhealthy validators in emergency mode
+    /// (defaults to the first healthy accepted validator)
+    pub fallback_validator: Option<String>,
     /// Optional maximum cap for total staked amount
     pub max_cap: Option<Uint128>,
     /// Code ID of the ProposalOptionLocker contract
     pub locker_code_id: u64,
+    /// Optional voting power rental pricing (defaults to a flat 1 VP = 0.1 staking token)
+    pub rental_pricing: Option<RentalPricing>,
+    /// Optional tip paid to whoever destroys an expired voting session (defaults to zero, capped)
+    pub keeper_tip: Option<Uint128>,
+    /// Optional denoms other than the staking denom accepted as rewards (defaults to none)
+    pub reward_denoms: Option<Vec<String>>,
+    /// Optional address receiving the protocol fees (defaults to the owner)
+    pub fee_collector: Option<String>,
+    /// Optional share of rental income withheld as protocol fee (defaults to zero)
+    pub rental_fee_rate: Option<Decimal>,
+    /// Optional share of staking rewards withheld as protocol fee (defaults to zero)
+    pub reward_fee_rate: Option<Decimal>,
+    /// Optional max fraction of the pool tokens (State::total_tokens) that may be rented
+    /// per proposal (defaults to no limit)
+    pub max_rent_fraction: Option<Decimal>,
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     /// Deposit LSM shares to the contract
@@ -32,34 +59,133 @@ pub enum ExecuteMsg {
     /// Tokenizes shares and returns LSM shares to the user
     Withdraw { amount: Uint128 },
 
+    /// Set whether the staking denom rewards of the caller are compounded into their stake
+    /// instead of being paid out, ClaimRewards still pays them out
+    /// The pending rewards are settled under the previous setting, not while paused or in emergency mode
+    SetAutoCompound { enabled: bool },
+
+    /// Compound the pending staking denom rewards of a batch of auto-compounding stakers
+    /// by delegating them back to the validator, callable by anyone
+    /// Successive calls go through all auto-compounding stakers, not in emergency mode
+    Compound {},
+
     /// Update contract configuration (owner only)
-    UpdateConfig {
-        owner: Option<String>,
-        max_cap: Option<Uint128>,
+    UpdateConfig(Box<ConfigUpdate>),
+
+    /// Send the accrued protocol fees to the fee collector
+    /// Only callable by owner or fee collector
+    WithdrawFees {},
+
+    /// Move stake between validators by redelegation (owner only)
+    /// Each move is (from_validator, to_validator, amount), the destination must be accepted
+    /// Not allowed while a voting session is active
+    Rebalance {
+        moves: Vec<(String, String, Uint128)>,
     },
 
+    /// Check whether the validators are jailed, callable by anyone
+    /// Tombstoned validators stay jailed, so they are caught as well
+    /// An unhealthy validator puts the contract in emergency mode, blocking deposits and rentals,
+    /// the mode is lifted once all validators are healthy again
+    CheckValidatorHealth {},
+
+    /// Redelegate the stake of the unhealthy validators to the fallback validator
+    /// Only callable in emergency mode, by the owner or a staker
+    EmergencyRedelegate {},
+
     /// Create voting lockers for a governance proposal (owner only)
     /// This will pause deposits and withdrawals
-    CreateVotingLockers { proposal_id: u64 },
+    /// If auction is set, voting power is allocated by a sealed-bid auction
+    /// instead of RentVotingPower
+    /// If options is set, lockers are only created for those vote options,
+    /// which must all be valid options of the proposal
+    /// If weighted is set, a single locker holds the voting power of all options
+    /// and casts a weighted vote following the rented amounts
+    CreateVotingLockers {
+        proposal_id: u64,
+        auction: Option<AuctionParams>,
+        options: Option<Vec<i32>>,
+        #[serde(default)]
+        weighted: bool,
+    },
 
-    /// Destroy voting lockers for a governance proposal (owner only)
+    /// Destroy voting lockers for a governance proposal
+    /// Callable by anyone once the proposal is finished or its voting end time has passed,
+    /// a caller other than the owner receives the keeper tip of the session
     /// This will unpause if no other active voting sessions exist
     DestroyVotingLockers { proposal_id: u64 },
 
+    /// Destroy voting lockers for a governance proposal that is still ongoing
+    /// Escape hatch for a stuck proposal, the lockers skip their proposal status check
+    /// Only callable by owner
+    EmergencyDestroyVotingLockers { proposal_id: u64 },
+
     /// Return LSM shares from a voting locker after destroy
-    /// This redeems the shares without modifying total_staked or global_reward_index
-    /// Only callable by registered voting lockers
-    ReturnLsmShares {
-        proposal_id: u64,
-        vote_option: i32,
-    },
+    /// This redeems the shares without modifying total_staked or the reward indices
+    /// Only callable by the locker registered for the vote option and the validator of the shares
+    ReturnLsmShares { proposal_id: u64, vote_option: i32 },
 
     /// Rent voting power for a governance proposal
     /// Receives ATOM in funds and tokenizes shares to deposit to the specified locker
+    /// Reverts if less than min_vp_amount is received or if the average price paid
+    /// per unit of voting power exceeds max_price
+    /// No more than max_rent_fraction of the pool tokens may be rented per proposal
+    /// With allow_partial, fills as much voting power as is available and refunds the rest
+    /// The voting power is tokenized from the given validator, or from the validator
+    /// of the session with the most delegated tokens
     RentVotingPower {
         proposal_id: u64,
         vote_option: i32,
+        min_vp_amount: Option<Uint128>,
+        max_price: Option<Decimal256>,
+        #[serde(default)]
+        allow_partial: bool,
+        #[serde(default)]
+        validator: Option<String>,
+    },
+
+    /// Commit a sealed bid during the commit phase of a voting session auction
+    /// Funds must contain the staking_denom deposit covering the bid
+    /// commitment = sha256("{bidder}:{proposal_id}:{vote_option}:{vp_amount}:{price_per_vp}:{salt}")
+    CommitBid {
+        proposal_id: u64,
+        commitment: HexBinary,
+    },
+
+    /// Reveal a committed bid during the reveal phase of a voting session auction
+    RevealBid {
+        proposal_id: u64,
+        vote_option: i32,
+        vp_amount: Uint128,
+        price_per_vp: Decimal256,
+        salt: String,
     },
+
+    /// Settle a voting session auction once the reveal phase is over (permissionless)
+    /// Allocates voting power to the highest revealed bids, tokenizes shares into the
+    /// winning lockers and refunds unused deposits
+    /// Deposits of unrevealed bids are forfeited to stakers
+    SettleAuction { proposal_id: u64 },
+}
+
+/// Settings changed by UpdateConfig, the unset ones are left untouched
+#[cw_serde]
+#[derive(Default)]
+pub struct ConfigUpdate {
+    pub owner: Option<String>,
+    pub max_cap: Option<Uint128>,
+    pub rental_pricing: Option<RentalPricing>,
+    pub keeper_tip: Option<Uint128>,
+    pub reward_denoms: Option<Vec<String>>,
+    pub fee_collector: Option<String>,
+    pub rental_fee_rate: Option<Decimal>,
+    pub reward_fee_rate: Option<Decimal>,
+    pub validators: Option<Vec<String>>,
+    pub fallback_validator: Option<String>,
+    /// Unset the fallback validator, can't be combined with fallback_validator
+    #[serde(default)]
+    pub clear_fallback_validator: bool,
+    pub max_rent_fraction: Option<Decimal>,
 }
 
 #[cw_serde]
@@ -77,16 +203,74 @@ pub enum QueryMsg {
     #[returns(TotalStakedResponse)]
     TotalStaked {},
 
-    /// Get global reward index
+    /// Get the staking reward and rental income indices
     #[returns(RewardIndexResponse)]
     RewardIndex {},
 
+    /// Get the exchange rate between pool tokens and pool shares
+    #[returns(ExchangeRateResponse)]
+    ExchangeRate {},
+
     /// Get list of stakers with pagination
     #[returns(StakersResponse)]
     Stakers {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+
+    /// Get the sealed bids of a voting session auction with pagination
+    #[returns(BidsResponse)]
+    Bids {
+        proposal_id: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Get the cost of renting voting power on a proposal option
+    #[returns(RentalQuoteResponse)]
+    RentalQuote {
+        proposal_id: u64,
+        vote_option: i32,
+        vp_amount: Uint128,
+    },
+
+    /// Get the rentals of a proposal with pagination
+    /// start_after is the id of the last rental returned
+    #[returns(RentalsResponse)]
+    RentalsByProposal {
+        proposal_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Get the rentals of a renter with pagination
+    /// start_after is the id of the last rental returned
+    #[returns(RentalsResponse)]
+    RentalsByRenter {
+        renter: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Get the voting power rented on each option of a proposal
+    #[returns(RentalTotalsResponse)]
+    RentalTotals { proposal_id: u64 },
+
+    /// Get the protocol fee configuration and the fees accrued so far
+    #[returns(FeesResponse)]
+    Fees {},
+
+    /// Get the denoms accepted by DepositRewards, the staking denom first
+    #[returns(RewardDenomsResponse)]
+    RewardDenoms {},
+
+    /// Get the tokens delegated to each validator
+    #[returns(DelegationsResponse)]
+    Delegations {},
+
+    /// Get a voting session with the stake deployed in its lockers
+    #[returns(VotingSessionResponse)]
+    VotingSession { proposal_id: u64 },
 }
 
 #[cw_serde]
@@ -94,11 +278,24 @@ pub struct ConfigResponse {
     pub owner: Addr,
     pub staking_denom: String,
     pub validator: String,
+    pub validators: Vec<String>,
     pub max_cap: Option<Uint128>,
     pub locker_code_id: u64,
+    pub rental_pricing: RentalPricing,
+    pub keeper_tip: Uint128,
+    pub reward_denoms: Vec<String>,
+    pub fee_collector: Addr,
+    pub rental_fee_rate: Decimal,
+    pub reward_fee_rate: Decimal,
+    pub fallback_validator: Option<String>,
+    pub max_rent_fraction: Option<Decimal>,
     pub total_staked: Uint128,
+    /// Reward index of the staking denom
     pub global_reward_index: Decimal256,
     pub is_paused: bool,
+    /// Validators found unhealthy by the last health check, the contract is in emergency
+    /// mode while this is not empty
+    pub unhealthy_validators: Vec<String>,
 }
 
 /// Helper struct to hold LSM share information
@@ -108,21 +305,218 @@ pub struct LsmShareInfo {
     pub record_id: String,
 }
 
+/// Vote option under which the single locker of a weighted voting session is registered
+pub const WEIGHTED_LOCKER_OPTION: i32 = 0;
+
 /// Voting session for a governance proposal
 #[cw_serde]
 pub struct VotingSession {
     pub proposal_id: u64,
     /// List of (vote_option, locker_address) pairs
+    /// A weighted session has a single locker registered under WEIGHTED_LOCKER_OPTION
+    /// These lockers hold voting power delegated to the primary validator
     pub locker_addresses: Vec<(i32, Addr)>,
+    /// List of (validator, vote_option, locker_address) of the lockers holding voting power
+    /// delegated to the other validators
+    #[serde(default)]
+    pub validator_lockers: Vec<(String, i32, Addr)>,
+    /// Vote options of a weighted session, None when each option has its own locker
+    pub weighted_options: Option<Vec<i32>>,
     pub is_active: bool,
+    /// Total voting power tokenized into the lockers of this session
+    #[serde(default)]
+    pub rented_amount: Uint128,
+    /// Sealed-bid auction allocating the voting power of this session, if any
+    pub auction: Option<Auction>,
+    /// End of the proposal's voting period
+    #[serde(default)]
+    pub voting_end_time: Timestamp,
+    /// Rental income withheld to tip the keeper destroying this session
+    #[serde(default)]
+    pub keeper_reserve: Uint128,
+    /// List of (locker_address, amount) of the stake currently deployed in each locker
+    /// A locker's stake is released once it returns its shares
+    #[serde(default)]
+    pub deployed: Vec<(Addr, Uint128)>,
+}
+
+impl VotingSession {
+    /// Locker holding the voting power of a vote option delegated to the primary validator
+    pub fn locker_for(&self, vote_option: i32) -> Option<&Addr> {
+        let locker_option = self.locker_option(vote_option)?;
+        self.locker_addresses
+            .iter()
+            .find(|(option, _)| *option == locker_option)
+            .map(|(_, addr)| addr)
+    }
+
+    /// Locker holding the voting power of a vote option delegated to another validator
+    pub fn validator_locker_for(&self, validator: &str, vote_option: i32) -> Option<&Addr> {
+        let locker_option = self.locker_option(vote_option)?;
+        self.validator_lockers
+            .iter()
+            .find(|(locker_validator, option, _)| {
+                locker_validator == validator && *option == locker_option
+            })
+            .map(|(_, _, addr)| addr)
+    }
+
+    /// Addresses of all the lockers of the session
+    pub fn all_lockers(&self) -> impl Iterator<Item = &Addr> {
+        self.locker_addresses
+            .iter()
+            .map(|(_, addr)| addr)
+            .chain(self.validator_lockers.iter().map(|(_, _, addr)| addr))
+    }
+
+    /// Record stake deployed in a locker
+    pub fn deploy(&mut self, locker: &Addr, amount: Uint128) {
+        match self.deployed.iter_mut().find(|(addr, _)| addr == locker) {
+            Some((_, deployed)) => *deployed += amount,
+            None => self.deployed.push((locker.clone(), amount)),
+        }
+    }
+
+    /// Release the stake deployed in a locker, returns the released amount
+    pub fn release(&mut self, locker: &Addr) -> Uint128 {
+        let released = self.deployed_in(locker);
+        self.deployed.retain(|(addr, _)| addr != locker);
+        released
+    }
+
+    /// Stake currently deployed in a locker
+    pub fn deployed_in(&self, locker: &Addr) -> Uint128 {
+        self.deployed
+            .iter()
+            .find(|(addr, _)| addr == locker)
+            .map(|(_, amount)| *amount)
+            .unwrap_or_default()
+    }
+
+    /// Stake currently deployed in all the lockers of the session
+    pub fn deployed_amount(&self) -> Uint128 {
+        self.deployed.iter().map(|(_, amount)| *amount).sum()
+    }
+
+    /// Option under which the locker of a vote option is registered
+    /// A weighted session only accepts its own vote options, WEIGHTED_LOCKER_OPTION is not a vote
+    fn locker_option(&self, vote_option: i32) -> Option<i32> {
+        match &self.weighted_options {
+            Some(options)
+                if vote_option != WEIGHTED_LOCKER_OPTION && options.contains(&vote_option) =>
+            {
+                Some(WEIGHTED_LOCKER_OPTION)
+            }
+            Some(_) => None,
+            None => Some(vote_option),
+        }
+    }
+}
+
+/// Voting power rented by a renter on a proposal option
+#[cw_serde]
+pub struct Rental {
+    /// Sequential id of the rental, unique across proposals
+    pub id: u64,
+    pub renter: Addr,
+    pub proposal_id: u64,
+    pub vote_option: i32,
+    /// staking_denom paid for the rented voting power
+    pub amount_paid: Uint128,
+    /// Voting power received
+    pub vp_amount: Uint128,
+    /// Block height of the rental
+    pub height: u64,
+}
+
+/// Aggregate of all rentals on a proposal option
+#[cw_serde]
+pub struct RentalTotal {
+    pub vote_option: i32,
+    pub amount_paid: Uint128,
+    pub vp_amount: Uint128,
+}
+
+/// Parameters of a sealed-bid auction for a voting session
+#[cw_serde]
+pub struct AuctionParams {
+    /// Duration of the commit phase in seconds
+    pub commit_duration: u64,
+    /// Duration of the reveal phase in seconds
+    pub reveal_duration: u64,
+}
+
+/// Sealed-bid auction attached to a voting session
+#[cw_serde]
+pub struct Auction {
+    /// Bids can be committed until this time
+    pub commit_end: Timestamp,
+    /// Bids can be revealed until this time, settlement is possible afterwards
+    pub reveal_end: Timestamp,
+    pub settled: bool,
+}
+
+/// Sealed bid committed to a voting session auction
+#[cw_serde]
+pub struct Bid {
+    pub bidder: Addr,
+    /// sha256 commitment of the bid
+    pub commitment: HexBinary,
+    /// Amount of staking_denom escrowed with the commitment
+    pub deposit: Uint128,
+    /// Block height of the commitment, used to break ties between equal prices
+    pub committed_at: u64,
+    /// Bid details, set once revealed
+    pub revealed: Option<RevealedBid>,
+}
+
+#[cw_serde]
+pub struct RevealedBid {
+    pub vote_option: i32,
+    pub vp_amount: Uint128,
+    pub price_per_vp: Decimal256,
 }
 
 #[cw_serde]
 pub struct StakerInfoResponse {
     pub address: Addr,
+    /// Pool shares of the staker
     pub staked_amount: Uint128,
+    /// Tokens backing the shares of the staker at the current exchange rate
+    pub staked_tokens: Uint128,
+    /// Staking reward index of the staking denom
     pub reward_index: Decimal256,
+    /// Rental income index
+    pub rental_index: Decimal256,
+    /// Pending staking denom amount, staking rewards plus rental income
     pub pending_rewards: Uint128,
+    /// Pending staking rewards in the staking denom
+    pub pending_staking_rewards: Uint128,
+    /// Pending rental income in the staking denom
+    pub pending_rental_income: Uint128,
+    /// Pending staking rewards in denoms other than the staking denom
+    pub pending_denom_rewards: Vec<Coin>,
+    pub auto_compound: bool,
+}
+
+#[cw_serde]
+pub struct DelegationsResponse {
+    pub delegations: Vec<ValidatorDelegation>,
+}
+
+#[cw_serde]
+pub struct ValidatorDelegation {
+    pub validator: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct VotingSessionResponse {
+    pub voting_session: VotingSession,
+    /// Stake currently deployed in all the lockers of the session
+    pub deployed_amount: Uint128,
+    /// Max voting power that may be rented on the proposal, None if unlimited
+    pub max_rent_amount: Option<Uint128>,
 }
 
 #[cw_serde]
@@ -130,9 +524,38 @@ pub struct TotalStakedResponse {
     pub total_staked: Uint128,
 }
 
+#[cw_serde]
+pub struct ExchangeRateResponse {
+    /// Pool tokens per pool share, after accounting for any slashing not recorded yet
+    pub exchange_rate: Decimal256,
+    pub total_tokens: Uint128,
+    pub total_shares: Uint128,
+    /// Tokens currently delegated, lower than the pool tokens while voting sessions are active
+    pub delegated_tokens: Uint128,
+}
+
 #[cw_serde]
 pub struct RewardIndexResponse {
+    /// Staking reward index of the staking denom
     pub global_reward_index: Decimal256,
+    /// Staking reward index of every reward denom
+    pub reward_indices: BTreeMap<String, Decimal256>,
+    /// Rental income index, rental income is paid in the staking denom
+    pub rental_index: Decimal256,
+}
+
+#[cw_serde]
+pub struct RewardDenomsResponse {
+    pub denoms: Vec<String>,
+}
+
+#[cw_serde]
+pub struct FeesResponse {
+    pub fee_collector: Addr,
+    pub rental_fee_rate: Decimal,
+    pub reward_fee_rate: Decimal,
+    /// Fees accrued in each denom and not withdrawn yet
+    pub accrued_fees: Vec<Coin>,
 }
 
 #[cw_serde]
@@ -140,88 +563,549 @@ pub struct StakersResponse {
     pub stakers: Vec<StakerInfoResponse>,
 }
 
+#[cw_serde]
+pub struct BidsResponse {
+    pub bids: Vec<Bid>,
+}
+
+#[cw_serde]
+pub struct RentalsResponse {
+    pub rentals: Vec<Rental>,
+}
+
+#[cw_serde]
+pub struct RentalTotalsResponse {
+    pub proposal_id: u64,
+    pub totals: Vec<RentalTotal>,
+}
+
+#[cw_serde]
+pub struct RentalQuoteResponse {
+    pub proposal_id: u64,
+    pub vote_option: i32,
+    pub vp_amount: Uint128,
+    /// Amount of staking_denom to send with RentVotingPower to receive vp_amount
+    pub cost: Uint128,
+    /// Average price paid per unit of voting power
+    pub price_per_vp: Decimal256,
+}
+
+/// Maximum steepness of the exponential pricing curve
+/// At full utilization the price is base_price * e^steepness
+pub const MAX_EXPONENTIAL_STEEPNESS: u64 = 20;
+
+/// Pricing model for voting power rentals
+/// Prices are expressed in staking_denom per unit of voting power
+/// Curve prices depend on the utilization u = rented / total_tokens of the proposal,
+/// where total_tokens is the amount of tokens backing the pool (State::total_tokens),
+/// not the amount of pool shares
+#[cw_serde]
+pub enum PricingModel {
+    /// Every unit of voting power costs the same price
+    Flat { price_per_vp: Decimal256 },
+    /// price(u) = base_price + slope * u
+    Linear {
+        base_price: Decimal256,
+        slope: Decimal256,
+    },
+    /// price(u) = base_price * e^(steepness * u)
+    Exponential {
+        base_price: Decimal256,
+        steepness: Decimal256,
+    },
+}
+
+impl PricingModel {
+    pub fn validate(&self) -> StdResult<()> {
+        match self {
+            PricingModel::Flat { price_per_vp } => {
+                if price_per_vp.is_zero() {
+                    return Err(StdError::generic_err(
+                        "price_per_vp must be greater than zero",
+                    ));
+                }
+            }
+            PricingModel::Linear { base_price, .. } => {
+                if base_price.is_zero() {
+                    return Err(StdError::generic_err(
+                        "base_price must be greater than zero",
+                    ));
+                }
+            }
+            PricingModel::Exponential {
+                base_price,
+                steepness,
+            } => {
+                if base_price.is_zero() {
+                    return Err(StdError::generic_err(
+                        "base_price must be greater than zero",
+                    ));
+                }
+                if *steepness > Decimal256::from_atomics(MAX_EXPONENTIAL_STEEPNESS, 0).unwrap() {
+                    return Err(StdError::generic_err(format!(
+                        "steepness cannot exceed {}",
+                        MAX_EXPONENTIAL_STEEPNESS
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Cost in staking_denom of renting vp_amount on top of the already rented amount, rounded up
+    /// Curve prices are integrated over [rented, rented + vp_amount]
+    pub fn cost(
+        &self,
+        vp_amount: Uint128,
+        rented: Uint128,
+        total_tokens: Uint128,
+    ) -> StdResult<Uint128> {
+        let cost = match self {
+            PricingModel::Flat { price_per_vp } => Uint256::from(vp_amount)
+                .checked_mul_ceil(*price_per_vp)
+                .map_err(|e| StdError::generic_err(e.to_string()))?,
+            PricingModel::Linear { base_price, slope } => {
+                let vp = Decimal256::from_ratio(vp_amount, 1u128);
+                let base_cost = base_price.checked_mul(vp)?;
+                if total_tokens.is_zero() {
+                    base_cost.to_uint_ceil()
+                } else {
+                    // ∫ slope * x / T dx over [r, r + v] = slope * v * (2r + v) / 2T
+                    let two_r_plus_v = Decimal256::from_ratio(
+                        Uint256::from(rented) * Uint256::from(2u128) + Uint256::from(vp_amount),
+                        Uint256::from(total_tokens) * Uint256::from(2u128),
+                    );
+                    base_cost
+                        .checked_add(slope.checked_mul(vp)?.checked_mul(two_r_plus_v)?)?
+                        .to_uint_ceil()
+                }
+            }
+            PricingModel::Exponential {
+                base_price,
+                steepness,
+            } => {
+                let vp = Decimal256::from_ratio(vp_amount, 1u128);
+                if total_tokens.is_zero() || steepness.is_zero() {
+                    base_price.checked_mul(vp)?.to_uint_ceil()
+                } else {
+                    // ∫ base * e^(k * x / T) dx over [r, r + v] = base * T / k * (e^(k(r+v)/T) - e^(kr/T))
+                    let start =
+                        steepness.checked_mul(Decimal256::from_ratio(rented, total_tokens))?;
+                    let end = steepness.checked_mul(Decimal256::from_ratio(
+                        Uint256::from(rented) + Uint256::from(vp_amount),
+                        Uint256::from(total_tokens),
+                    ))?;
+                    exp(end)?
+                        .checked_sub(exp(start)?)?
+                        .checked_mul(*base_price)?
+                        .checked_mul(Decimal256::from_ratio(total_tokens, 1u128))?
+                        .checked_div(*steepness)
+                        .map_err(|e| StdError::generic_err(e.to_string()))?
+                        .to_uint_ceil()
+                }
+            }
+        };
+        Ok(Uint128::try_from(cost)?)
+    }
+
+    /// Voting power bought by a payment in staking_denom on top of the already rented amount,
+    /// rounded down
+    pub fn vp_for_payment(
+        &self,
+        payment: Uint128,
+        rented: Uint128,
+        total_tokens: Uint128,
+    ) -> StdResult<Uint128> {
+        let base_price = match self {
+            PricingModel::Flat { price_per_vp } => {
+                let vp_amount = Uint256::from(payment)
+                    .checked_div_floor(*price_per_vp)
+                    .map_err(|e| StdError::generic_err(e.to_string()))?;
+                return Ok(Uint128::try_from(vp_amount)?);
+            }
+            PricingModel::Linear { base_price, .. } => base_price,
+            PricingModel::Exponential { base_price, .. } => base_price,
+        };
+
+        // Curve prices never go below base_price, which bounds the search
+        let upper_bound = Uint256::from(payment)
+            .checked_div_floor(*base_price)
+            .map_err(|e| StdError::generic_err(e.to_string()))?;
+        let mut low = Uint128::zero();
+        let mut high = Uint128::try_from(upper_bound)?;
+
+        // Binary search the largest amount whose cost fits in the payment
+        // A cost that overflows is always above the payment
+        while low < high {
+            let mid = low + (high - low + Uint128::one()) / Uint128::new(2);
+            match self.cost(mid, rented, total_tokens) {
+                Ok(cost) if cost <= payment => low = mid,
+                _ => high = mid - Uint128::one(),
+            }
+        }
+        Ok(low)
+    }
+}
+
+/// Compute e^x using its Taylor series
+fn exp(x: Decimal256) -> StdResult<Decimal256> {
+    let mut sum = Decimal256::one();
+    let mut term = Decimal256::one();
+    let mut n = 1u128;
+    loop {
+        // term_n = term_(n-1) * x / n
+        term = term
+            .checked_mul(x)?
+            .checked_div(Decimal256::from_ratio(n, 1u128))
+            .map_err(|e| StdError::generic_err(e.to_string()))?;
+        if term.is_zero() {
+            return Ok(sum);
+        }
+        sum = sum.checked_add(term)?;
+        n += 1;
+    }
+}
+
+/// Voting power rental pricing with optional per-proposal overrides
+#[cw_serde]
+pub struct RentalPricing {
+    /// Pricing applied to proposals without an override
+    pub default: PricingModel,
+    /// List of (proposal_id, pricing) pairs overriding the default
+    pub overrides: Vec<(u64, PricingModel)>,
+}
+
+impl Default for RentalPricing {
+    /// 1 VP = 0.1 staking token
+    fn default() -> Self {
+        Self {
+            default: PricingModel::Flat {
+                price_per_vp: Decimal256::permille(100),
+            },
+            overrides: vec![],
+        }
+    }
+}
+
+impl RentalPricing {
+    pub fn validate(&self) -> StdResult<()> {
+        self.default.validate()?;
+        for (index, (proposal_id, model)) in self.overrides.iter().enumerate() {
+            if self.overrides[..index]
+                .iter()
+                .any(|(other_id, _)| other_id == proposal_id)
+            {
+                return Err(StdError::generic_err(format!(
+                    "Duplicate pricing override for proposal {}",
+                    proposal_id
+                )));
+            }
+            model.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Get the pricing model that applies to a proposal
+    pub fn for_proposal(&self, proposal_id: u64) -> &PricingModel {
+        self.overrides
+            .iter()
+            .find(|(id, _)| *id == proposal_id)
+            .map(|(_, model)| model)
+            .unwrap_or(&self.default)
+    }
+}
+
 /// State stored for each staker
 #[cw_serde]
 pub struct Staker {
     /// Amount of tokens staked by this user
     pub staked_amount: Uint128,
-    /// Reward index at the last update for this user
-    pub reward_index: Decimal256,
+    /// Staking reward index of each denom at the last update for this user
+    pub reward_indices: BTreeMap<String, Decimal256>,
+    /// Rental income index at the last update for this user
+    #[serde(default)]
+    pub rental_index: Decimal256,
+    /// Whether staking denom rewards are compounded into staked_amount
+    #[serde(default)]
+    pub auto_compound: bool,
+}
+
+impl Default for Staker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Staker {
     pub fn new() -> Self {
         Self {
             staked_amount: Uint128::zero(),
-            reward_index: Decimal256::zero(),
+            reward_indices: BTreeMap::new(),
+            rental_index: Decimal256::zero(),
+            auto_compound: false,
         }
     }
 
-    /// Calculate pending rewards based on current global index
-    pub fn calculate_pending_rewards(&self, global_index: Decimal256) -> Uint128 {
-        if self.staked_amount.is_zero() {
-            return Uint128::zero();
+    /// Calculate pending staking rewards in every denom based on current global indices
+    /// A denom missing from the user's indices started accruing from zero
+    pub fn calculate_pending_rewards(
+        &self,
+        global_indices: &BTreeMap<String, Decimal256>,
+    ) -> Vec<Coin> {
+        global_indices
+            .iter()
+            .filter_map(|(denom, global_index)| {
+                let reward_index = self.reward_indices.get(denom).copied().unwrap_or_default();
+                let amount = pending_rewards(self.staked_amount, reward_index, *global_index);
+                (!amount.is_zero()).then(|| Coin::new(amount, denom))
+            })
+            .collect()
+    }
+
+    /// Calculate pending rental income based on the current rental index
+    pub fn calculate_pending_rental_income(&self, rental_index: Decimal256) -> Uint128 {
+        pending_rewards(self.staked_amount, self.rental_index, rental_index)
+    }
+
+    /// Calculate all pending income, the rental income is added to the staking denom rewards
+    pub fn calculate_pending_income(&self, state: &State, staking_denom: &str) -> Vec<Coin> {
+        let mut income = self.calculate_pending_rewards(&state.reward_indices);
+        let rental_income = self.calculate_pending_rental_income(state.rental_index);
+        if rental_income.is_zero() {
+            return income;
         }
 
-        // rewards = staked_amount * (global_index - user_index)
-        let index_diff = global_index
-            .checked_sub(self.reward_index)
-            .unwrap_or_default();
-        let new_rewards = Uint256::from(self.staked_amount)
-            .checked_mul(index_diff.atomics())
-            .unwrap_or_default()
-            / Uint256::from(10u128.pow(18)); // Decimal256 has 18 decimals
+        match income.iter_mut().find(|coin| coin.denom == staking_denom) {
+            Some(coin) => coin.amount += rental_income,
+            None => {
+                income.push(Coin::new(rental_income, staking_denom));
+                income.sort_by(|a, b| a.denom.cmp(&b.denom));
+            }
+        }
+        income
+    }
 
-        Uint128::try_from(new_rewards).unwrap_or_default()
+    /// Update user's indices (called after claiming or when staked amount changes)
+    pub fn update_index(&mut self, state: &State) {
+        self.reward_indices = state.reward_indices.clone();
+        self.rental_index = state.rental_index;
     }
+}
 
-    /// Update user's reward index (called after claiming or when staked amount changes)
-    pub fn update_index(&mut self, global_index: Decimal256) {
-        self.reward_index = global_index;
+/// Rewards accrued by a staked amount since its reward index
+fn pending_rewards(
+    staked_amount: Uint128,
+    reward_index: Decimal256,
+    global_index: Decimal256,
+) -> Uint128 {
+    if staked_amount.is_zero() {
+        return Uint128::zero();
     }
+
+    // rewards = staked_amount * (global_index - user_index)
+    let index_diff = global_index.checked_sub(reward_index).unwrap_or_default();
+    let new_rewards = Uint256::from(staked_amount)
+        .checked_mul(index_diff.atomics())
+        .unwrap_or_default()
+        / Uint256::from(10u128.pow(18)); // Decimal256 has 18 decimals
+
+    Uint128::try_from(new_rewards).unwrap_or_default()
 }
 
 #[cw_serde]
 pub struct Config {
     pub owner: Addr,
     pub staking_denom: String,
+    /// Primary validator, compounded rewards are delegated to it
     pub validator: String,
+    /// Validators whose LSM shares are also accepted
+    #[serde(default)]
+    pub validators: Vec<String>,
     pub max_cap: Option<Uint128>,
     pub locker_code_id: u64,
+    #[serde(default)]
+    pub rental_pricing: RentalPricing,
+    /// Tip withheld from the rental income of each voting session for its keeper
+    #[serde(default)]
+    pub keeper_tip: Uint128,
+    /// Denoms other than the staking denom accepted as rewards
+    #[serde(default)]
+    pub reward_denoms: Vec<String>,
+    /// Address receiving the protocol fees, the owner if unset
+    #[serde(default)]
+    pub fee_collector: Option<Addr>,
+    /// Share of rental income withheld as protocol fee
+    #[serde(default)]
+    pub rental_fee_rate: Decimal,
+    /// Share of staking rewards withheld as protocol fee
+    #[serde(default)]
+    pub reward_fee_rate: Decimal,
+    /// Validator receiving the stake of unhealthy validators in emergency mode
+    #[serde(default)]
+    pub fallback_validator: Option<String>,
+    /// Max fraction of the pool tokens that may be rented per proposal, no limit if unset
+    /// Measured against State::total_tokens rather than total_staked, which counts pool
+    /// shares and drifts from the delegated tokens once a slash is recorded
+    #[serde(default)]
+    pub max_rent_fraction: Option<Decimal>,
+}
+
+impl Config {
+    /// Whether rewards in this denom are distributed to stakers
+    pub fn is_reward_denom(&self, denom: &str) -> bool {
+        denom == self.staking_denom || self.reward_denoms.iter().any(|d| d == denom)
+    }
+
+    /// Whether LSM shares of this validator are accepted
+    pub fn is_validator(&self, validator: &str) -> bool {
+        validator == self.validator || self.validators.iter().any(|v| v == validator)
+    }
+
+    /// Primary validator followed by the other accepted validators
+    pub fn all_validators(&self) -> Vec<String> {
+        let mut validators = vec![self.validator.clone()];
+        validators.extend(self.validators.iter().cloned());
+        validators
+    }
+
+    /// Address receiving the protocol fees
+    pub fn fee_collector(&self) -> &Addr {
+        self.fee_collector.as_ref().unwrap_or(&self.owner)
+    }
+
+    /// Max voting power that may be rented per proposal out of the pool tokens,
+    /// None if unlimited
+    pub fn max_rent_amount(&self, total_tokens: Uint128) -> Option<Uint128> {
+        self.max_rent_fraction
+            .map(|fraction| total_tokens.mul_floor(fraction))
+    }
 }
 
 #[cw_serde]
 pub struct State {
-    /// Total amount staked in the contract
+    /// Total pool shares held by the stakers
     pub total_staked: Uint128,
-    /// Global reward index (cumulative rewards per token)
-    pub global_reward_index: Decimal256,
+    /// Total pool tokens backing the shares, lowered when a slash is detected
+    #[serde(default)]
+    pub total_tokens: Uint128,
+    /// Staking reward index of each denom (cumulative rewards per token)
+    pub reward_indices: BTreeMap<String, Decimal256>,
+    /// Rental income index (cumulative rental income per token, in the staking denom)
+    #[serde(default)]
+    pub rental_index: Decimal256,
+    /// Protocol fees accrued in each denom and not withdrawn yet
+    #[serde(default)]
+    pub accrued_fees: BTreeMap<String, Uint128>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
             total_staked: Uint128::zero(),
-            global_reward_index: Decimal256::zero(),
+            total_tokens: Uint128::zero(),
+            reward_indices: BTreeMap::new(),
+            rental_index: Decimal256::zero(),
+            accrued_fees: BTreeMap::new(),
         }
     }
 
-    /// Update global reward index when new rewards are added
-    pub fn add_rewards(&mut self, reward_amount: Uint128) {
+    /// Pool tokens per pool share, one while the pool is empty
+    pub fn exchange_rate(&self) -> Decimal256 {
         if self.total_staked.is_zero() {
+            return Decimal256::one();
+        }
+        Decimal256::from_ratio(self.total_tokens, self.total_staked)
+    }
+
+    /// Shares minted for tokens added to the pool, rounded down
+    pub fn shares_for_tokens(&self, tokens: Uint128) -> Uint128 {
+        if self.total_staked.is_zero() || self.total_tokens.is_zero() {
+            return tokens;
+        }
+        tokens.multiply_ratio(self.total_staked, self.total_tokens)
+    }
+
+    /// Shares burned for tokens taken out of the pool, rounded up
+    pub fn shares_for_tokens_ceil(&self, tokens: Uint128) -> Uint128 {
+        if self.total_staked.is_zero() || self.total_tokens.is_zero() {
+            return tokens;
+        }
+        let numerator = tokens.full_mul(self.total_staked);
+        let denominator = Uint256::from(self.total_tokens);
+        let shares = (numerator + denominator - Uint256::one()) / denominator;
+        shares.try_into().unwrap_or(Uint128::MAX)
+    }
+
+    /// Tokens backing shares, rounded down
+    pub fn tokens_for_shares(&self, shares: Uint128) -> Uint128 {
+        if self.total_staked.is_zero() {
+            return Uint128::zero();
+        }
+        shares.multiply_ratio(self.total_tokens, self.total_staked)
+    }
+
+    /// Lower the pool tokens to the delegated tokens if they fell short, i.e. after a slash
+    /// Returns the slashed amount
+    pub fn record_delegated_tokens(&mut self, delegated: Uint128) -> Uint128 {
+        let slashed = self.total_tokens.saturating_sub(delegated);
+        self.total_tokens -= slashed;
+        slashed
+    }
+
+    /// Reward index of a denom, zero before the first rewards in it
+    pub fn reward_index(&self, denom: &str) -> Decimal256 {
+        self.reward_indices.get(denom).copied().unwrap_or_default()
+    }
+
+    /// Update the staking reward index of a denom when new rewards are added
+    pub fn add_rewards(&mut self, denom: &str, reward_amount: Uint128) {
+        let Some(reward_per_token) = self.income_per_token(reward_amount) else {
+            return;
+        };
+
+        let index = self.reward_indices.entry(denom.to_string()).or_default();
+        *index = index.checked_add(reward_per_token).unwrap_or(*index);
+    }
+
+    /// Update the rental income index when new rental income is added
+    pub fn add_rental_income(&mut self, amount: Uint128) {
+        let Some(income_per_token) = self.income_per_token(amount) else {
             return;
+        };
+
+        self.rental_index = self
+            .rental_index
+            .checked_add(income_per_token)
+            .unwrap_or(self.rental_index);
+    }
+
+    /// Withhold the protocol fee from an income, the fee accrues in state
+    /// Returns the income left for stakers
+    pub fn withhold_fee(&mut self, denom: &str, amount: Uint128, fee_rate: Decimal) -> Uint128 {
+        let fee = amount.mul_floor(fee_rate);
+        if !fee.is_zero() {
+            *self.accrued_fees.entry(denom.to_string()).or_default() += fee;
         }
+        amount - fee
+    }
 
-        // new_index = old_index + (reward_amount / total_staked)
-        let reward_per_token = Decimal256::from_ratio(
-            Uint256::from(reward_amount),
-            Uint256::from(self.total_staked),
-        );
+    /// Increase of an index for an income, None if there is nothing to distribute
+    fn income_per_token(&self, amount: Uint128) -> Option<Decimal256> {
+        if self.total_staked.is_zero() || amount.is_zero() {
+            return None;
+        }
 
-        self.global_reward_index = self
-            .global_reward_index
-            .checked_add(reward_per_token)
-            .unwrap_or(self.global_reward_index);
+        // new_index = old_index + (amount / total_staked)
+        Some(Decimal256::from_ratio(
+            Uint256::from(amount),
+            Uint256::from(self.total_staked),
+        ))
     }
 }
 
@@ -235,16 +1119,24 @@ mod tests {
         staker.staked_amount = Uint128::new(1000);
 
         // Global index increased by 0.1 (meaning 0.1 tokens reward per staked token)
-        let global_index = Decimal256::from_ratio(1u128, 10u128);
-        let rewards = staker.calculate_pending_rewards(global_index);
+        let mut global_indices = BTreeMap::new();
+        global_indices.insert("uatom".to_string(), Decimal256::from_ratio(1u128, 10u128));
+        let rewards = staker.calculate_pending_rewards(&global_indices);
 
         // Expected: 1000 * 0.1 = 100
-        assert_eq!(rewards, Uint128::new(100));
+        assert_eq!(rewards, vec![Coin::new(100u128, "uatom")]);
 
-        // After updating index, pending rewards should be 0
-        staker.update_index(global_index);
-        let rewards_after = staker.calculate_pending_rewards(global_index);
-        assert_eq!(rewards_after, Uint128::zero());
+        // After updating index, pending rewards should be empty
+        let mut state = State::new();
+        state.reward_indices = global_indices.clone();
+        staker.update_index(&state);
+        let rewards_after = staker.calculate_pending_rewards(&global_indices);
+        assert!(rewards_after.is_empty());
+
+        // A new denom accrues from zero
+        global_indices.insert("uosmo".to_string(), Decimal256::percent(5));
+        let rewards = staker.calculate_pending_rewards(&global_indices);
+        assert_eq!(rewards, vec![Coin::new(50u128, "uosmo")]);
     }
 
     #[test]
@@ -253,10 +1145,230 @@ mod tests {
         state.total_staked = Uint128::new(1000);
 
         // Add 100 tokens as rewards
-        state.add_rewards(Uint128::new(100));
+        state.add_rewards("uatom", Uint128::new(100));
 
         // Expected: 100 / 1000 = 0.1
         let expected = Decimal256::from_ratio(1u128, 10u128);
-        assert_eq!(state.global_reward_index, expected);
+        assert_eq!(state.reward_index("uatom"), expected);
+        assert_eq!(state.reward_index("uosmo"), Decimal256::zero());
+    }
+
+    #[test]
+    fn test_state_exchange_rate() {
+        let mut state = State::new();
+        assert_eq!(state.exchange_rate(), Decimal256::one());
+        assert_eq!(
+            state.shares_for_tokens(Uint128::new(100)),
+            Uint128::new(100)
+        );
+
+        state.total_staked = Uint128::new(1000);
+        state.total_tokens = Uint128::new(1000);
+        assert_eq!(
+            state.record_delegated_tokens(Uint128::new(1200)),
+            Uint128::zero()
+        );
+
+        // A 10% slash lowers the tokens backing every share
+        assert_eq!(
+            state.record_delegated_tokens(Uint128::new(900)),
+            Uint128::new(100)
+        );
+        assert_eq!(state.exchange_rate(), Decimal256::percent(90));
+        assert_eq!(state.tokens_for_shares(Uint128::new(100)), Uint128::new(90));
+        assert_eq!(
+            state.shares_for_tokens(Uint128::new(100)),
+            Uint128::new(111)
+        );
+        assert_eq!(
+            state.shares_for_tokens_ceil(Uint128::new(100)),
+            Uint128::new(112)
+        );
+    }
+
+    #[test]
+    fn test_voting_session_deployed() {
+        let locker = Addr::unchecked("locker");
+        let other = Addr::unchecked("other");
+        let mut session = VotingSession {
+            proposal_id: 1,
+            locker_addresses: vec![(1, locker.clone()), (3, other.clone())],
+            validator_lockers: vec![],
+            weighted_options: None,
+            is_active: true,
+            rented_amount: Uint128::zero(),
+            auction: None,
+            voting_end_time: Timestamp::from_seconds(100),
+            keeper_reserve: Uint128::zero(),
+            deployed: vec![],
+        };
+
+        session.deploy(&locker, Uint128::new(100));
+        session.deploy(&other, Uint128::new(50));
+        session.deploy(&locker, Uint128::new(20));
+        assert_eq!(session.deployed_in(&locker), Uint128::new(120));
+        assert_eq!(session.deployed_amount(), Uint128::new(170));
+
+        // Releasing a locker returns all of its stake
+        assert_eq!(session.release(&locker), Uint128::new(120));
+        assert_eq!(session.release(&locker), Uint128::zero());
+        assert_eq!(session.deployed_amount(), Uint128::new(50));
+    }
+
+    #[test]
+    fn test_state_withhold_fee() {
+        let mut state = State::new();
+
+        // 10% of 105 is 10.5, the fee is rounded down
+        let income = state.withhold_fee("uatom", Uint128::new(105), Decimal::percent(10));
+        assert_eq!(income, Uint128::new(95));
+        assert_eq!(state.accrued_fees["uatom"], Uint128::new(10));
+    }
+
+    #[test]
+    fn test_staker_pending_income() {
+        let mut state = State::new();
+        state.total_staked = Uint128::new(1000);
+        state.add_rewards("uosmo", Uint128::new(20));
+        state.add_rental_income(Uint128::new(50));
+
+        let mut staker = Staker::new();
+        staker.staked_amount = Uint128::new(500);
+
+        // Rental income is tracked apart from the staking rewards
+        assert_eq!(state.reward_index("uatom"), Decimal256::zero());
+        assert_eq!(
+            staker.calculate_pending_rental_income(state.rental_index),
+            Uint128::new(25)
+        );
+        assert_eq!(
+            staker.calculate_pending_income(&state, "uatom"),
+            vec![Coin::new(25u128, "uatom"), Coin::new(10u128, "uosmo")]
+        );
+
+        state.add_rewards("uatom", Uint128::new(100));
+        assert_eq!(
+            staker.calculate_pending_income(&state, "uatom"),
+            vec![Coin::new(75u128, "uatom"), Coin::new(10u128, "uosmo")]
+        );
+    }
+
+    #[test]
+    fn test_flat_pricing() {
+        let model = PricingModel::Flat {
+            price_per_vp: Decimal256::permille(100),
+        };
+
+        let zero = Uint128::zero();
+
+        // 1 VP = 0.1 token, so 15 VP costs 1.5 rounded up to 2
+        assert_eq!(
+            model.cost(Uint128::new(15), zero, zero).unwrap(),
+            Uint128::new(2)
+        );
+        assert_eq!(
+            model.cost(Uint128::new(1000), zero, zero).unwrap(),
+            Uint128::new(100)
+        );
+
+        // Paying the quoted cost always buys at least the quoted VP
+        assert_eq!(
+            model.vp_for_payment(Uint128::new(2), zero, zero).unwrap(),
+            Uint128::new(20)
+        );
+        assert_eq!(
+            model.vp_for_payment(Uint128::new(100), zero, zero).unwrap(),
+            Uint128::new(1000)
+        );
+    }
+
+    #[test]
+    fn test_linear_pricing_curve() {
+        // Price goes from 0.1 at zero utilization to 1.0 at full utilization
+        let model = PricingModel::Linear {
+            base_price: Decimal256::percent(10),
+            slope: Decimal256::percent(90),
+        };
+        let total_tokens = Uint128::new(1000);
+
+        // Renting the whole pool: 0.1 * 1000 + 0.9 * 1000 / 2 = 550
+        let full = model
+            .cost(Uint128::new(1000), Uint128::zero(), total_tokens)
+            .unwrap();
+        assert_eq!(full, Uint128::new(550));
+
+        // The second half of the pool is more expensive than the first half
+        let first_half = model
+            .cost(Uint128::new(500), Uint128::zero(), total_tokens)
+            .unwrap();
+        let second_half = model
+            .cost(Uint128::new(500), Uint128::new(500), total_tokens)
+            .unwrap();
+        assert_eq!(first_half, Uint128::new(163));
+        assert_eq!(second_half, Uint128::new(388));
+        assert!(second_half > first_half);
+
+        // The inverse returns the largest amount the payment covers
+        assert_eq!(
+            model
+                .vp_for_payment(Uint128::new(550), Uint128::zero(), total_tokens)
+                .unwrap(),
+            Uint128::new(1000)
+        );
+        assert_eq!(
+            model
+                .vp_for_payment(Uint128::new(388), Uint128::new(500), total_tokens)
+                .unwrap(),
+            Uint128::new(500)
+        );
+    }
+
+    #[test]
+    fn test_exponential_pricing_curve() {
+        let model = PricingModel::Exponential {
+            base_price: Decimal256::one(),
+            steepness: Decimal256::one(),
+        };
+        let total_tokens = Uint128::new(1000);
+
+        // Renting the whole pool: 1000 * (e - 1) = 1718.28...
+        let full = model
+            .cost(Uint128::new(1000), Uint128::zero(), total_tokens)
+            .unwrap();
+        assert_eq!(full, Uint128::new(1719));
+        assert_eq!(
+            model
+                .vp_for_payment(full, Uint128::zero(), total_tokens)
+                .unwrap(),
+            Uint128::new(1000)
+        );
+
+        // Steepness is capped
+        let too_steep = PricingModel::Exponential {
+            base_price: Decimal256::one(),
+            steepness: Decimal256::percent(2100),
+        };
+        assert!(too_steep.validate().is_err());
+    }
+
+    #[test]
+    fn test_rental_pricing_overrides() {
+        let mut pricing = RentalPricing::default();
+        let expensive = PricingModel::Flat {
+            price_per_vp: Decimal256::percent(250),
+        };
+        pricing.overrides.push((7, expensive.clone()));
+        pricing.validate().unwrap();
+
+        assert_eq!(pricing.for_proposal(7), &expensive);
+        assert_eq!(pricing.for_proposal(8), &RentalPricing::default().default);
+
+        // Duplicate overrides and zero prices are rejected
+        pricing.overrides.push((7, expensive));
+        assert!(pricing.validate().is_err());
+        let free = PricingModel::Flat {
+            price_per_vp: Decimal256::zero(),
+        };
+        assert!(free.validate().is_err());
     }
 }
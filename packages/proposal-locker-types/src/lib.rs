@@ -1,5 +1,5 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal, StdError, StdResult, Uint128};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -11,6 +11,60 @@ pub struct InstantiateMsg {
     pub validator: String,
     /// The manager address (only address allowed to deposit and destroy)
     pub manager: String,
+    /// The bonded denom of the chain, used for rewards and tokenization
+    pub staking_denom: String,
+    /// Optional split vote, cast as MsgVoteWeighted instead of a single vote_option
+    pub weights: Option<Vec<WeightedVoteOption>>,
+}
+
+/// Weight given to a vote option in a split vote
+#[cw_serde]
+pub struct WeightedVoteOption {
+    pub option: i32,
+    pub weight: Decimal,
+}
+
+/// Validate the weights of a split vote
+/// Weights must be positive, for distinct valid options, and sum to 1
+pub fn validate_weights(weights: &[WeightedVoteOption]) -> StdResult<()> {
+    if weights.is_empty() {
+        return Err(StdError::generic_err(
+            "At least one weighted option is required",
+        ));
+    }
+
+    let mut total = Decimal::zero();
+    for (i, weighted) in weights.iter().enumerate() {
+        // VOTE_OPTION_UNSPECIFIED (0) and negative options are rejected by gov
+        if weighted.option <= 0 {
+            return Err(StdError::generic_err(format!(
+                "Invalid vote option {}",
+                weighted.option
+            )));
+        }
+        if weighted.weight.is_zero() {
+            return Err(StdError::generic_err(format!(
+                "Weight of option {} must be greater than zero",
+                weighted.option
+            )));
+        }
+        if weights[..i].iter().any(|w| w.option == weighted.option) {
+            return Err(StdError::generic_err(format!(
+                "Duplicate option {}",
+                weighted.option
+            )));
+        }
+        total = total.checked_add(weighted.weight)?;
+    }
+
+    if total != Decimal::one() {
+        return Err(StdError::generic_err(format!(
+            "Weights must sum to 1, got {}",
+            total
+        )));
+    }
+
+    Ok(())
 }
 
 #[cw_serde]
@@ -19,10 +73,28 @@ pub enum ExecuteMsg {
     /// Only callable by manager
     DepositLsmShares {},
 
+    /// Replace the split vote of the locker and re-cast it
+    /// Only callable by manager
+    UpdateVote { weights: Vec<WeightedVoteOption> },
+
+    /// Re-cast the vote of the locker, e.g. when the previous vote was not recorded
+    /// Only callable by manager
+    ReVote {},
+
     /// Destroy the contract after proposal is finished
     /// Claims rewards, tokenizes all delegations, and sends everything to manager
     /// Only callable by manager
     Destroy {},
+
+    /// Forward the non-LSM balances of the locker to the manager as rewards
+    /// Picks up rewards the manager rejected until it accepts their denom
+    /// Callable by anyone
+    ForwardRewards {},
+
+    /// Destroy the contract without waiting for the proposal to finish
+    /// Escape hatch for a stuck proposal, same flow as Destroy
+    /// Only callable by manager
+    EmergencyDestroy {},
 }
 
 #[cw_serde]
@@ -41,8 +113,10 @@ pub enum QueryMsg {
 pub struct ConfigResponse {
     pub proposal_id: u64,
     pub vote_option: i32,
+    pub weights: Option<Vec<WeightedVoteOption>>,
     pub validator: String,
     pub manager: Addr,
+    pub staking_denom: String,
     pub total_staked: Uint128,
     pub has_voted: bool,
 }
@@ -56,18 +130,27 @@ pub struct TotalVotingPowerResponse {
 pub struct Config {
     pub proposal_id: u64,
     pub vote_option: i32,
+    /// Split vote cast instead of vote_option, if any
+    pub weights: Option<Vec<WeightedVoteOption>>,
     pub validator: String,
     pub manager: Addr,
+    pub staking_denom: String,
 }
 
 #[cw_serde]
 pub struct State {
     /// Total amount staked (voting power)
     pub total_staked: Uint128,
-    /// Whether the initial vote has been cast
+    /// Whether a vote of the locker has been recorded by the gov module
     pub has_voted: bool,
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl State {
     pub fn new() -> Self {
         Self {
@@ -83,3 +166,31 @@ pub struct LsmShareInfo {
     pub validator: String,
     pub record_id: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weighted(option: i32, percent: u64) -> WeightedVoteOption {
+        WeightedVoteOption {
+            option,
+            weight: Decimal::percent(percent),
+        }
+    }
+
+    #[test]
+    fn test_validate_weights() {
+        assert!(validate_weights(&[weighted(1, 100)]).is_ok());
+        assert!(validate_weights(&[weighted(1, 60), weighted(3, 40)]).is_ok());
+
+        // No options, invalid options, zero weights, duplicate options, and sums other than 1
+        // are rejected
+        assert!(validate_weights(&[]).is_err());
+        assert!(validate_weights(&[weighted(0, 100)]).is_err());
+        assert!(validate_weights(&[weighted(-1, 50), weighted(1, 50)]).is_err());
+        assert!(validate_weights(&[weighted(1, 100), weighted(2, 0)]).is_err());
+        assert!(validate_weights(&[weighted(1, 50), weighted(1, 50)]).is_err());
+        assert!(validate_weights(&[weighted(1, 50), weighted(2, 40)]).is_err());
+        assert!(validate_weights(&[weighted(1, 60), weighted(2, 60)]).is_err());
+    }
+}